@@ -0,0 +1,266 @@
+use std::collections::BTreeMap;
+
+use egui_node_graph::NodeId;
+
+use crate::sdf::expr::Value;
+
+/// How a track moves from one key to the next
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "persistence", derive(serde::Serialize, serde::Deserialize))]
+pub enum Interpolation {
+    #[default]
+    Linear,
+    /// Holds the value until the next key
+    Step,
+    /// Eases out of and into the keys. The handles are the fraction of the
+    /// segment they reach into, both in `0..=1`.
+    Bezier { ease_out: f32, ease_in: f32 },
+}
+
+impl Interpolation {
+    pub const BEZIER: Interpolation = Interpolation::Bezier {
+        ease_out: 1.0 / 3.0,
+        ease_in: 1.0 / 3.0,
+    };
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Interpolation::Linear => "Linear",
+            Interpolation::Step => "Step",
+            Interpolation::Bezier { .. } => "Bezier",
+        }
+    }
+
+    /// Maps the progress `u` through a segment to the blend factor between its keys
+    fn blend(&self, u: f32) -> f32 {
+        match *self {
+            Interpolation::Linear => u,
+            Interpolation::Step => 0.0,
+            Interpolation::Bezier { ease_out, ease_in } => {
+                bezier_ease(u, ease_out.clamp(0.0, 1.0), 1.0 - ease_in.clamp(0.0, 1.0))
+            }
+        }
+    }
+}
+
+/// Height of the cubic Bezier from (0, 0) to (1, 1) with flat handles ending
+/// at `x1` and `x2`, at the point where its horizontal position is `u`
+fn bezier_ease(u: f32, x1: f32, x2: f32) -> f32 {
+    let cubic = |s: f32, p1: f32, p2: f32| {
+        let r = 1.0 - s;
+        3.0 * r * r * s * p1 + 3.0 * r * s * s * p2 + s * s * s
+    };
+    // x(s) is monotonic for handles inside the segment, so bisection always converges
+    let (mut low, mut high) = (0.0f32, 1.0f32);
+    for _ in 0..32 {
+        let mid = 0.5 * (low + high);
+        if cubic(mid, x1, x2) < u {
+            low = mid;
+        } else {
+            high = mid;
+        }
+    }
+    cubic(0.5 * (low + high), 0.0, 1.0)
+}
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "persistence", derive(serde::Serialize, serde::Deserialize))]
+pub struct Keyframe {
+    pub frame: f32,
+    pub value: Value,
+    /// Interpolation towards the next key
+    pub interpolation: Interpolation,
+}
+
+/// Keys of one parameter, sorted by frame
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "persistence", derive(serde::Serialize, serde::Deserialize))]
+pub struct Track {
+    keys: Vec<Keyframe>,
+}
+
+impl Track {
+    pub fn keys(&self) -> &[Keyframe] {
+        &self.keys
+    }
+
+    fn position(&self, frame: f32) -> Result<usize, usize> {
+        self.keys
+            .binary_search_by(|key| key.frame.total_cmp(&frame))
+    }
+
+    pub fn key_at(&self, frame: f32) -> Option<&Keyframe> {
+        self.position(frame).ok().map(|i| &self.keys[i])
+    }
+
+    /// Key at or before `frame`, the one whose interpolation applies there
+    pub fn key_before_mut(&mut self, frame: f32) -> Option<&mut Keyframe> {
+        let i = match self.position(frame) {
+            Ok(i) => i,
+            Err(i) => i.checked_sub(1)?,
+        };
+        self.keys.get_mut(i)
+    }
+
+    /// Adds a key at `frame` or replaces the value of the one already there
+    pub fn set_key(&mut self, frame: f32, value: Value) {
+        match self.position(frame) {
+            Ok(i) => self.keys[i].value = value,
+            Err(i) => self.keys.insert(
+                i,
+                Keyframe {
+                    frame,
+                    value,
+                    interpolation: Interpolation::default(),
+                },
+            ),
+        }
+    }
+
+    pub fn remove_key(&mut self, frame: f32) {
+        if let Ok(i) = self.position(frame) {
+            self.keys.remove(i);
+        }
+    }
+
+    /// Value at `frame`, holding the first and last keys outside their range
+    pub fn sample(&self, frame: f32) -> Option<Value> {
+        let i = match self.position(frame) {
+            Ok(i) => return Some(self.keys[i].value),
+            Err(i) => i,
+        };
+        match (i.checked_sub(1).map(|i| &self.keys[i]), self.keys.get(i)) {
+            (Some(a), Some(b)) => {
+                let u = (frame - a.frame) / (b.frame - a.frame);
+                Some(a.value.lerp(b.value, a.interpolation.blend(u)))
+            }
+            (Some(key), None) | (None, Some(key)) => Some(key.value),
+            (None, None) => None,
+        }
+    }
+}
+
+/// Input parameter a track drives
+#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "persistence", derive(serde::Serialize, serde::Deserialize))]
+pub struct TrackId {
+    pub node: NodeId,
+    pub param: String,
+}
+
+/// Keyframed inline values of the node graph. Keys are placed on whole frames.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "persistence", derive(serde::Serialize, serde::Deserialize))]
+pub struct Animation {
+    pub fps: f32,
+    /// Sorted so project files come out the same every time they are saved
+    tracks: BTreeMap<TrackId, Track>,
+}
+
+impl Default for Animation {
+    fn default() -> Self {
+        Self {
+            fps: 24.0,
+            tracks: BTreeMap::new(),
+        }
+    }
+}
+
+impl Animation {
+    pub fn tracks(&self) -> impl Iterator<Item = (&TrackId, &Track)> {
+        self.tracks.iter()
+    }
+
+    pub fn track(&self, node: NodeId, param: &str) -> Option<&Track> {
+        self.tracks.get(&TrackId {
+            node,
+            param: param.to_string(),
+        })
+    }
+
+    pub fn track_mut(&mut self, node: NodeId, param: &str) -> Option<&mut Track> {
+        self.tracks.get_mut(&TrackId {
+            node,
+            param: param.to_string(),
+        })
+    }
+
+    pub fn set_key(&mut self, node: NodeId, param: &str, frame: f32, value: Value) {
+        self.tracks
+            .entry(TrackId {
+                node,
+                param: param.to_string(),
+            })
+            .or_default()
+            .set_key(frame, value);
+    }
+
+    /// Removes the key at `frame`, and the whole track once it has no keys left
+    pub fn remove_key(&mut self, node: NodeId, param: &str, frame: f32) {
+        let id = TrackId {
+            node,
+            param: param.to_string(),
+        };
+        if let Some(track) = self.tracks.get_mut(&id) {
+            track.remove_key(frame);
+            if track.keys.is_empty() {
+                self.tracks.remove(&id);
+            }
+        }
+    }
+
+    pub fn remove_track(&mut self, id: &TrackId) {
+        self.tracks.remove(id);
+    }
+
+    /// Drops the tracks of parameters that no longer exist
+    pub fn retain(&mut self, mut exists: impl FnMut(&TrackId) -> bool) {
+        self.tracks.retain(|id, _| exists(id));
+    }
+
+    pub fn sample(&self, node: NodeId, param: &str, frame: f32) -> Option<Value> {
+        self.track(node, param)?.sample(frame)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn track(interpolation: Interpolation) -> Track {
+        let mut track = Track::default();
+        track.set_key(10.0, Value::Scalar(1.0));
+        track.set_key(20.0, Value::Scalar(3.0));
+        track.key_before_mut(10.0).unwrap().interpolation = interpolation;
+        track
+    }
+
+    #[test]
+    fn holds_outside_keys() {
+        let track = track(Interpolation::Linear);
+        assert_eq!(track.sample(0.0), Some(Value::Scalar(1.0)));
+        assert_eq!(track.sample(30.0), Some(Value::Scalar(3.0)));
+        assert_eq!(Track::default().sample(0.0), None);
+    }
+
+    #[test]
+    fn interpolates_between_keys() {
+        assert_eq!(
+            track(Interpolation::Linear).sample(15.0),
+            Some(Value::Scalar(2.0))
+        );
+        assert_eq!(
+            track(Interpolation::Step).sample(19.0),
+            Some(Value::Scalar(1.0))
+        );
+        let bezier = track(Interpolation::BEZIER);
+        let Some(Value::Scalar(early)) = bezier.sample(11.0) else {
+            panic!("scalar track")
+        };
+        let Some(Value::Scalar(middle)) = bezier.sample(15.0) else {
+            panic!("scalar track")
+        };
+        assert!(early < 1.2 && early > 1.0);
+        assert!((middle - 2.0).abs() < 1e-4);
+    }
+}
@@ -1,6 +1,7 @@
 use nalgebra::{self};
 
 /// 3D Camera
+#[cfg_attr(feature = "persistence", derive(serde::Serialize, serde::Deserialize))]
 pub struct Camera {
     /// Position in space
     pub pos: nalgebra::Point3<f32>,
@@ -0,0 +1,368 @@
+//! Subcommands run from the command line without opening a window, for
+//! batch exports on machines with no display.
+//!
+//! Each command loads a project, evaluates one of its nodes to a volume and
+//! either prints the fragment shader of the volume, meshes it to a file or
+//! prints measurements of its mesh.
+use std::{collections::HashMap, path::PathBuf, time::Instant};
+
+use egui_node_graph::NodeId;
+use nalgebra::Vector3;
+use thiserror::Error;
+
+use crate::{
+    export::{ExportError, ExportFormat, ExportSettings},
+    mesher::Mesher,
+    node_graph::{evaluate_node, EvalContext, N3DNodeTemplate, N3DValueType, NodeError},
+    nodes::{volume::SDFViewer, NodeDefinition},
+    project::{Project, ProjectError},
+    renderer::mesh::Mesh,
+    sdf::{cpu::CpuEvaluator, ir::SDFVolume, SDFBuilder},
+};
+
+pub const USAGE: &str = "\
+Usage:
+  n3d                               open the editor
+  n3d glsl <project> [options]      print the fragment shader of a node
+  n3d mesh <project> <file> [options]
+                                    write a mesh of a node, the format
+                                    following the extension of <file>
+  n3d stats <project> [options]     print measurements of a mesh of a node
+  n3d help, --help, -h              print this message
+
+Options:
+  --node <name>          node to evaluate, by the name given to it in the
+                         editor or else by its type if only one node has
+                         it, by default the active node of the project or
+                         else its only SDF Viewer
+  --time <seconds>       scene time, 0 by default
+  --resolution <cells>   cells along each axis of the meshed region, 64
+  --min <x,y,z>          lower corner of the meshed region, -2,-2,-2
+  --max <x,y,z>          upper corner of the meshed region, 2,2,2
+  --iso <distance>       distance of the meshed surface, 0
+  --mesher <name>        dual-contouring (default) or marching-cubes
+  --tolerance <distance> simplification of dual contouring, 0.001
+  --format <name>        stl, stl-ascii, obj, ply or glb, overriding the
+                         extension of <file>";
+
+#[derive(Error, Debug)]
+pub enum CliError {
+    #[error("{0}\n\n{USAGE}")]
+    Usage(String),
+    #[error(transparent)]
+    Project(#[from] ProjectError),
+    #[error("{0}")]
+    Node(String),
+    #[error("could not evaluate node: {0}")]
+    Eval(#[from] NodeError),
+    #[error(transparent)]
+    Export(#[from] ExportError),
+}
+
+fn usage<T>(message: impl Into<String>) -> Result<T, CliError> {
+    Err(CliError::Usage(message.into()))
+}
+
+/// Options shared by all commands
+struct Options {
+    node: Option<String>,
+    time: f32,
+    settings: ExportSettings,
+    format: Option<ExportFormat>,
+}
+
+fn parse_number<T: std::str::FromStr>(option: &str, value: &str) -> Result<T, CliError> {
+    value
+        .parse()
+        .or_else(|_| usage(format!("invalid value {:?} for {}", value, option)))
+}
+
+fn parse_vector(option: &str, value: &str) -> Result<Vector3<f32>, CliError> {
+    let components = value
+        .split(',')
+        .map(|component| parse_number(option, component.trim()))
+        .collect::<Result<Vec<f32>, _>>()?;
+    match components.as_slice() {
+        [x, y, z] => Ok(Vector3::new(*x, *y, *z)),
+        _ => usage(format!("{} takes three components, as in 1,2,3", option)),
+    }
+}
+
+/// Splits `args` into the positional arguments and the options
+fn parse_args(args: &[String]) -> Result<(Vec<String>, Options), CliError> {
+    let mut positional = Vec::new();
+    let mut options = Options {
+        node: None,
+        time: 0.0,
+        settings: ExportSettings::default(),
+        format: None,
+    };
+    let mut tolerance = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if !arg.starts_with("--") {
+            positional.push(arg.clone());
+            continue;
+        }
+        let Some(value) = args.next() else {
+            return usage(format!("missing value for {}", arg));
+        };
+        let grid = &mut options.settings.grid;
+        match arg.as_str() {
+            "--node" => options.node = Some(value.clone()),
+            "--time" => options.time = parse_number(arg, value)?,
+            "--resolution" => grid.resolution = parse_number(arg, value)?,
+            "--min" => grid.min = parse_vector(arg, value)?,
+            "--max" => grid.max = parse_vector(arg, value)?,
+            "--iso" => grid.iso_level = parse_number(arg, value)?,
+            "--tolerance" => tolerance = Some(parse_number(arg, value)?),
+            "--mesher" => {
+                options.settings.mesher = match value.as_str() {
+                    "dual-contouring" => Mesher::default(),
+                    "marching-cubes" => Mesher::MarchingCubes,
+                    _ => return usage(format!("unknown mesher {:?}", value)),
+                }
+            }
+            "--format" => {
+                options.format = Some(match value.as_str() {
+                    "stl" => ExportFormat::BinaryStl,
+                    "stl-ascii" => ExportFormat::AsciiStl,
+                    "obj" => ExportFormat::Obj,
+                    "ply" => ExportFormat::Ply,
+                    "glb" => ExportFormat::Glb,
+                    _ => return usage(format!("unknown format {:?}", value)),
+                })
+            }
+            _ => return usage(format!("unknown option {}", arg)),
+        }
+    }
+    if let Some(tolerance) = tolerance {
+        match &mut options.settings.mesher {
+            Mesher::DualContouring { tolerance: value } => *value = tolerance,
+            Mesher::MarchingCubes => return usage("--tolerance only applies to dual contouring"),
+        }
+    }
+    if options.settings.grid.resolution == 0 {
+        return usage("--resolution must be at least 1");
+    }
+    Ok((positional, options))
+}
+
+/// Node of `project` named `name`, or the one the project would show
+fn find_node(project: &Project, name: Option<&str>) -> Result<NodeId, CliError> {
+    let graph = &project.editor.graph;
+    let named = |name: &str| -> Vec<NodeId> {
+        let named: Vec<NodeId> = graph
+            .iter_nodes()
+            .filter(|node| graph[*node].user_data.name() == name)
+            .collect();
+        if !named.is_empty() {
+            return named;
+        }
+        // Nodes nobody named are still found by their type
+        graph
+            .iter_nodes()
+            .filter(|node| graph[*node].label == name)
+            .collect()
+    };
+    let candidates: Vec<NodeId> = match name {
+        Some(name) => named(name),
+        None => match project.user_state.active_node {
+            Some(node) if graph.nodes.contains_key(node) => vec![node],
+            _ => graph
+                .iter_nodes()
+                .filter(|node| {
+                    matches!(graph[*node].user_data.template(),
+                        N3DNodeTemplate::Node(definition) if definition.name() == SDFViewer.name())
+                })
+                .collect(),
+        },
+    };
+    let described = match name {
+        Some(name) => format!("nodes named {:?}", name),
+        None => "SDF Viewer nodes and no active node".to_string(),
+    };
+    match candidates.as_slice() {
+        [node] => Ok(*node),
+        [] => Err(CliError::Node(format!("the project has no {}", described))),
+        _ => Err(CliError::Node(format!(
+            "the project has {} {}, pick one with --node",
+            candidates.len(),
+            described
+        ))),
+    }
+}
+
+/// Volume output by the chosen node of the project at `path`
+fn evaluate(path: &str, options: &Options) -> Result<SDFVolume, CliError> {
+    let project = Project::open(path)?;
+    let node = find_node(&project, options.node.as_deref())?;
+    let ctx = EvalContext {
+        animation: &project.user_state.animation,
+        frame: options.time * project.user_state.animation.fps,
+        groups: &project.user_state.groups,
+    };
+    match evaluate_node(&project.editor.graph, node, ctx, &mut HashMap::new())? {
+        N3DValueType::SDFVolume { value } => Ok(value),
+        _ => Err(CliError::Node(format!(
+            "{} does not output a volume",
+            project.editor.graph[node].label
+        ))),
+    }
+}
+
+/// Runs the command in `args`, the arguments after the program name
+pub fn run(args: &[String]) -> Result<(), CliError> {
+    // Asking for help anywhere wins over the options, which all take values
+    if args.iter().any(|arg| arg == "--help" || arg == "-h") {
+        println!("{}", USAGE);
+        return Ok(());
+    }
+    let (positional, options) = parse_args(args)?;
+    match positional
+        .iter()
+        .map(String::as_str)
+        .collect::<Vec<_>>()
+        .as_slice()
+    {
+        ["glsl", project] => {
+            let volume = evaluate(project, &options)?;
+            print!("{}", SDFBuilder::new().build_volume(&volume, options.time));
+        }
+        ["mesh", project, file] => {
+            let path = PathBuf::from(file);
+            let format = match options.format.or_else(|| ExportFormat::from_path(&path)) {
+                Some(format) => format,
+                None => return usage(format!("unknown mesh format of {}", file)),
+            };
+            let volume = evaluate(project, &options)?;
+            let mesh = options.settings.mesh(&volume, options.time)?;
+            format.save(&mesh, &path)?;
+            println!(
+                "Wrote {} triangles to {}",
+                mesh.mesh.elements.len() / 3,
+                path.display()
+            );
+        }
+        ["stats", project] => {
+            let volume = evaluate(project, &options)?;
+            let start = Instant::now();
+            let mesh = options.settings.mesh(&volume, options.time)?;
+            let elapsed = start.elapsed();
+            let sdf = CpuEvaluator::new(&volume, options.time).map_err(ExportError::from)?;
+            print_stats(&mesh.mesh, &sdf, &options.settings);
+            println!("meshed in:   {:.3} s", elapsed.as_secs_f32());
+        }
+        ["help"] => println!("{}", USAGE),
+        [] => return usage("missing command"),
+        [command, ..] => return usage(format!("unknown command or arguments for {:?}", command)),
+    }
+    Ok(())
+}
+
+fn print_stats(mesh: &Mesh, sdf: &CpuEvaluator, settings: &ExportSettings) {
+    let mut area = 0.0;
+    let mut volume = 0.0;
+    for triangle in mesh.elements.chunks_exact(3) {
+        let [a, b, c] = [0, 1, 2].map(|i| mesh.verts[triangle[i] as usize].pos.coords);
+        area += (b - a).cross(&(c - a)).norm() / 2.0;
+        // Signed volumes of the tetrahedra to the origin sum to the enclosed one
+        volume += a.dot(&b.cross(&c)) / 6.0;
+    }
+    let (min, max) = mesh.verts.iter().fold(
+        (
+            Vector3::from_element(f32::MAX),
+            Vector3::from_element(f32::MIN),
+        ),
+        |(min, max), vert| (min.inf(&vert.pos.coords), max.sup(&vert.pos.coords)),
+    );
+    let vector = |v: Vector3<f32>| format!("{:.4} {:.4} {:.4}", v.x, v.y, v.z);
+    let grid = &settings.grid;
+    println!("mesher:      {}", settings.mesher.label());
+    println!("resolution:  {}", grid.resolution);
+    println!("triangles:   {}", mesh.elements.len() / 3);
+    println!("vertices:    {}", mesh.verts.len());
+    println!("bounds min:  {}", vector(min));
+    println!("bounds max:  {}", vector(max));
+    println!("size:        {}", vector(max - min));
+    println!("area:        {:.6}", area);
+    println!("volume:      {:.6}", volume);
+    println!("distortion:  {}", sdf.distortion());
+    let cell = grid.cell();
+    let clipped = (0..3).any(|i| min[i] < grid.min[i] + cell[i] || max[i] > grid.max[i] - cell[i]);
+    if clipped {
+        println!("warning:     the surface reaches the edge of the meshed region");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{run, CliError};
+    use crate::{
+        camera::Camera,
+        node_graph::{
+            tests::{add, connect},
+            MyEditorState, MyGraphState, N3DNodeTemplate, N3DValueType,
+        },
+        nodes::volume::{SDFSphere, SDFViewer},
+        project::Project,
+    };
+
+    #[test]
+    fn meshes_the_viewer_of_a_project() {
+        let mut state = MyEditorState::default();
+        let mut user_state = MyGraphState::default();
+        let sphere = add(
+            &mut state,
+            &mut user_state,
+            N3DNodeTemplate::Node(&SDFSphere),
+        );
+        let viewer = add(
+            &mut state,
+            &mut user_state,
+            N3DNodeTemplate::Node(&SDFViewer),
+        );
+        let radius = state.graph[sphere].get_input("radius").unwrap();
+        state.graph[radius].value = N3DValueType::Scalar { value: 1.0 };
+        connect(&mut state, sphere, viewer, "sdf");
+        // A second viewer makes the one to mesh ambiguous unless named
+        add(
+            &mut state,
+            &mut user_state,
+            N3DNodeTemplate::Node(&SDFViewer),
+        );
+        state.graph[viewer].user_data.set_name("Part".to_string());
+
+        let dir = std::env::temp_dir().join(format!("n3d-cli-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let project = dir.join("sphere.n3d");
+        Project::save(&project, &state, &user_state, &Camera::new()).unwrap();
+        let mesh = dir.join("sphere.stl");
+        let args = |args: &[&str]| args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>();
+        let project = project.to_str().unwrap();
+        run(&args(&[
+            "mesh",
+            project,
+            mesh.to_str().unwrap(),
+            "--node",
+            "Part",
+            "--resolution",
+            "16",
+        ]))
+        .unwrap();
+        let bytes = std::fs::read(&mesh).unwrap();
+        let triangles = u32::from_le_bytes(bytes[80..84].try_into().unwrap()) as usize;
+        assert!(triangles > 0);
+        assert_eq!(bytes.len(), 84 + 50 * triangles);
+
+        let missing = run(&args(&["mesh", project, "sphere.stl", "--node", "Teapot"]));
+        assert!(matches!(missing, Err(CliError::Node(_))));
+        let ambiguous = run(&args(&["mesh", project, "sphere.stl"]));
+        assert!(matches!(ambiguous, Err(CliError::Node(_))));
+        let unknown = run(&args(&["mesh", project, "sphere.txt"]));
+        assert!(matches!(unknown, Err(CliError::Usage(_))));
+        assert!(run(&args(&["--help"])).is_ok());
+        assert!(run(&args(&["mesh", project, "-h"])).is_ok());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
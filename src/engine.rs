@@ -88,8 +88,9 @@ impl EngineState {
     pub fn setup(&mut self) {
         let sdf = SDFBuilder::new()
             .build_with("sdf_box(p, vec3(1) - vec3(0.125)) - 0.125");
-        self.ray_marcher.shader =
-            Shader::new(std::include_str!("../res/shaders/ray.vert"), sdf.as_str());
+        if let Err(log) = self.ray_marcher.use_source(sdf.as_str()) {
+            eprintln!("{}", log);
+        }
         //println!("=============");
         //println!("{}", sdf);
         //println!("=============");
@@ -99,10 +100,9 @@ impl EngineState {
             0.01,
             100.0,
         );
-        self.ray_marcher.matcap.gen();
-        self.ray_marcher.matcap.set_unit(gl::TEXTURE15);
-        self.ray_marcher.matcap.bind().unwrap();
+        self.ray_marcher.gen_matcaps();
         self.ray_marcher.shader.enable();
+        self.ray_marcher.bind_matcaps();
         self.ray_marcher.shader.uniform_f32(
             "u_cam_zoom",
             (self.camera.pos - self.camera.look_at).magnitude(),
@@ -213,14 +213,29 @@ impl EngineState {
         {
             self.camera.reset();
         }
+        let control = self.input.keys.held(VirtualKeyCode::LControl)
+            || self.input.keys.held(VirtualKeyCode::RControl);
+        let shift = self.input.keys.held(VirtualKeyCode::LShift)
+            || self.input.keys.held(VirtualKeyCode::RShift);
         static mut WIREFRAME: bool = false;
         if self.input.keys.pressed(VirtualKeyCode::Z) {
-            unsafe {
-                WIREFRAME = !WIREFRAME;
-                if WIREFRAME {
-                    gl::PolygonMode(gl::FRONT_AND_BACK, gl::LINE);
-                } else {
-                    gl::PolygonMode(gl::FRONT_AND_BACK, gl::FILL);
+            if control {
+                // Text fields handle their own undo
+                if !self.ctx.wants_keyboard_input() {
+                    if shift {
+                        self.graph.redo();
+                    } else {
+                        self.graph.undo();
+                    }
+                }
+            } else {
+                unsafe {
+                    WIREFRAME = !WIREFRAME;
+                    if WIREFRAME {
+                        gl::PolygonMode(gl::FRONT_AND_BACK, gl::LINE);
+                    } else {
+                        gl::PolygonMode(gl::FRONT_AND_BACK, gl::FILL);
+                    }
                 }
             }
         }
@@ -239,10 +254,14 @@ impl EngineState {
             .uniform_mat3("u_cam_rot", &self.camera.rot);
         self.ray_marcher
             .shader
-            .uniform_f32("u_time", self.itime.elapsed().unwrap().as_secs_f32());
+            .uniform_f32("u_time", self.graph.time());
         self.ray_marcher
             .shader
             .uniform_f32("u_fov", self.camera.fov);
+        self.ray_marcher.shader.uniform_f32(
+            "u_cam_zoom",
+            (self.camera.pos - self.camera.look_at).magnitude(),
+        );
         self.ray_marcher.shader.uniform_f32("u_fillet", self.fillet);
         self.ray_marcher
             .shader
@@ -254,9 +273,7 @@ impl EngineState {
             "u_cam_translation",
             &(self.camera.look_at - nalgebra::Point3::new(0.0, 0.0, 0.0)),
         );
-        Texture::set_active_unit(gl::TEXTURE15);
-        self.ray_marcher.matcap.bind().unwrap();
-        self.ray_marcher.shader.uniform_tex("u_matcap", 15);
+        self.ray_marcher.bind_matcaps();
         //unsafe { gl::Disable(gl::DEPTH_TEST); }
         unsafe {
             gl::Enable(gl::DEPTH_TEST);
@@ -273,7 +290,7 @@ impl EngineState {
         self.shader.uniform_mat4("view", &self.camera.view());
         self.shader.uniform_mat4("persp", &self.camera.persp);
         self.shader
-            .uniform_f32("u_time", self.itime.elapsed().unwrap().as_secs_f32());
+            .uniform_f32("u_time", self.graph.time());
         self.grid.verts.iter().for_each(|v| {
             //println!("World Space: {:?}", nalgebra::Vector4::new(v.pos.x, v.pos.y, v.pos.z, 1.0));
             //println!("View Space: {:?}", nalgebra_glm::perspective_rh_no(self.resolution[0]/self.resolution[1], self.camera.fov.to_radians(), 0.01, 100.0) * self.camera.view() * nalgebra::Vector4::new(v.pos.x, v.pos.y, v.pos.z, 1.0));
@@ -424,22 +441,37 @@ impl EngineState {
                     .uniform_mat4("persp", &self.camera.persp);
                 let raw_input = self.egui_st.take_egui_input(&window.window);
                 let full_output = self.ctx.run(raw_input, |ctx| {
-                    if let Some(shader) = self.graph.update(ctx) {
-                        ////println!("{}", shader);
-                        //let shader = format!("({})", shader);
-                        let sdf = SDFBuilder::new()
-                            .build_with(shader.as_str());
-                        ////println!("{}", sdf);
-                        self.ray_marcher.shader.recompile(std::include_str!("../res/shaders/ray.vert"), sdf.as_str());
+                    let volume = self.graph.update(ctx, &mut self.camera);
+                    let time = self.graph.time();
+                    let lowered =
+                        volume.map(|volume| SDFBuilder::lower_volume_params(&volume, time));
+                    // A scene that does not compile keeps the previous program,
+                    // whose parameters are left as they were
+                    let mut compiled = false;
+                    let changed = match lowered {
+                        Some(ref lowered) => {
+                            let sdf = SDFBuilder::new().build_lowered(lowered);
+                            match self.ray_marcher.use_source(sdf.as_str()) {
+                                Ok(changed) => {
+                                    compiled = true;
+                                    changed
+                                }
+                                Err(log) => {
+                                    self.graph.show_shader_errors(&sdf, &log);
+                                    false
+                                }
+                            }
+                        }
+                        None => false,
+                    };
+                    if changed {
                         self.ray_marcher.shader.enable();
                         self.ray_marcher.shader.uniform_vec2(
                             "u_resolution",
                             self.resolution[0],
                             self.resolution[1],
                             );
-                        //self.ray_marcher.matcap.gen();
-                        self.ray_marcher.matcap.set_unit(gl::TEXTURE15);
-                        self.ray_marcher.matcap.bind().unwrap();
+                        self.ray_marcher.bind_matcaps();
                         self.ray_marcher.shader.enable();
                         self.ray_marcher.shader.uniform_f32(
                             "u_cam_zoom",
@@ -456,6 +488,17 @@ impl EngineState {
                             );
                         self.ray_marcher.shader.uniform_f32("u_fillet", self.fillet);
                     }
+                    // Only the values changed when the topology is the same, so
+                    // they are pushed every frame instead of recompiling
+                    if let Some(lowered) = lowered.filter(|_| compiled) {
+                        self.ray_marcher.shader.enable();
+                        self.ray_marcher
+                            .shader
+                            .uniform_f32v("u_params", &lowered.params);
+                        self.ray_marcher
+                            .shader
+                            .uniform_f32("u_distortion", lowered.distortion);
+                    }
                     // self.ray_marcher.shader =
                     //     Shader::new(std::include_str!("../res/shaders/ray.vert"), sdf.as_str());
                     egui::SidePanel::new(egui::panel::Side::Right, "Outliner")
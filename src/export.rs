@@ -0,0 +1,512 @@
+//! Writing meshes of the scene to files for slicers, CAD and game engines.
+//!
+//! Vertex colors are the linear colors of the materials at the surface.
+//! Formats with 8 bit or untyped colors get them in sRGB, glTF keeps them
+//! linear as its specification asks. glTF and OBJ also get one material per
+//! distinct material of the scene, with its roughness and, for the metal
+//! matcap, full metalness. Matcaps themselves have no equivalent there.
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    ops::Range,
+    path::{Path, PathBuf},
+};
+
+use nalgebra::Vector3;
+use thiserror::Error;
+
+use crate::{
+    mesher::{Grid, Mesher},
+    renderer::mesh::Mesh,
+    sdf::{
+        cpu::{CpuError, CpuEvaluator},
+        ir::{Matcap, Material, SDFVolume},
+    },
+};
+
+pub mod gltf;
+pub mod obj;
+pub mod ply;
+pub mod stl;
+
+#[derive(Error, Debug)]
+pub enum ExportError {
+    #[error("could not write mesh file: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("could not mesh the volume: {0}")]
+    Cpu(#[from] CpuError),
+    #[error("the active node has no volume to export")]
+    NoVolume,
+    #[error("the surface does not pass through the meshed region")]
+    Empty,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExportFormat {
+    BinaryStl,
+    AsciiStl,
+    Obj,
+    Ply,
+    Glb,
+}
+
+impl ExportFormat {
+    pub const ALL: [ExportFormat; 5] = [
+        Self::BinaryStl,
+        Self::AsciiStl,
+        Self::Obj,
+        Self::Ply,
+        Self::Glb,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::BinaryStl => "STL (binary)",
+            Self::AsciiStl => "STL (ASCII)",
+            Self::Obj => "OBJ",
+            Self::Ply => "PLY",
+            Self::Glb => "glTF (.glb)",
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::BinaryStl | Self::AsciiStl => "stl",
+            Self::Obj => "obj",
+            Self::Ply => "ply",
+            Self::Glb => "glb",
+        }
+    }
+
+    /// Format written to a file named `path`, binary for STL
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+        Self::ALL
+            .into_iter()
+            .find(|format| format.extension() == extension)
+    }
+
+    /// Writes `mesh` without any files it refers to, see [`Self::save`]
+    pub fn write<W: Write>(&self, mesh: &ExportMesh, writer: &mut W) -> io::Result<()> {
+        match self {
+            Self::BinaryStl => stl::write_binary(&mesh.mesh, writer),
+            Self::AsciiStl => stl::write_ascii(&mesh.mesh, writer),
+            Self::Obj => obj::write(mesh, writer, None),
+            Self::Ply => ply::write(&mesh.mesh, writer),
+            Self::Glb => gltf::write(mesh, writer),
+        }
+    }
+
+    /// Writes `mesh` to a new file at `path`, replacing any existing one.
+    /// OBJ materials go to a library of the same name ending in `.mtl`.
+    pub fn save(&self, mesh: &ExportMesh, path: &Path) -> Result<(), ExportError> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        if let Self::Obj = self {
+            let library = path.with_extension("mtl");
+            let mut library_writer = BufWriter::new(File::create(&library)?);
+            obj::write_library(mesh, &mut library_writer)?;
+            library_writer.flush()?;
+            let name = library.file_name().and_then(|name| name.to_str());
+            obj::write(mesh, &mut writer, name)?;
+        } else {
+            self.write(mesh, &mut writer)?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+/// `linear_to_srgb` of the shader, applied to each channel
+pub fn srgb(linear: Vector3<f32>) -> Vector3<f32> {
+    linear.map(|c| {
+        let c = c.clamp(0.0, 1.0);
+        if c <= 0.0031308 {
+            c * 12.92
+        } else {
+            c.powf(1.0 / 2.4) * 1.055 - 0.055
+        }
+    })
+}
+
+/// Material of the triangles of a [`Part`], evaluated at the time of export
+#[derive(Clone, Debug, PartialEq)]
+pub struct PartMaterial {
+    /// Linear color
+    pub color: Vector3<f32>,
+    pub roughness: f32,
+    pub matcap: Matcap,
+}
+
+impl PartMaterial {
+    fn new(material: &Material, time: f32) -> Self {
+        Self {
+            color: material.color.eval(time).to_vec3(),
+            roughness: material.roughness.eval(time).to_scalar().clamp(0.0, 1.0),
+            matcap: material.matcap,
+        }
+    }
+
+    /// Name of the material of part `index`, unique within a mesh
+    pub fn name(&self, index: usize) -> String {
+        format!("{}_{}", self.matcap.name().replace(' ', "_"), index)
+    }
+
+    /// Metalness for formats with physically based materials
+    pub fn metallic(&self) -> f32 {
+        match self.matcap {
+            Matcap::Metal => 1.0,
+            _ => 0.0,
+        }
+    }
+}
+
+/// Consecutive triangles of an [`ExportMesh`] sharing a material
+#[derive(Clone, Debug, PartialEq)]
+pub struct Part {
+    pub material: PartMaterial,
+    /// Range of the mesh elements holding the triangles
+    pub elements: Range<usize>,
+}
+
+/// Meshed scene with its triangles ordered by the material showing on them
+pub struct ExportMesh {
+    pub mesh: Mesh,
+    pub parts: Vec<Part>,
+}
+
+impl ExportMesh {
+    /// Groups the triangles of `mesh` by the material weighing the most at
+    /// their centers, as `sdf` has it at `time`
+    pub fn new(mut mesh: Mesh, sdf: &CpuEvaluator, time: f32) -> Self {
+        let mut materials: Vec<Option<&Material>> = Vec::new();
+        let mut triangles: Vec<(usize, [u32; 3])> = mesh
+            .elements
+            .chunks_exact(3)
+            .map(|triangle| {
+                let center = triangle
+                    .iter()
+                    .map(|&i| mesh.verts[i as usize].pos.coords)
+                    .sum::<Vector3<f32>>()
+                    / 3.0;
+                let material = sdf.surface(center).material();
+                let index = match materials.iter().position(|other| *other == material) {
+                    Some(index) => index,
+                    None => {
+                        materials.push(material);
+                        materials.len() - 1
+                    }
+                };
+                (index, [triangle[0], triangle[1], triangle[2]])
+            })
+            .collect();
+        triangles.sort_by_key(|(index, _)| *index);
+        mesh.elements = triangles
+            .iter()
+            .flat_map(|(_, triangle)| *triangle)
+            .collect();
+        let default = Material::default();
+        let parts = materials
+            .iter()
+            .enumerate()
+            .map(|(index, material)| {
+                let start = triangles.partition_point(|(other, _)| *other < index);
+                let end = triangles.partition_point(|(other, _)| *other <= index);
+                Part {
+                    material: PartMaterial::new(material.unwrap_or(&default), time),
+                    elements: 3 * start..3 * end,
+                }
+            })
+            .collect();
+        Self { mesh, parts }
+    }
+}
+
+impl From<Mesh> for ExportMesh {
+    /// The mesh as a single part with the default material
+    fn from(mesh: Mesh) -> Self {
+        let parts = vec![Part {
+            material: PartMaterial::new(&Material::default(), 0.0),
+            elements: 0..mesh.elements.len(),
+        }];
+        Self { mesh, parts }
+    }
+}
+
+/// How the scene is meshed for export
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ExportSettings {
+    pub mesher: Mesher,
+    pub grid: Grid,
+}
+
+impl ExportSettings {
+    /// Meshes `volume` as it is at `time`
+    pub fn mesh(&self, volume: &SDFVolume, time: f32) -> Result<ExportMesh, ExportError> {
+        let sdf = CpuEvaluator::new(volume, time)?;
+        let mesh = self.mesher.mesh(&sdf, &self.grid);
+        if mesh.elements.is_empty() {
+            return Err(ExportError::Empty);
+        }
+        if !volume.has_material() {
+            return Ok(ExportMesh::from(mesh));
+        }
+        Ok(ExportMesh::new(mesh, &sdf, time))
+    }
+
+    /// Meshes `volume` as it is at `time` and writes it to `path`
+    pub fn export(
+        &self,
+        volume: &SDFVolume,
+        time: f32,
+        format: ExportFormat,
+        path: &Path,
+    ) -> Result<(), ExportError> {
+        let mesh = self.mesh(volume, time)?;
+        format.save(&mesh, path)
+    }
+
+    fn ui(&mut self, ui: &mut egui::Ui) {
+        egui::ComboBox::from_label("Mesher")
+            .selected_text(self.mesher.label())
+            .show_ui(ui, |ui| {
+                for mesher in [Mesher::MarchingCubes, Mesher::default()] {
+                    let selected = self.mesher.label() == mesher.label();
+                    if ui.selectable_label(selected, mesher.label()).clicked() && !selected {
+                        self.mesher = mesher;
+                    }
+                }
+            });
+        if let Mesher::DualContouring { tolerance } = &mut self.mesher {
+            ui.horizontal(|ui| {
+                ui.label("Tolerance");
+                ui.add(
+                    egui::DragValue::new(tolerance)
+                        .speed(0.0001)
+                        .clamp_range(0.0..=1.0),
+                );
+            });
+        }
+        ui.horizontal(|ui| {
+            ui.label("Resolution");
+            ui.add(egui::DragValue::new(&mut self.grid.resolution).clamp_range(1..=1024));
+        });
+        for (label, corner) in [("Min", &mut self.grid.min), ("Max", &mut self.grid.max)] {
+            ui.horizontal(|ui| {
+                ui.label(label);
+                for component in corner.iter_mut() {
+                    ui.add(egui::DragValue::new(component).speed(0.1));
+                }
+            });
+        }
+        ui.horizontal(|ui| {
+            ui.label("Iso level");
+            ui.add(egui::DragValue::new(&mut self.grid.iso_level).speed(0.01));
+        });
+    }
+}
+
+pub enum ExportDialogResponse {
+    Pending,
+    Cancelled,
+    Confirmed(PathBuf),
+}
+
+/// Minimal egui prompt for the file a mesh is exported to and how it is
+/// meshed, used by the Export menu
+pub struct ExportDialog {
+    pub format: ExportFormat,
+    pub path: String,
+}
+
+impl ExportDialog {
+    /// Dialog for a file next to `project`, named after it
+    pub fn new(format: ExportFormat, project: Option<&Path>) -> Self {
+        let path = project
+            .unwrap_or(Path::new("untitled"))
+            .with_extension(format.extension());
+        Self {
+            format,
+            path: path.display().to_string(),
+        }
+    }
+
+    /// Draws the dialog and reports whether the user confirmed a path
+    pub fn show(
+        &mut self,
+        ctx: &egui::Context,
+        settings: &mut ExportSettings,
+    ) -> ExportDialogResponse {
+        let mut response = ExportDialogResponse::Pending;
+        egui::Window::new(format!("Export {}", self.format.label()))
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label("Path");
+                    ui.text_edit_singleline(&mut self.path);
+                });
+                settings.ui(ui);
+                ui.horizontal(|ui| {
+                    if ui.button("Export").clicked() && !self.path.trim().is_empty() {
+                        response = ExportDialogResponse::Confirmed(PathBuf::from(self.path.trim()));
+                    }
+                    if ui.button("Cancel").clicked() {
+                        response = ExportDialogResponse::Cancelled;
+                    }
+                });
+            });
+        response
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::Vector3;
+
+    use super::{srgb, ExportFormat, ExportMesh, ExportSettings};
+    use crate::{
+        mesher::{Grid, Mesher},
+        renderer::{mesh::Mesh, vert::Vert},
+        sdf::ir::{Matcap, Material, SDFPosition, SDFVolume},
+    };
+
+    /// Tetrahedron with a red vertex
+    fn tetrahedron() -> Mesh {
+        let mut verts: Vec<Vert> = [
+            [0.0, 0.0, 0.0],
+            [1.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0],
+            [0.0, 0.0, 1.0],
+        ]
+        .into_iter()
+        .map(|position| Vert::from(position, [0.0; 2], [1.0, 1.0, 1.0]))
+        .collect();
+        verts[1].col.y = 0.0;
+        verts[1].col.z = 0.0;
+        for vert in &mut verts {
+            vert.norm = (vert.pos.coords - Vector3::from_element(0.25)).normalize();
+        }
+        Mesh {
+            elements: vec![0, 2, 1, 0, 1, 3, 0, 3, 2, 1, 2, 3],
+            verts,
+        }
+    }
+
+    fn bytes(format: ExportFormat) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        format
+            .write(&ExportMesh::from(tetrahedron()), &mut bytes)
+            .unwrap();
+        bytes
+    }
+
+    fn u32_at(bytes: &[u8], offset: usize) -> u32 {
+        u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+    }
+
+    #[test]
+    fn stl() {
+        let binary = bytes(ExportFormat::BinaryStl);
+        assert_eq!(binary.len(), 84 + 4 * 50);
+        assert_eq!(u32_at(&binary, 80), 4);
+        assert!(!binary.starts_with(b"solid"));
+        let ascii = String::from_utf8(bytes(ExportFormat::AsciiStl)).unwrap();
+        assert_eq!(ascii.matches("facet normal").count(), 4);
+        // The first facet faces down the z axis
+        assert!(ascii.contains("facet normal 0 0 -1\n"));
+    }
+
+    #[test]
+    fn obj_and_ply() {
+        let obj = String::from_utf8(bytes(ExportFormat::Obj)).unwrap();
+        assert!(obj.contains("\nv 1 0 0 0.99999994 0 0\n"));
+        assert_eq!(obj.matches("\nvn ").count(), 4);
+        assert!(obj.ends_with("f 2//2 3//3 4//4\n"));
+        let ply = bytes(ExportFormat::Ply);
+        let header = b"end_header\n";
+        let start = ply
+            .windows(header.len())
+            .position(|window| window == header)
+            .unwrap()
+            + header.len();
+        assert_eq!(ply.len() - start, 4 * (6 * 4 + 3) + 4 * (1 + 3 * 4));
+        assert_eq!(&ply[start + 27 + 24..start + 27 + 27], &[255, 0, 0]);
+    }
+
+    #[test]
+    fn glb() {
+        let glb = bytes(ExportFormat::Glb);
+        assert_eq!(&glb[..4], b"glTF");
+        assert_eq!(u32_at(&glb, 8) as usize, glb.len());
+        let json_length = u32_at(&glb, 12) as usize;
+        assert_eq!(json_length % 4, 0);
+        let json = std::str::from_utf8(&glb[20..20 + json_length]).unwrap();
+        assert!(json.contains(r#""min":[0,0,0],"max":[1,1,1]"#));
+        let bin_length = u32_at(&glb, 20 + json_length) as usize;
+        assert_eq!(bin_length, 3 * 4 * 12 + 12 * 4);
+        assert_eq!(20 + json_length + 8 + bin_length, glb.len());
+    }
+
+    #[test]
+    fn parts_follow_materials() {
+        let ball = |x: f32, matcap: Matcap| SDFVolume::Material {
+            volume: Box::new(SDFVolume::Sphere {
+                position: SDFPosition::Translate {
+                    position: Box::new(SDFPosition::Origin),
+                    translation: Vector3::new(x, 0.0, 0.0).into(),
+                },
+                radius: 0.5.into(),
+            }),
+            material: Material {
+                matcap,
+                roughness: 0.25.into(),
+                ..Material::default()
+            },
+        };
+        let volume = SDFVolume::Union {
+            a: Box::new(ball(-1.0, Matcap::Clay)),
+            b: Box::new(ball(1.0, Matcap::Metal)),
+        };
+        let settings = ExportSettings {
+            mesher: Mesher::MarchingCubes,
+            grid: Grid {
+                resolution: 16,
+                ..Grid::default()
+            },
+        };
+        let mesh = settings.mesh(&volume, 0.0).unwrap();
+        assert_eq!(mesh.parts.len(), 2);
+        for part in &mesh.parts {
+            assert_eq!(part.material.roughness, 0.25);
+            // Each ball is its own part
+            let side = part.material.metallic() * 2.0 - 1.0;
+            for &i in &mesh.mesh.elements[part.elements.clone()] {
+                assert!(mesh.mesh.verts[i as usize].pos.x * side > 0.0);
+            }
+        }
+        let mut glb = Vec::new();
+        ExportFormat::Glb.write(&mesh, &mut glb).unwrap();
+        let glb = String::from_utf8_lossy(&glb);
+        // Parts are numbered in the order the mesher found them
+        assert!(glb.contains(r#"{"metallicFactor":1,"roughnessFactor":0.25}"#));
+        assert!(glb.contains(r#"{"metallicFactor":0,"roughnessFactor":0.25}"#));
+        let mut obj = Vec::new();
+        ExportFormat::Obj.write(&mesh, &mut obj).unwrap();
+        assert_eq!(
+            String::from_utf8(obj).unwrap().matches("usemtl ").count(),
+            2
+        );
+    }
+
+    #[test]
+    fn srgb_matches_the_shader() {
+        let color = srgb(Vector3::new(0.0, 0.5, 1.0));
+        assert_eq!(color.x, 0.0);
+        assert!((color.y - 0.7354).abs() < 1e-3);
+        assert!((color.z - 1.0).abs() < 1e-6);
+        assert_eq!(
+            ExportFormat::from_path(std::path::Path::new("part.STL")),
+            Some(ExportFormat::BinaryStl)
+        );
+    }
+}
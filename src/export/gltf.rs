@@ -0,0 +1,147 @@
+//! Binary glTF 2.0 (.glb), a single mesh with normals and linear vertex
+//! colors, one primitive per part. The vertex colors already hold the color
+//! of the materials, so these only set roughness and metalness.
+use std::io::{self, Write};
+
+use nalgebra::Vector3;
+
+use super::ExportMesh;
+
+const MAGIC: u32 = 0x4654_6c67;
+const VERSION: u32 = 2;
+const JSON_CHUNK: u32 = 0x4e4f_534a;
+const BIN_CHUNK: u32 = 0x004e_4942;
+const FLOAT: u32 = 5126;
+const UNSIGNED_INT: u32 = 5125;
+const ARRAY_BUFFER: u32 = 34962;
+const ELEMENT_ARRAY_BUFFER: u32 = 34963;
+
+/// Bytes of `vectors` as consecutive little endian floats
+fn vec3_bytes(vectors: impl Iterator<Item = Vector3<f32>>) -> Vec<u8> {
+    vectors
+        .flat_map(|vector| [vector.x, vector.y, vector.z])
+        .flat_map(f32::to_le_bytes)
+        .collect()
+}
+
+fn json_array(vector: Vector3<f32>) -> String {
+    format!("[{},{},{}]", vector.x, vector.y, vector.z)
+}
+
+/// Writes `mesh`, which must have at least one triangle, as glTF does not
+/// allow empty accessors. Parts without triangles are left out.
+pub fn write<W: Write>(mesh: &ExportMesh, writer: &mut W) -> io::Result<()> {
+    let parts: Vec<_> = mesh
+        .parts
+        .iter()
+        .enumerate()
+        .filter(|(_, part)| !part.elements.is_empty())
+        .collect();
+    let mesh = &mesh.mesh;
+    let positions = vec3_bytes(mesh.verts.iter().map(|vert| vert.pos.coords));
+    // Normals have to be unit length
+    let normals = vec3_bytes(mesh.verts.iter().map(|vert| {
+        vert.norm
+            .try_normalize(f32::EPSILON)
+            .unwrap_or(Vector3::z())
+    }));
+    let colors = vec3_bytes(mesh.verts.iter().map(|vert| vert.col.coords));
+    let indices: Vec<u8> = mesh
+        .elements
+        .iter()
+        .flat_map(|index| index.to_le_bytes())
+        .collect();
+    let (min, max) = mesh.verts.iter().fold(
+        (
+            Vector3::from_element(f32::MAX),
+            Vector3::from_element(f32::MIN),
+        ),
+        |(min, max), vert| (min.inf(&vert.pos.coords), max.sup(&vert.pos.coords)),
+    );
+
+    let views = [&positions, &normals, &colors, &indices];
+    let mut buffer_views = Vec::new();
+    let mut offset = 0;
+    for (i, view) in views.iter().enumerate() {
+        let target = if i == 3 {
+            ELEMENT_ARRAY_BUFFER
+        } else {
+            ARRAY_BUFFER
+        };
+        buffer_views.push(format!(
+            r#"{{"buffer":0,"byteOffset":{},"byteLength":{},"target":{}}}"#,
+            offset,
+            view.len(),
+            target
+        ));
+        offset += view.len();
+    }
+    let count = mesh.verts.len();
+    let mut primitives = Vec::new();
+    let mut materials = Vec::new();
+    let mut index_accessors = Vec::new();
+    for (i, (index, part)) in parts.iter().enumerate() {
+        primitives.push(format!(
+            r#"{{"attributes":{{"POSITION":0,"NORMAL":1,"COLOR_0":2}},"indices":{},"material":{}}}"#,
+            3 + i,
+            i
+        ));
+        materials.push(format!(
+            r#"{{"name":"{}","pbrMetallicRoughness":{{"metallicFactor":{},"roughnessFactor":{}}}}}"#,
+            part.material.name(*index),
+            part.material.metallic(),
+            part.material.roughness
+        ));
+        index_accessors.push(format!(
+            r#"{{"bufferView":3,"byteOffset":{},"componentType":{},"count":{},"type":"SCALAR"}}"#,
+            4 * part.elements.start,
+            UNSIGNED_INT,
+            part.elements.len()
+        ));
+    }
+    let json = format!(
+        concat!(
+            r#"{{"asset":{{"version":"2.0","generator":"N3D"}},"#,
+            r#""scene":0,"scenes":[{{"nodes":[0]}}],"nodes":[{{"mesh":0}}],"#,
+            r#""meshes":[{{"primitives":[{}]}}],"#,
+            r#""materials":[{}],"#,
+            r#""buffers":[{{"byteLength":{}}}],"#,
+            r#""bufferViews":[{}],"#,
+            r#""accessors":["#,
+            r#"{{"bufferView":0,"componentType":{},"count":{},"type":"VEC3","min":{},"max":{}}},"#,
+            r#"{{"bufferView":1,"componentType":{},"count":{},"type":"VEC3"}},"#,
+            r#"{{"bufferView":2,"componentType":{},"count":{},"type":"VEC3"}},"#,
+            r#"{}]}}"#
+        ),
+        primitives.join(","),
+        materials.join(","),
+        offset,
+        buffer_views.join(","),
+        FLOAT,
+        count,
+        json_array(min),
+        json_array(max),
+        FLOAT,
+        count,
+        FLOAT,
+        count,
+        index_accessors.join(","),
+    );
+    // Chunks are padded to 4 bytes, JSON with spaces
+    let mut json = json.into_bytes();
+    json.resize(json.len().next_multiple_of(4), b' ');
+    let bin_length = offset.next_multiple_of(4);
+    let length = 12 + 8 + json.len() + 8 + bin_length;
+
+    for word in [MAGIC, VERSION, length as u32, json.len() as u32, JSON_CHUNK] {
+        writer.write_all(&word.to_le_bytes())?;
+    }
+    writer.write_all(&json)?;
+    for word in [bin_length as u32, BIN_CHUNK] {
+        writer.write_all(&word.to_le_bytes())?;
+    }
+    for view in views {
+        writer.write_all(view)?;
+    }
+    writer.write_all(&vec![0; bin_length - offset])
+}
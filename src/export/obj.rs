@@ -0,0 +1,50 @@
+//! Wavefront OBJ with vertex normals. Colors follow the positions on the `v`
+//! lines, an extension read by Blender, MeshLab and most other tools. The
+//! materials go to a separate MTL library.
+use std::io::{self, Write};
+
+use super::{srgb, ExportMesh};
+
+/// Writes the mesh, referring to the materials in the MTL file `library`
+/// if there is one
+pub fn write<W: Write>(mesh: &ExportMesh, writer: &mut W, library: Option<&str>) -> io::Result<()> {
+    writeln!(writer, "# N3D")?;
+    if let Some(library) = library {
+        writeln!(writer, "mtllib {}", library)?;
+    }
+    for vert in &mesh.mesh.verts {
+        let color = srgb(vert.col.coords);
+        writeln!(
+            writer,
+            "v {} {} {} {} {} {}",
+            vert.pos.x, vert.pos.y, vert.pos.z, color.x, color.y, color.z
+        )?;
+    }
+    for vert in &mesh.mesh.verts {
+        writeln!(writer, "vn {} {} {}", vert.norm.x, vert.norm.y, vert.norm.z)?;
+    }
+    for (index, part) in mesh.parts.iter().enumerate() {
+        writeln!(writer, "usemtl {}", part.material.name(index))?;
+        for triangle in mesh.mesh.elements[part.elements.clone()].chunks_exact(3) {
+            // Indices start at 1
+            let [a, b, c] = [0, 1, 2].map(|i| triangle[i] + 1);
+            writeln!(writer, "f {a}//{a} {b}//{b} {c}//{c}")?;
+        }
+    }
+    Ok(())
+}
+
+/// Writes the MTL library with the materials of the mesh, using the PBR
+/// extension for roughness and metalness
+pub fn write_library<W: Write>(mesh: &ExportMesh, writer: &mut W) -> io::Result<()> {
+    writeln!(writer, "# N3D")?;
+    for (index, part) in mesh.parts.iter().enumerate() {
+        let material = &part.material;
+        let color = srgb(material.color);
+        writeln!(writer, "newmtl {}", material.name(index))?;
+        writeln!(writer, "Kd {} {} {}", color.x, color.y, color.z)?;
+        writeln!(writer, "Pr {}", material.roughness)?;
+        writeln!(writer, "Pm {}", material.metallic())?;
+    }
+    Ok(())
+}
@@ -0,0 +1,43 @@
+//! Binary PLY with normals and 8 bit vertex colors
+use std::io::{self, Write};
+
+use super::srgb;
+use crate::renderer::mesh::Mesh;
+
+pub fn write<W: Write>(mesh: &Mesh, writer: &mut W) -> io::Result<()> {
+    write!(
+        writer,
+        "ply\n\
+         format binary_little_endian 1.0\n\
+         comment N3D\n\
+         element vertex {}\n\
+         property float x\n\
+         property float y\n\
+         property float z\n\
+         property float nx\n\
+         property float ny\n\
+         property float nz\n\
+         property uchar red\n\
+         property uchar green\n\
+         property uchar blue\n\
+         element face {}\n\
+         property list uchar uint vertex_indices\n\
+         end_header\n",
+        mesh.verts.len(),
+        mesh.elements.len() / 3
+    )?;
+    for vert in &mesh.verts {
+        for component in vert.pos.iter().chain(vert.norm.iter()) {
+            writer.write_all(&component.to_le_bytes())?;
+        }
+        let color = srgb(vert.col.coords).map(|c| (c * 255.0).round() as u8);
+        writer.write_all(color.as_slice())?;
+    }
+    for triangle in mesh.elements.chunks_exact(3) {
+        writer.write_all(&[3])?;
+        for index in triangle {
+            writer.write_all(&index.to_le_bytes())?;
+        }
+    }
+    Ok(())
+}
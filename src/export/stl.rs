@@ -0,0 +1,54 @@
+//! STL, the triangle soup read by every slicer. Neither variant has a
+//! standard way to store colors, so they are left out.
+use std::io::{self, Write};
+
+use nalgebra::Vector3;
+
+use crate::renderer::mesh::Mesh;
+
+/// Corners of each triangle and its unit normal, computed from the corners
+/// as STL readers expect rather than taken from the vertices
+fn facets(mesh: &Mesh) -> impl Iterator<Item = (Vector3<f32>, [Vector3<f32>; 3])> + '_ {
+    mesh.elements.chunks_exact(3).map(|triangle| {
+        let corners = [0, 1, 2].map(|i| mesh.verts[triangle[i] as usize].pos.coords);
+        let normal = (corners[1] - corners[0]).cross(&(corners[2] - corners[0]));
+        (normal.try_normalize(0.0).unwrap_or_default(), corners)
+    })
+}
+
+pub fn write_binary<W: Write>(mesh: &Mesh, writer: &mut W) -> io::Result<()> {
+    // The header must not start with "solid", or readers take it for ASCII
+    let mut header = [0u8; 80];
+    let name = b"N3D binary STL";
+    header[..name.len()].copy_from_slice(name);
+    writer.write_all(&header)?;
+    writer.write_all(&((mesh.elements.len() / 3) as u32).to_le_bytes())?;
+    for (normal, corners) in facets(mesh) {
+        for vector in std::iter::once(normal).chain(corners) {
+            for component in vector.iter() {
+                writer.write_all(&component.to_le_bytes())?;
+            }
+        }
+        // Attribute byte count
+        writer.write_all(&0u16.to_le_bytes())?;
+    }
+    Ok(())
+}
+
+pub fn write_ascii<W: Write>(mesh: &Mesh, writer: &mut W) -> io::Result<()> {
+    writeln!(writer, "solid n3d")?;
+    for (normal, corners) in facets(mesh) {
+        writeln!(
+            writer,
+            "facet normal {} {} {}",
+            normal.x, normal.y, normal.z
+        )?;
+        writeln!(writer, "  outer loop")?;
+        for corner in corners {
+            writeln!(writer, "    vertex {} {} {}", corner.x, corner.y, corner.z)?;
+        }
+        writeln!(writer, "  endloop")?;
+        writeln!(writer, "endfacet")?;
+    }
+    writeln!(writer, "endsolid n3d")
+}
@@ -0,0 +1,463 @@
+use std::collections::{BTreeMap, HashMap};
+
+use egui_node_graph::{InputId, Node, NodeId, NodeTemplateTrait, OutputId};
+
+use crate::node_graph::{
+    MyEditorState, MyGraph, MyGraphState, N3DDataType, N3DNodeData, N3DNodeTemplate,
+};
+
+/// Extension used for group library files
+#[cfg(feature = "persistence")]
+pub const LIBRARY_EXTENSION: &str = "n3dgroups";
+
+#[cfg(feature = "persistence")]
+#[derive(thiserror::Error, Debug)]
+pub enum LibraryError {
+    #[error("could not access group library: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("could not parse group library: {0}")]
+    Parse(#[from] ron::error::SpannedError),
+    #[error("could not write group library: {0}")]
+    Serialize(#[from] ron::Error),
+}
+
+/// Identifies a group in [`NodeGroups`], stays the same when it is renamed
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "persistence", derive(serde::Serialize, serde::Deserialize))]
+pub struct GroupId(u32);
+
+/// Input or output of a group, forwarded to a parameter of a node inside it
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "persistence", derive(serde::Serialize, serde::Deserialize))]
+pub struct GroupPort {
+    pub name: String,
+    pub node: NodeId,
+    pub param: String,
+    pub typ: N3DDataType,
+}
+
+impl GroupPort {
+    /// Port forwarding to `param` of `node`, named after the parameter, or
+    /// after the node too if one of `ports` already has that name
+    pub fn new(
+        ports: &[GroupPort],
+        node: &Node<N3DNodeData>,
+        param: &str,
+        typ: N3DDataType,
+    ) -> Self {
+        let taken = |name: &String| ports.iter().any(|port| port.name == *name);
+        let name = [param.to_string(), format!("{} {}", node.label, param)]
+            .into_iter()
+            .chain((2..).map(|i| format!("{} {} {}", node.label, param, i)))
+            .find(|name| !taken(name))
+            .expect("unbounded range");
+        GroupPort {
+            name,
+            node: node.id,
+            param: param.to_string(),
+            typ,
+        }
+    }
+}
+
+/// Subgraph packaged as a single node. Nodes inside it without a position
+/// connected are placed relative to the group's position input, if it has one.
+#[derive(Clone)]
+#[cfg_attr(feature = "persistence", derive(serde::Serialize, serde::Deserialize))]
+pub struct NodeGroup {
+    pub name: String,
+    pub graph: MyGraph,
+    /// Where the nodes inside are drawn while editing the group, sorted so
+    /// files come out the same every time they are saved
+    pub positions: BTreeMap<NodeId, egui::Pos2>,
+    pub inputs: Vec<GroupPort>,
+    pub outputs: Vec<GroupPort>,
+}
+
+impl NodeGroup {
+    /// Drops the ports whose parameter no longer exists after editing
+    pub fn prune_ports(&mut self) {
+        let graph = &self.graph;
+        let node = |port: &GroupPort| graph.nodes.get(port.node);
+        self.inputs
+            .retain(|port| node(port).is_some_and(|node| node.get_input(&port.param).is_ok()));
+        self.outputs
+            .retain(|port| node(port).is_some_and(|node| node.get_output(&port.param).is_ok()));
+    }
+
+    /// Groups instanced directly inside this one
+    fn instances(&self) -> impl Iterator<Item = GroupId> + '_ {
+        self.graph
+            .nodes
+            .values()
+            .filter_map(|node| match node.user_data.template() {
+                N3DNodeTemplate::Group(id) => Some(id),
+                _ => None,
+            })
+    }
+}
+
+/// Groups available in the node finder, saved with the project and
+/// exchanged through library files
+#[derive(Clone, Default)]
+#[cfg_attr(feature = "persistence", derive(serde::Serialize, serde::Deserialize))]
+pub struct NodeGroups {
+    groups: Vec<(GroupId, NodeGroup)>,
+    next_id: u32,
+}
+
+impl NodeGroups {
+    pub fn get(&self, id: GroupId) -> Option<&NodeGroup> {
+        self.groups.iter().find(|(i, _)| *i == id).map(|(_, g)| g)
+    }
+
+    pub fn get_mut(&mut self, id: GroupId) -> Option<&mut NodeGroup> {
+        self.groups
+            .iter_mut()
+            .find(|(i, _)| *i == id)
+            .map(|(_, g)| g)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (GroupId, &NodeGroup)> {
+        self.groups.iter().map(|(id, group)| (*id, group))
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (GroupId, &mut NodeGroup)> {
+        self.groups.iter_mut().map(|(id, group)| (*id, group))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.groups.is_empty()
+    }
+
+    pub fn insert(&mut self, group: NodeGroup) -> GroupId {
+        let id = GroupId(self.next_id);
+        self.next_id += 1;
+        self.groups.push((id, group));
+        id
+    }
+
+    /// `base`, numbered if a group of that name already exists
+    pub fn unique_name(&self, base: &str) -> String {
+        let taken = |name: &str| self.groups.iter().any(|(_, g)| g.name == name);
+        (1..)
+            .map(|i| match i {
+                1 => base.to_string(),
+                i => format!("{} {}", base, i),
+            })
+            .find(|name| !taken(name))
+            .expect("unbounded range")
+    }
+
+    /// Whether `outer` instances `inner`, directly or through other groups.
+    /// A group cannot be placed inside itself this way.
+    pub fn contains(&self, outer: GroupId, inner: GroupId) -> bool {
+        let mut pending = vec![outer];
+        let mut seen = Vec::new();
+        while let Some(id) = pending.pop() {
+            if id == inner {
+                return true;
+            }
+            if seen.contains(&id) {
+                continue;
+            }
+            seen.push(id);
+            if let Some(group) = self.get(id) {
+                pending.extend(group.instances());
+            }
+        }
+        false
+    }
+
+    /// Adds the groups of `library` under new IDs, replacing the groups of
+    /// the same name. Returns the replaced and added IDs.
+    pub fn merge(&mut self, library: NodeGroups) -> Vec<GroupId> {
+        let ids: HashMap<GroupId, GroupId> = library
+            .groups
+            .iter()
+            .map(|(id, group)| {
+                let existing = self.groups.iter().find(|(_, g)| g.name == group.name);
+                let new = existing.map_or_else(
+                    || {
+                        self.next_id += 1;
+                        GroupId(self.next_id - 1)
+                    },
+                    |(id, _)| *id,
+                );
+                (*id, new)
+            })
+            .collect();
+        for (id, mut group) in library.groups {
+            for node in group.graph.nodes.values_mut() {
+                if let N3DNodeTemplate::Group(inner) = node.user_data.template() {
+                    if let Some(inner) = ids.get(&inner) {
+                        let name = node.user_data.name().to_string();
+                        node.user_data = N3DNodeData::new(N3DNodeTemplate::Group(*inner));
+                        node.user_data.set_name(name);
+                    }
+                }
+            }
+            let id = ids[&id];
+            match self.get_mut(id) {
+                Some(existing) => *existing = group,
+                None => self.groups.push((id, group)),
+            }
+        }
+        ids.into_values().collect()
+    }
+
+    #[cfg(feature = "persistence")]
+    pub fn open<P: AsRef<std::path::Path>>(path: P) -> Result<Self, LibraryError> {
+        let text = std::fs::read_to_string(path)?;
+        Ok(ron::from_str(&text)?)
+    }
+
+    #[cfg(feature = "persistence")]
+    pub fn save<P: AsRef<std::path::Path>>(&self, path: P) -> Result<(), LibraryError> {
+        let text = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())?;
+        std::fs::write(path, text)?;
+        Ok(())
+    }
+}
+
+/// Moves the selected nodes into a new group named `name` and puts an
+/// instance of it in their place. Connections coming from outside the
+/// selection become inputs of the group, outputs not consumed inside it become
+/// outputs, and the instance is wired up like the nodes it replaces.
+pub fn collapse_selection(
+    state: &mut MyEditorState,
+    user_state: &mut MyGraphState,
+    name: &str,
+) -> Option<GroupId> {
+    let outer = &state.graph;
+    let selected: Vec<NodeId> = state
+        .selected_nodes
+        .iter()
+        .copied()
+        .filter(|node| outer.nodes.contains_key(*node))
+        .collect();
+    if selected.is_empty() {
+        return None;
+    }
+
+    let mut graph = MyGraph::default();
+    let mut positions = BTreeMap::new();
+    let mut inputs: HashMap<InputId, InputId> = HashMap::new();
+    let mut outputs: HashMap<OutputId, OutputId> = HashMap::new();
+    for &node_id in &selected {
+        let node = &outer[node_id];
+        let copy = graph.add_node(node.label.clone(), node.user_data.clone(), |_, _| {});
+        for (param, input) in &node.inputs {
+            let input_param = outer.get_input(*input);
+            let id = graph.add_input_param(
+                copy,
+                param.clone(),
+                input_param.typ,
+                input_param.value.clone(),
+                input_param.kind,
+                input_param.shown_inline,
+            );
+            inputs.insert(*input, id);
+        }
+        for (param, output) in &node.outputs {
+            let id = graph.add_output_param(copy, param.clone(), outer.get_output(*output).typ);
+            outputs.insert(*output, id);
+        }
+        if let Some(position) = state.node_positions.get(node_id) {
+            positions.insert(copy, *position);
+        }
+    }
+
+    let param_name = |names: &[(String, InputId)], id: InputId| {
+        names
+            .iter()
+            .find(|(_, i)| *i == id)
+            .map(|(name, _)| name.clone())
+    };
+
+    // Connections from outside become inputs, the same outer output feeding
+    // several nodes inside is exposed once per input it feeds
+    let mut group_inputs = Vec::new();
+    let mut incoming = Vec::new();
+    let mut consumed = Vec::new();
+    for (input, output) in outer.iter_connections() {
+        match (inputs.get(&input), outputs.get(&output)) {
+            (Some(&inner_input), Some(&inner_output)) => {
+                graph.add_connection(inner_output, inner_input);
+                consumed.push(inner_output);
+            }
+            (Some(&inner_input), None) => {
+                let node = graph.get_input(inner_input).node;
+                let param = param_name(&graph[node].inputs, inner_input)?;
+                let typ = graph.get_input(inner_input).typ;
+                let port = GroupPort::new(&group_inputs, &graph[node], &param, typ);
+                incoming.push((output, port.name.clone()));
+                group_inputs.push(port);
+            }
+            _ => {}
+        }
+    }
+
+    // Outputs read from outside or not read at all become outputs of the group
+    let read_outside: Vec<OutputId> = outer
+        .iter_connections()
+        .filter(|(input, output)| !inputs.contains_key(input) && outputs.contains_key(output))
+        .map(|(_, output)| output)
+        .collect();
+    let mut group_outputs = Vec::new();
+    let mut exposed: HashMap<OutputId, String> = HashMap::new();
+    for &node_id in &selected {
+        for (_, output) in &outer[node_id].outputs {
+            let inner_output = outputs[output];
+            if consumed.contains(&inner_output) && !read_outside.contains(output) {
+                continue;
+            }
+            let node = graph.get_output(inner_output).node;
+            let param = graph[node]
+                .outputs
+                .iter()
+                .find(|(_, o)| *o == inner_output)
+                .map(|(name, _)| name.clone())?;
+            let typ = graph.get_output(inner_output).typ;
+            let port = GroupPort::new(&group_outputs, &graph[node], &param, typ);
+            exposed.insert(*output, port.name.clone());
+            group_outputs.push(port);
+        }
+    }
+    let outgoing: Vec<(InputId, String)> = outer
+        .iter_connections()
+        .filter(|(input, _)| !inputs.contains_key(input))
+        .filter_map(|(input, output)| Some((input, exposed.get(&output)?.clone())))
+        .collect();
+
+    let placed: Vec<egui::Pos2> = selected
+        .iter()
+        .filter_map(|node| state.node_positions.get(*node).copied())
+        .collect();
+    let center = match placed.len() {
+        0 => egui::Pos2::ZERO,
+        n => (placed
+            .iter()
+            .fold(egui::Vec2::ZERO, |sum, pos| sum + pos.to_vec2())
+            / n as f32)
+            .to_pos2(),
+    };
+
+    for node in &selected {
+        state.graph.remove_node(*node);
+        state.node_positions.remove(*node);
+        state.node_order.retain(|n| n != node);
+        if user_state.active_node == Some(*node) {
+            user_state.active_node = None;
+        }
+    }
+    state.selected_nodes.clear();
+
+    let id = user_state.groups.insert(NodeGroup {
+        name: user_state.groups.unique_name(name),
+        graph,
+        positions,
+        inputs: group_inputs,
+        outputs: group_outputs,
+    });
+    let template = N3DNodeTemplate::Group(id);
+    let instance = state.graph.add_node(
+        template.node_graph_label(user_state),
+        template.user_data(user_state),
+        |graph, node| template.build_node(graph, user_state, node),
+    );
+    state.node_positions.insert(instance, center);
+    state.node_order.push(instance);
+    for (output, name) in incoming {
+        if let Ok(input) = state.graph[instance].get_input(&name) {
+            state.graph.add_connection(output, input);
+        }
+    }
+    for (input, name) in outgoing {
+        if let Ok(output) = state.graph[instance].get_output(&name) {
+            state.graph.add_connection(output, input);
+        }
+    }
+    Some(id)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::{
+        node_graph::{
+            evaluate_node,
+            tests::{add, connect},
+            EvalContext, N3DValueType,
+        },
+        nodes::{math::NewScalar, space::SDFTranslate, volume::{SDFSphere, SDFViewer}},
+        sdf::{
+            expr::{Expr, ValueKind},
+            ir::{SDFPosition, SDFVolume},
+        },
+    };
+
+    #[test]
+    fn collapsed_sphere_becomes_call() {
+        let mut state = MyEditorState::default();
+        let mut user_state = MyGraphState::default();
+        let radius = add(&mut state, &mut user_state, N3DNodeTemplate::Node(&NewScalar));
+        let translate = add(&mut state, &mut user_state, N3DNodeTemplate::Node(&SDFTranslate));
+        let sphere = add(&mut state, &mut user_state, N3DNodeTemplate::Node(&SDFSphere));
+        let viewer = add(&mut state, &mut user_state, N3DNodeTemplate::Node(&SDFViewer));
+        let value = state.graph[radius].get_input("value").unwrap();
+        state.graph[value].value = N3DValueType::Scalar { value: 0.5 };
+        connect(&mut state, radius, sphere, "radius");
+        connect(&mut state, translate, sphere, "sdf position");
+        connect(&mut state, sphere, viewer, "sdf");
+
+        state.selected_nodes = vec![sphere];
+        let id = collapse_selection(&mut state, &mut user_state, "Ball").unwrap();
+        let group = user_state.groups.get(id).unwrap();
+        let names = |ports: &[GroupPort]| ports.iter().map(|p| p.name.clone()).collect::<Vec<_>>();
+        assert_eq!(names(&group.inputs), ["radius", "sdf position"]);
+        assert_eq!(names(&group.outputs), ["out"]);
+        assert!(!state.graph.nodes.contains_key(sphere));
+
+        let value = evaluate_node(
+            &state.graph,
+            viewer,
+            EvalContext::new(&user_state),
+            &mut HashMap::new(),
+        )
+        .unwrap();
+        let N3DValueType::SDFVolume { value: call } = value else {
+            panic!("group instance evaluates to a volume");
+        };
+        let SDFVolume::Call {
+            position,
+            args,
+            body,
+            ..
+        } = &call
+        else {
+            panic!("group instance evaluates to a call");
+        };
+        assert!(matches!(position, SDFPosition::Translate { .. }));
+        assert_eq!(args, &[Expr::from(0.5)]);
+        assert_eq!(
+            **body,
+            SDFVolume::Sphere {
+                position: SDFPosition::Origin,
+                radius: Expr::Arg {
+                    index: 0,
+                    kind: ValueKind::Scalar
+                },
+            }
+        );
+        assert_eq!(
+            call.inlined(),
+            SDFVolume::Sphere {
+                position: position.clone(),
+                radius: 0.5.into()
+            }
+        );
+    }
+}
@@ -0,0 +1,78 @@
+/// Most edits kept for undoing, the oldest are forgotten past this
+const LIMIT: usize = 100;
+
+/// Undo and redo stacks of document states, each labelled with the edit that
+/// left it. States are stored whole rather than as inverse operations, so an
+/// undo never has to reconstruct IDs of removed nodes.
+pub struct History<T> {
+    undo: Vec<(&'static str, T)>,
+    redo: Vec<(&'static str, T)>,
+}
+
+impl<T> Default for History<T> {
+    fn default() -> Self {
+        Self {
+            undo: Vec::new(),
+            redo: Vec::new(),
+        }
+    }
+}
+
+impl<T> History<T> {
+    /// Records `before`, the state an edit called `label` started from. Any
+    /// undone edits can no longer be redone.
+    pub fn push(&mut self, label: &'static str, before: T) {
+        self.redo.clear();
+        self.undo.push((label, before));
+        if self.undo.len() > LIMIT {
+            self.undo.remove(0);
+        }
+    }
+
+    /// State before the last edit, `current` becomes redoable
+    pub fn undo(&mut self, current: T) -> Option<T> {
+        let (label, state) = self.undo.pop()?;
+        self.redo.push((label, current));
+        Some(state)
+    }
+
+    /// State after the last undone edit, `current` becomes undoable again
+    pub fn redo(&mut self, current: T) -> Option<T> {
+        let (label, state) = self.redo.pop()?;
+        self.undo.push((label, current));
+        Some(state)
+    }
+
+    pub fn undo_label(&self) -> Option<&'static str> {
+        self.undo.last().map(|(label, _)| *label)
+    }
+
+    pub fn redo_label(&self) -> Option<&'static str> {
+        self.redo.last().map(|(label, _)| *label)
+    }
+
+    pub fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn undo_and_redo_in_order() {
+        let mut history = History::default();
+        history.push("one", 0);
+        history.push("two", 1);
+        assert_eq!(history.undo(2), Some(1));
+        assert_eq!(history.undo(1), Some(0));
+        assert_eq!(history.undo(0), None);
+        assert_eq!(history.redo_label(), Some("one"));
+        assert_eq!(history.redo(0), Some(1));
+        history.push("three", 1);
+        assert_eq!(history.redo(2), None);
+        assert_eq!(history.undo_label(), Some("three"));
+    }
+}
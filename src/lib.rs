@@ -12,8 +12,27 @@ pub mod input;
 pub mod camera;
 /// Ray marcher info
 pub mod ray_marcher;
+/// Clock driving animated parameters
+pub mod playback;
+/// Keyframed node parameters
+pub mod animation;
+/// Reusable subgraphs shown as a single node
+pub mod group;
+/// Undo and redo of graph edits
+pub mod history;
+/// Project files holding a saved node graph and camera
+#[cfg(feature = "persistence")]
+pub mod project;
 
 pub mod node_graph;
+/// Node types and the registry other crates add theirs to
 pub mod nodes;
 
 pub mod sdf;
+/// Triangle meshes of SDFs sampled on the CPU
+pub mod mesher;
+/// Mesh files written from the scene
+pub mod export;
+/// Commands run without a window
+#[cfg(feature = "persistence")]
+pub mod cli;
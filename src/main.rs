@@ -4,6 +4,18 @@ use n3d::{
 };
 
 fn main() {
+    // Any arguments are a command to run without a window
+    #[cfg(feature = "persistence")]
+    {
+        let args: Vec<String> = std::env::args().skip(1).collect();
+        if !args.is_empty() {
+            if let Err(err) = n3d::cli::run(&args) {
+                eprintln!("Error: {}", err);
+                std::process::exit(1);
+            }
+            return;
+        }
+    }
     match Window::new() {
         Ok((window, event_loop)) => {
             let mut engine = EngineState::new(&window);
@@ -0,0 +1,211 @@
+//! Extraction of triangle meshes from volumes sampled on the CPU.
+//!
+//! Meshers sample a [`CpuEvaluator`] on a regular [`Grid`]. The grid is
+//! split into blocks of cells that are handed to worker threads, and blocks
+//! the surface can not pass through, judging by the distance at their center,
+//! are skipped without sampling them any further.
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+    thread,
+};
+
+use nalgebra::{Point2, Point3, Vector3};
+
+use crate::{
+    renderer::{mesh::Mesh, vert::Vert},
+    sdf::cpu::CpuEvaluator,
+};
+
+pub mod dual_contouring;
+pub mod marching_cubes;
+
+/// Cells along each side of a block
+const BLOCK_CELLS: usize = 16;
+
+/// Region sampled by a mesher
+#[derive(Clone, Debug, PartialEq)]
+pub struct Grid {
+    pub min: Vector3<f32>,
+    pub max: Vector3<f32>,
+    /// Cells along each axis
+    pub resolution: usize,
+    /// Distance the surface is extracted at, 0 for the surface itself
+    pub iso_level: f32,
+}
+
+impl Default for Grid {
+    fn default() -> Self {
+        Self {
+            min: Vector3::from_element(-2.0),
+            max: Vector3::from_element(2.0),
+            resolution: 64,
+            iso_level: 0.0,
+        }
+    }
+}
+
+impl Grid {
+    /// Size of one cell
+    pub fn cell(&self) -> Vector3<f32> {
+        (self.max - self.min) / self.resolution.max(1) as f32
+    }
+    /// Position of the sample at integer coordinates `index`
+    pub fn point(&self, index: [usize; 3]) -> Vector3<f32> {
+        let cell = self.cell();
+        Vector3::from_fn(|i, _| self.min[i] + index[i] as f32 * cell[i])
+    }
+    /// Index of the sample at `index` among all samples of the grid
+    pub fn sample_index(&self, index: [usize; 3]) -> usize {
+        let samples = self.resolution + 1;
+        index[0] + samples * (index[1] + samples * index[2])
+    }
+
+    /// Calls `f` with the first cell and the number of cells along each axis
+    /// of every block the surface may pass through, from several threads.
+    /// Returns what `f` returned, in no particular order.
+    pub fn par_blocks<T, F>(&self, sdf: &CpuEvaluator, f: F) -> Vec<T>
+    where
+        T: Send,
+        F: Fn([usize; 3], [usize; 3]) -> T + Sync,
+    {
+        let per_axis = self.resolution.div_ceil(BLOCK_CELLS);
+        let count = per_axis * per_axis * per_axis;
+        let next = AtomicUsize::new(0);
+        let results = Mutex::new(Vec::new());
+        let cell = self.cell();
+        let distortion = sdf.distortion();
+        let workers = thread::available_parallelism().map_or(1, |n| n.get());
+        thread::scope(|scope| {
+            for _ in 0..workers {
+                scope.spawn(|| loop {
+                    let block = next.fetch_add(1, Ordering::Relaxed);
+                    if block >= count {
+                        break;
+                    }
+                    let index = [
+                        block % per_axis,
+                        block / per_axis % per_axis,
+                        block / (per_axis * per_axis),
+                    ];
+                    let start = index.map(|i| i * BLOCK_CELLS);
+                    let size = start.map(|s| BLOCK_CELLS.min(self.resolution - s));
+                    let extent = Vector3::from_fn(|i, _| size[i] as f32 * cell[i]);
+                    let center = self.point(start) + extent / 2.0;
+                    // Distances are at most `distortion` times too long, so
+                    // nothing closer than this can be inside the block
+                    let reach = (sdf.distance(center) - self.iso_level).abs() / distortion;
+                    if reach > extent.norm() / 2.0 {
+                        continue;
+                    }
+                    let result = f(start, size);
+                    results.lock().unwrap().push(result);
+                });
+            }
+        });
+        results.into_inner().unwrap()
+    }
+}
+
+/// Algorithm turning a volume into triangles
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Mesher {
+    /// Smooth surfaces, rounding off sharp edges
+    MarchingCubes,
+    /// Sharp edges and corners kept, flat regions simplified within
+    /// `tolerance`, see [`dual_contouring::dual_contouring`]
+    DualContouring { tolerance: f32 },
+}
+
+impl Default for Mesher {
+    fn default() -> Self {
+        Self::DualContouring { tolerance: 1e-3 }
+    }
+}
+
+impl Mesher {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::MarchingCubes => "Marching cubes",
+            Self::DualContouring { .. } => "Dual contouring",
+        }
+    }
+    pub fn mesh(&self, sdf: &CpuEvaluator, grid: &Grid) -> Mesh {
+        match *self {
+            Self::MarchingCubes => marching_cubes::marching_cubes(sdf, grid),
+            Self::DualContouring { tolerance } => {
+                dual_contouring::dual_contouring(sdf, grid, tolerance)
+            }
+        }
+    }
+}
+
+/// Samples of a block of cells, together with the corners of its last cells
+pub struct Block {
+    pub start: [usize; 3],
+    pub size: [usize; 3],
+    values: Vec<f32>,
+}
+
+impl Block {
+    pub fn sample(grid: &Grid, sdf: &CpuEvaluator, start: [usize; 3], size: [usize; 3]) -> Self {
+        let mut values = Vec::with_capacity((size[0] + 1) * (size[1] + 1) * (size[2] + 1));
+        for z in 0..=size[2] {
+            for y in 0..=size[1] {
+                for x in 0..=size[0] {
+                    let point = grid.point([start[0] + x, start[1] + y, start[2] + z]);
+                    values.push(sdf.distance(point) - grid.iso_level);
+                }
+            }
+        }
+        Self {
+            start,
+            size,
+            values,
+        }
+    }
+    /// Distance less the iso level at the sample `index` relative to the start
+    pub fn value(&self, index: [usize; 3]) -> f32 {
+        let [x, y, z] = self.size.map(|s| s + 1);
+        debug_assert!(index[0] < x && index[1] < y && index[2] < z);
+        self.values[index[0] + x * (index[1] + y * index[2])]
+    }
+}
+
+/// Vertex at `position` with the normal and color of the surface there
+pub fn surface_vert(sdf: &CpuEvaluator, position: Vector3<f32>) -> Vert {
+    let normal = sdf.gradient(position);
+    let color = sdf.color(position);
+    Vert {
+        pos: Point3::from(position),
+        uv: Point2::origin(),
+        col: Point3::from(color),
+        norm: normal.try_normalize(f32::EPSILON).unwrap_or_default(),
+    }
+}
+
+/// Mesh with vertices at `positions`, which are shaded in parallel
+pub fn shade(sdf: &CpuEvaluator, positions: &[Vector3<f32>], elements: Vec<u32>) -> Mesh {
+    let workers = thread::available_parallelism().map_or(1, |n| n.get());
+    let chunk = positions.len().div_ceil(workers);
+    let verts = thread::scope(|scope| {
+        let handles: Vec<_> = positions
+            .chunks(chunk.max(1))
+            .map(|chunk| {
+                scope.spawn(move || {
+                    chunk
+                        .iter()
+                        .map(|position| surface_vert(sdf, *position))
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect();
+        handles
+            .into_iter()
+            .flat_map(|handle| handle.join().unwrap())
+            .collect()
+    });
+    Mesh { elements, verts }
+}
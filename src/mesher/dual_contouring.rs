@@ -0,0 +1,481 @@
+//! Dual contouring, one vertex per cell the surface crosses and one quad per
+//! crossed edge, joining the vertices of the four cells around the edge.
+//!
+//! Vertices are placed where they are closest to the tangent planes of the
+//! surface at the crossings of the edges of their cell, solving a quadratic
+//! error function (QEF) with the gradients of the SDF as the plane normals.
+//! Unlike with marching cubes, a vertex can sit on the edge or corner of a box
+//! and keep it sharp.
+//!
+//! Each block is the root of an octree. Groups of eight cells whose vertices
+//! can be replaced by a single one without moving it further than the
+//! tolerance from the planes are merged, so flat regions get large triangles.
+use std::collections::{HashMap, HashSet};
+
+use nalgebra::{Matrix3, Vector3};
+
+use super::{shade, Block, Grid};
+use crate::{renderer::mesh::Mesh, sdf::cpu::CpuEvaluator};
+
+/// Eigenvalues of the QEF below this fraction of the largest are ignored,
+/// which keeps vertices near the mass point along flat directions
+const SINGULAR_RATIO: f64 = 0.05;
+/// Refinements of the position of an edge crossing
+const CROSSING_STEPS: usize = 8;
+
+/// Sum of the squared distances to a set of planes, kept as the normal
+/// equations together with the mean of the points the planes go through.
+/// The error is a small difference of large sums, so it is kept in `f64`.
+#[derive(Clone, Copy, Debug, Default)]
+struct Qef {
+    ata: Matrix3<f64>,
+    atb: Vector3<f64>,
+    btb: f64,
+    point_sum: Vector3<f64>,
+    points: usize,
+}
+
+impl Qef {
+    /// Adds the plane through `point` with unit `normal`
+    fn add(&mut self, point: Vector3<f32>, normal: Vector3<f32>) {
+        let (point, normal) = (point.cast::<f64>(), normal.cast::<f64>());
+        let b = normal.dot(&point);
+        self.ata += normal * normal.transpose();
+        self.atb += normal * b;
+        self.btb += b * b;
+        self.point_sum += point;
+        self.points += 1;
+    }
+    fn merge(&mut self, other: &Qef) {
+        self.ata += other.ata;
+        self.atb += other.atb;
+        self.btb += other.btb;
+        self.point_sum += other.point_sum;
+        self.points += other.points;
+    }
+    fn mass_point(&self) -> Vector3<f64> {
+        self.point_sum / self.points.max(1) as f64
+    }
+    fn error(&self, x: Vector3<f64>) -> f64 {
+        (x.dot(&(self.ata * x)) - 2.0 * x.dot(&self.atb) + self.btb).max(0.0)
+    }
+    /// Point minimizing the error, kept in the box from `min` to `max`, and
+    /// the error there
+    fn solve(&self, min: Vector3<f32>, max: Vector3<f32>) -> (Vector3<f32>, f32) {
+        let mass = self.mass_point();
+        let eigen = self.ata.symmetric_eigen();
+        let largest = eigen.eigenvalues.amax();
+        let inverse = Vector3::from_fn(|i, _| {
+            let value = eigen.eigenvalues[i];
+            if value > largest * SINGULAR_RATIO {
+                1.0 / value
+            } else {
+                0.0
+            }
+        });
+        let pseudo_inverse =
+            eigen.eigenvectors * Matrix3::from_diagonal(&inverse) * eigen.eigenvectors.transpose();
+        let x = mass + pseudo_inverse * (self.atb - self.ata * mass);
+        let x = x.sup(&min.cast()).inf(&max.cast());
+        (x.cast(), self.error(x) as f32)
+    }
+}
+
+/// Vertex of a cell, or of a group of cells that were merged
+struct Vertex {
+    position: Vector3<f32>,
+    qef: Qef,
+    /// Vertex of the group this one was merged into
+    merged: Option<usize>,
+}
+
+/// Octree node of a block
+enum Node {
+    /// No cell under the node is crossed by the surface
+    Empty,
+    /// All cells under the node share a vertex
+    Leaf(usize),
+    /// The cells under the node keep vertices of their own
+    Split,
+}
+
+/// Vertices of the crossed cells of a block and the crossed edges it owns
+struct BlockMesh {
+    vertices: Vec<Vector3<f32>>,
+    /// Cell by its sample index, and index into `vertices`
+    cells: Vec<(usize, usize)>,
+    /// First sample, axis, and whether the edge leads out of the volume
+    edges: Vec<([usize; 3], usize, bool)>,
+}
+
+struct BlockContour<'a> {
+    grid: &'a Grid,
+    sdf: &'a CpuEvaluator,
+    block: Block,
+    tolerance: f32,
+    vertices: Vec<Vertex>,
+    /// Vertex of every crossed cell, relative to the start of the block
+    cells: Vec<([usize; 3], usize)>,
+}
+
+impl BlockContour<'_> {
+    fn inside(&self, sample: [usize; 3]) -> bool {
+        self.block.value(sample) < 0.0
+    }
+    fn global(&self, local: [usize; 3]) -> [usize; 3] {
+        [0, 1, 2].map(|i| self.block.start[i] + local[i])
+    }
+
+    /// Point and normal where the surface crosses the edge from `sample`
+    /// along `axis`
+    fn crossing(&self, sample: [usize; 3], axis: usize) -> (Vector3<f32>, Vector3<f32>) {
+        let mut end = sample;
+        end[axis] += 1;
+        let (mut a, mut b) = (
+            self.grid.point(self.global(sample)),
+            self.grid.point(self.global(end)),
+        );
+        let (mut va, mut vb) = (self.block.value(sample), self.block.value(end));
+        let mut point = a;
+        // False position, the distance along an edge is close to linear.
+        // Near edges of the volume it has a kink and one end would never
+        // move, so the value kept at an end that stays is halved (Illinois).
+        let mut kept = 0;
+        for _ in 0..CROSSING_STEPS {
+            let t = (va / (va - vb)).clamp(0.0, 1.0);
+            point = a.lerp(&b, t);
+            let value = self.sdf.distance(point) - self.grid.iso_level;
+            if value == 0.0 {
+                break;
+            }
+            if (value < 0.0) == (va < 0.0) {
+                (a, va) = (point, value);
+                if kept == 1 {
+                    vb /= 2.0;
+                }
+                kept = 1;
+            } else {
+                (b, vb) = (point, value);
+                if kept == -1 {
+                    va /= 2.0;
+                }
+                kept = -1;
+            }
+        }
+        let normal = self.sdf.gradient(point);
+        (
+            point,
+            normal.try_normalize(f32::EPSILON).unwrap_or_default(),
+        )
+    }
+
+    /// Vertex of the cell at `cell`, if the surface crosses it
+    fn cell_vertex(&mut self, cell: [usize; 3]) -> Option<usize> {
+        let sample = |corner: usize| [0, 1, 2].map(|i| cell[i] + (corner >> i & 1));
+        let mut qef = Qef::default();
+        for axis in 0..3 {
+            for corner in (0..8).filter(|corner| corner & 1 << axis == 0) {
+                let start = sample(corner);
+                let mut end = start;
+                end[axis] += 1;
+                if self.inside(start) != self.inside(end) {
+                    let (point, normal) = self.crossing(start, axis);
+                    qef.add(point, normal);
+                }
+            }
+        }
+        if qef.points == 0 {
+            return None;
+        }
+        let global = self.global(cell);
+        let min = self.grid.point(global);
+        let max = self.grid.point(global.map(|i| i + 1));
+        let (position, _) = qef.solve(min, max);
+        self.vertices.push(Vertex {
+            position,
+            qef,
+            merged: None,
+        });
+        self.cells.push((cell, self.vertices.len() - 1));
+        Some(self.vertices.len() - 1)
+    }
+
+    /// Whether the surface inside the node at `origin` spanning `size` cells
+    /// has the same shape as what its corners alone describe. Every sample in
+    /// the middle of an edge, face or the node has to be on the side of one of
+    /// the corners of that edge, face or node.
+    fn keeps_topology(&self, origin: [usize; 3], size: usize) -> bool {
+        let half = size / 2;
+        for middle in 0..27usize {
+            let steps = [middle % 3, middle / 3 % 3, middle / 9];
+            let sample = |steps: [usize; 3]| [0, 1, 2].map(|i| origin[i] + steps[i] * half);
+            if !steps.contains(&1) {
+                continue;
+            }
+            let side = self.inside(sample(steps));
+            // Corners of the edge, face or node the sample is the middle of
+            let free: Vec<usize> = (0..3).filter(|i| steps[*i] == 1).collect();
+            let agrees = (0..1usize << free.len()).any(|bits| {
+                let mut corner = steps;
+                for (k, axis) in free.iter().enumerate() {
+                    corner[*axis] = (bits >> k & 1) * 2;
+                }
+                self.inside(sample(corner)) == side
+            });
+            if !agrees {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Builds the octree below the node at `origin` spanning `size` cells,
+    /// merging vertices bottom up
+    fn simplify(&mut self, origin: [usize; 3], size: usize) -> Node {
+        if (0..3).any(|i| origin[i] >= self.block.size[i]) {
+            return Node::Empty;
+        }
+        if size == 1 {
+            return match self.cell_vertex(origin) {
+                Some(vertex) => Node::Leaf(vertex),
+                None => Node::Empty,
+            };
+        }
+        let half = size / 2;
+        let mut leaves = Vec::new();
+        let mut split = false;
+        for child in 0..8 {
+            let child_origin = [0, 1, 2].map(|i| origin[i] + (child >> i & 1) * half);
+            match self.simplify(child_origin, half) {
+                Node::Empty => {}
+                Node::Leaf(vertex) => leaves.push(vertex),
+                Node::Split => split = true,
+            }
+        }
+        if split || leaves.is_empty() {
+            return if split { Node::Split } else { Node::Empty };
+        }
+        let partial = (0..3).any(|i| origin[i] + size > self.block.size[i]);
+        if partial || !self.keeps_topology(origin, size) {
+            return Node::Split;
+        }
+        let mut qef = Qef::default();
+        for vertex in &leaves {
+            qef.merge(&self.vertices[*vertex].qef);
+        }
+        let global = self.global(origin);
+        let min = self.grid.point(global);
+        let max = self.grid.point(global.map(|i| i + size));
+        let (position, error) = qef.solve(min, max);
+        if error > self.tolerance * self.tolerance {
+            return Node::Split;
+        }
+        self.vertices.push(Vertex {
+            position,
+            qef,
+            merged: None,
+        });
+        let merged = self.vertices.len() - 1;
+        for vertex in leaves {
+            self.vertices[vertex].merged = Some(merged);
+        }
+        Node::Leaf(merged)
+    }
+
+    /// Final vertex of a cell vertex, after all the merges it went through
+    fn resolve(&self, mut vertex: usize) -> usize {
+        while let Some(merged) = self.vertices[vertex].merged {
+            vertex = merged;
+        }
+        vertex
+    }
+
+    fn finish(self) -> BlockMesh {
+        // Only vertices that were not merged away end up in the mesh
+        let mut compact = HashMap::new();
+        let mut vertices = Vec::new();
+        let mut cells = Vec::with_capacity(self.cells.len());
+        for (cell, vertex) in &self.cells {
+            let vertex = self.resolve(*vertex);
+            let index = *compact.entry(vertex).or_insert_with(|| {
+                vertices.push(self.vertices[vertex].position);
+                vertices.len() - 1
+            });
+            cells.push((self.grid.sample_index(self.global(*cell)), index));
+        }
+        let mut edges = Vec::new();
+        let size = self.block.size;
+        for z in 0..=size[2] {
+            for y in 0..=size[1] {
+                for x in 0..=size[0] {
+                    let sample = [x, y, z];
+                    for axis in (0..3).filter(|axis| sample[*axis] < size[*axis]) {
+                        let mut end = sample;
+                        end[axis] += 1;
+                        let inside = self.inside(sample);
+                        if inside != self.inside(end) {
+                            edges.push((self.global(sample), axis, inside));
+                        }
+                    }
+                }
+            }
+        }
+        BlockMesh {
+            vertices,
+            cells,
+            edges,
+        }
+    }
+}
+
+/// Triangulates the surface of `sdf` at the iso level of `grid` keeping its
+/// sharp edges and corners, with normals from the gradient and colors from
+/// the materials of the surface.
+///
+/// Cells are merged as long as their common vertex is within `tolerance` of
+/// the surface, measured as the root of the summed squared distances to the
+/// tangent planes of the merged cells. A tolerance of 0 only merges cells of
+/// perfectly flat regions.
+pub fn dual_contouring(sdf: &CpuEvaluator, grid: &Grid, tolerance: f32) -> Mesh {
+    let blocks = grid.par_blocks(sdf, |start, size| {
+        let mut contour = BlockContour {
+            grid,
+            sdf,
+            block: Block::sample(grid, sdf, start, size),
+            tolerance,
+            vertices: Vec::new(),
+            cells: Vec::new(),
+        };
+        let root = size.into_iter().max().unwrap_or(1).next_power_of_two();
+        contour.simplify([0; 3], root);
+        contour.finish()
+    });
+    let mut positions = Vec::new();
+    let mut cells = HashMap::new();
+    for block in &blocks {
+        for (cell, vertex) in &block.cells {
+            cells.insert(*cell, (positions.len() + vertex) as u32);
+        }
+        positions.extend(&block.vertices);
+    }
+    // Edges on the boundary of two blocks were found by both
+    let mut done = HashSet::new();
+    let mut elements = Vec::new();
+    for (sample, axis, inside) in blocks.iter().flat_map(|block| &block.edges) {
+        if !done.insert(grid.sample_index(*sample) * 3 + axis) {
+            continue;
+        }
+        let (u, v) = ((axis + 1) % 3, (axis + 2) % 3);
+        // Counterclockwise seen from the end of the edge
+        let around = [(1, 1), (0, 1), (0, 0), (1, 0)].map(|(du, dv)| {
+            let mut cell = *sample;
+            if cell[u] < du || cell[v] < dv {
+                return None;
+            }
+            cell[u] -= du;
+            cell[v] -= dv;
+            cells.get(&grid.sample_index(cell)).copied()
+        });
+        // Edges on the boundary of the grid miss some of their cells
+        let Some(mut quad) = around.into_iter().collect::<Option<Vec<u32>>>() else {
+            continue;
+        };
+        if !inside {
+            quad.reverse();
+        }
+        // Merged cells share vertices, leaving triangles or nothing
+        if quad[0] == quad[2] || quad[1] == quad[3] {
+            continue;
+        }
+        let position = |i: usize| -> Vector3<f32> { positions[quad[i] as usize] };
+        let split = if (position(0) - position(2)).norm() <= (position(1) - position(3)).norm() {
+            0
+        } else {
+            1
+        };
+        for triangle in [[0, 1, 2], [0, 2, 3]] {
+            let triangle = triangle.map(|i| quad[(i + split) % 4]);
+            if triangle[0] != triangle[1]
+                && triangle[1] != triangle[2]
+                && triangle[2] != triangle[0]
+            {
+                elements.extend(triangle);
+            }
+        }
+    }
+    shade(sdf, &positions, elements)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use nalgebra::Vector3;
+
+    use super::dual_contouring;
+    use crate::{
+        mesher::Grid,
+        sdf::{
+            cpu::CpuEvaluator,
+            ir::{SDFPosition, SDFVolume},
+        },
+    };
+
+    fn box_mesh(tolerance: f32) -> crate::renderer::mesh::Mesh {
+        let cube = SDFVolume::Box {
+            position: SDFPosition::Origin,
+            dimensions: Vector3::new(0.6, 0.5, 0.4).into(),
+            fillet: 0.0.into(),
+        };
+        let sdf = CpuEvaluator::new(&cube, 0.0).unwrap();
+        let grid = Grid {
+            min: Vector3::from_element(-1.0),
+            max: Vector3::from_element(1.03),
+            resolution: 37,
+            iso_level: 0.0,
+        };
+        dual_contouring(&sdf, &grid, tolerance)
+    }
+
+    #[test]
+    fn box_keeps_its_corners() {
+        let mesh = box_mesh(0.0);
+        for corner in 0..8 {
+            let corner = Vector3::from_fn(|i, _| {
+                [0.6, 0.5, 0.4][i] * if corner >> i & 1 == 0 { -1.0 } else { 1.0 }
+            });
+            assert!(mesh
+                .verts
+                .iter()
+                .any(|vert| (vert.pos.coords - corner).norm() < 1e-3));
+        }
+        // Closed, with every edge shared by two triangles running opposite
+        // ways, and facing out
+        let mut edges = HashMap::new();
+        for triangle in mesh.elements.chunks(3) {
+            for i in 0..3 {
+                *edges
+                    .entry((triangle[i], triangle[(i + 1) % 3]))
+                    .or_insert(0) += 1;
+            }
+            let [a, b, c] = [0, 1, 2].map(|i| mesh.verts[triangle[i] as usize].pos.coords);
+            let normal = (b - a).cross(&(c - a));
+            assert!(normal.dot(&(a + b + c)) > 0.0);
+        }
+        for ((a, b), count) in &edges {
+            assert_eq!(*count, 1);
+            assert_eq!(edges.get(&(*b, *a)), Some(&1));
+        }
+    }
+
+    #[test]
+    fn flat_faces_are_simplified() {
+        let fine = box_mesh(0.0);
+        let coarse = box_mesh(1e-3);
+        assert!(coarse.elements.len() * 2 < fine.elements.len());
+        assert!(coarse
+            .verts
+            .iter()
+            .any(|vert| (vert.pos.coords - Vector3::new(0.6, 0.5, 0.4)).norm() < 1e-3));
+    }
+}
@@ -0,0 +1,231 @@
+//! Marching cubes, one polygon per connected crossing of the surface with a
+//! cell, with a vertex on every cell edge the surface crosses.
+//!
+//! The triangles of each of the 256 sign configurations are derived from the
+//! faces of the cell instead of the usual hand written table. On faces with
+//! two diagonal corners inside, the inside corners are always kept apart.
+//! The choice only depends on the face, so the two cells sharing it agree and
+//! the mesh has no holes.
+use std::{collections::HashMap, sync::OnceLock};
+
+use nalgebra::Vector3;
+
+use super::{shade, Block, Grid};
+use crate::{renderer::mesh::Mesh, sdf::cpu::CpuEvaluator};
+
+/// Corners of a cell are numbered by their offsets, bit `i` of the number
+/// being the offset along axis `i`
+fn corner_offset(corner: usize) -> [usize; 3] {
+    [corner & 1, corner >> 1 & 1, corner >> 2 & 1]
+}
+
+/// Lower corner and axis of each edge of a cell
+fn edges() -> [(usize, usize); 12] {
+    let mut edges = [(0, 0); 12];
+    let mut i = 0;
+    for axis in 0..3 {
+        for corner in 0..8 {
+            if corner & 1 << axis == 0 {
+                edges[i] = (corner, axis);
+                i += 1;
+            }
+        }
+    }
+    edges
+}
+
+fn edge_between(a: usize, b: usize) -> usize {
+    let (corner, axis) = (a.min(b), (a ^ b).trailing_zeros() as usize);
+    edges()
+        .iter()
+        .position(|edge| *edge == (corner, axis))
+        .unwrap()
+}
+
+/// Triangles of a configuration as edges of the cell, wound
+/// counterclockwise seen from outside
+fn triangulate(inside: u8) -> Vec<[u8; 3]> {
+    let is_inside = |corner: usize| inside & 1 << corner != 0;
+    // Across the surface, the edge each crossing leads to on its face
+    let mut next = [None; 12];
+    for axis in 0..3 {
+        let (u, v) = ((axis + 1) % 3, (axis + 2) % 3);
+        for side in 0..2 {
+            // Counterclockwise seen from outside the cell
+            let mut corners =
+                [(0, 0), (1, 0), (1, 1), (0, 1)].map(|(cu, cv)| side << axis | cu << u | cv << v);
+            if side == 0 {
+                corners.reverse();
+            }
+            let mut crossings: Vec<(usize, bool)> = (0..4)
+                .map(|k| (corners[k], corners[(k + 1) % 4]))
+                .filter(|(a, b)| is_inside(*a) != is_inside(*b))
+                .map(|(a, b)| (edge_between(a, b), is_inside(b)))
+                .collect();
+            // Start with a crossing into the inside, so every run of inside
+            // corners is closed off by the following crossing out of it
+            if let Some(first) = crossings.iter().position(|(_, entry)| *entry) {
+                crossings.rotate_left(first);
+            }
+            for pair in crossings.chunks(2) {
+                let [(entry, _), (exit, _)] = pair else {
+                    unreachable!()
+                };
+                next[*exit] = Some(*entry);
+            }
+        }
+    }
+    let mut triangles = Vec::new();
+    let mut visited = [false; 12];
+    for start in 0..12 {
+        if visited[start] || next[start].is_none() {
+            continue;
+        }
+        let mut polygon = Vec::new();
+        let mut edge = start;
+        while !visited[edge] {
+            visited[edge] = true;
+            polygon.push(edge as u8);
+            edge = next[edge].expect("crossings form closed loops");
+        }
+        for i in 1..polygon.len() - 1 {
+            triangles.push([polygon[0], polygon[i + 1], polygon[i]]);
+        }
+    }
+    triangles
+}
+
+/// Triangles of every configuration
+fn table() -> &'static [Vec<[u8; 3]>] {
+    static TABLE: OnceLock<Vec<Vec<[u8; 3]>>> = OnceLock::new();
+    TABLE.get_or_init(|| (0..=255).map(triangulate).collect())
+}
+
+/// Vertices on the edges of the cells of a block keyed by edge, and its
+/// triangles
+struct BlockMesh {
+    vertices: Vec<(usize, Vector3<f32>)>,
+    triangles: Vec<[usize; 3]>,
+}
+
+fn mesh_block(grid: &Grid, block: &Block) -> BlockMesh {
+    let edges = edges();
+    let table = table();
+    let mut mesh = BlockMesh {
+        vertices: Vec::new(),
+        triangles: Vec::new(),
+    };
+    let mut known = HashMap::new();
+    for z in 0..block.size[2] {
+        for y in 0..block.size[1] {
+            for x in 0..block.size[0] {
+                let corner_index = |corner: usize| {
+                    let offset = corner_offset(corner);
+                    [x + offset[0], y + offset[1], z + offset[2]]
+                };
+                let values: [f32; 8] = std::array::from_fn(|c| block.value(corner_index(c)));
+                let configuration = (0..8)
+                    .filter(|c| values[*c] < 0.0)
+                    .fold(0, |bits, c| bits | 1 << c);
+                for triangle in &table[configuration] {
+                    let keys = triangle.map(|edge| {
+                        let (corner, axis) = edges[edge as usize];
+                        let local = corner_index(corner);
+                        let global = [0, 1, 2].map(|i| block.start[i] + local[i]);
+                        let key = grid.sample_index(global) * 3 + axis;
+                        *known.entry(key).or_insert_with(|| {
+                            let a = values[corner];
+                            let b = values[corner | 1 << axis];
+                            let t = (a / (a - b)).clamp(0.0, 1.0);
+                            let mut end = global;
+                            end[axis] += 1;
+                            let position = grid.point(global).lerp(&grid.point(end), t);
+                            mesh.vertices.push((key, position));
+                            key
+                        })
+                    });
+                    mesh.triangles.push(keys);
+                }
+            }
+        }
+    }
+    mesh
+}
+
+/// Triangulates the surface of `sdf` at the iso level of `grid`, with
+/// normals from the gradient and colors from the materials of the surface
+pub fn marching_cubes(sdf: &CpuEvaluator, grid: &Grid) -> Mesh {
+    let blocks = grid.par_blocks(sdf, |start, size| {
+        mesh_block(grid, &Block::sample(grid, sdf, start, size))
+    });
+    // Vertices on the boundary of two blocks were found by both
+    let mut indices = HashMap::new();
+    let mut positions = Vec::new();
+    for block in &blocks {
+        for (key, position) in &block.vertices {
+            indices.entry(*key).or_insert_with(|| {
+                positions.push(*position);
+                positions.len() as u32 - 1
+            });
+        }
+    }
+    let elements = blocks
+        .iter()
+        .flat_map(|block| &block.triangles)
+        .flat_map(|triangle| triangle.map(|key| indices[&key]))
+        .collect();
+    shade(sdf, &positions, elements)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use nalgebra::Vector3;
+
+    use super::marching_cubes;
+    use crate::{
+        mesher::Grid,
+        sdf::{
+            cpu::CpuEvaluator,
+            ir::{SDFPosition, SDFVolume},
+        },
+    };
+
+    #[test]
+    fn sphere_is_closed_and_faces_out() {
+        let sphere = SDFVolume::Sphere {
+            position: SDFPosition::Origin,
+            radius: 1.0.into(),
+        };
+        let sdf = CpuEvaluator::new(&sphere, 0.0).unwrap();
+        let grid = Grid {
+            min: Vector3::from_element(-1.5),
+            max: Vector3::from_element(1.5),
+            resolution: 40,
+            iso_level: 0.0,
+        };
+        let mesh = marching_cubes(&sdf, &grid);
+        assert!(!mesh.elements.is_empty());
+        // Every edge is shared by two triangles, running opposite ways
+        let mut edges = HashMap::new();
+        for triangle in mesh.elements.chunks(3) {
+            for i in 0..3 {
+                *edges
+                    .entry((triangle[i], triangle[(i + 1) % 3]))
+                    .or_insert(0) += 1;
+            }
+            let [a, b, c] = [0, 1, 2].map(|i| mesh.verts[triangle[i] as usize].pos.coords);
+            let normal = (b - a).cross(&(c - a));
+            assert!(normal.dot(&(a + b + c)) >= 0.0);
+        }
+        for ((a, b), count) in &edges {
+            assert_eq!(*count, 1);
+            assert_eq!(edges.get(&(*b, *a)), Some(&1));
+        }
+        for vert in &mesh.verts {
+            assert!((vert.pos.coords.norm() - 1.0).abs() < 0.01);
+            assert!((vert.norm - vert.pos.coords.normalize()).norm() < 0.01);
+        }
+    }
+}
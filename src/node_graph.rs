@@ -1,12 +1,33 @@
-use std::{borrow::Cow, collections::HashMap};
+use std::{
+    borrow::Cow,
+    collections::{HashMap, HashSet},
+    thread::JoinHandle,
+};
 
-use egui::{self, DragValue, TextStyle};
+use egui::{self, DragValue};
 use egui_node_graph::*;
 
-use crate::{renderer::shader::Shader, sdf::SDFBuilder};
+#[cfg(feature = "persistence")]
+use crate::project::{Project, ProjectDialog, ProjectDialogKind, ProjectDialogResponse};
+use crate::{
+    animation::{Animation, Interpolation, Keyframe, TrackId},
+    camera::Camera,
+    export::{ExportDialog, ExportDialogResponse, ExportError, ExportFormat, ExportSettings},
+    history::History,
+    group::{collapse_selection, GroupId, GroupPort, NodeGroup, NodeGroups},
+    nodes::{self, NodeBuilder, NodeDefinition},
+    playback::Playback,
+    renderer::shader::Shader,
+    sdf::{
+        expr::{Expr, Value, ValueKind},
+        formula::FormulaError,
+        snippet::SnippetError,
+        ir::{Matcap, Material, SDFPosition, SDFVolume, SDF2D},
+    },
+};
 
-type MyGraph = Graph<N3DNodeData, N3DDataType, N3DValueType>;
-type MyEditorState =
+pub type MyGraph = Graph<N3DNodeData, N3DDataType, N3DValueType>;
+pub type MyEditorState =
     GraphEditorState<N3DNodeData, N3DDataType, N3DValueType, N3DNodeTemplate, MyGraphState>;
 
 // ========= First, define your user data types =============
@@ -14,15 +35,41 @@ type MyEditorState =
 /// The NodeData holds a custom data struct inside each node. It's useful to
 /// store additional information that doesn't live in parameters. For this
 /// example, the node data stores the template (i.e. the "type") of the node.
+#[derive(Clone)]
 #[cfg_attr(feature = "persistence", derive(serde::Serialize, serde::Deserialize))]
 pub struct N3DNodeData {
     template: N3DNodeTemplate,
+    /// Name given by the user, empty for none. Unlike the label it tells
+    /// nodes of the same type apart, for `n3d mesh --node` for example
+    #[cfg_attr(feature = "persistence", serde(default))]
+    name: String,
+}
+
+impl N3DNodeData {
+    pub fn new(template: N3DNodeTemplate) -> Self {
+        Self {
+            template,
+            name: String::new(),
+        }
+    }
+
+    pub fn template(&self) -> N3DNodeTemplate {
+        self.template
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn set_name(&mut self, name: String) {
+        self.name = name;
+    }
 }
 
 /// `DataType`s are what defines the possible range of connections when
 /// attaching two ports together. The graph UI will make sure to not allow
 /// attaching incompatible datatypes.
-#[derive(PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 #[cfg_attr(feature = "persistence", derive(serde::Serialize, serde::Deserialize))]
 pub enum N3DDataType {
     Scalar,
@@ -30,6 +77,10 @@ pub enum N3DDataType {
     Vec3,
     SDFPosition,
     SDFVolume,
+    SDF2D,
+    Text,
+    Material,
+    Matcap,
 }
 
 /// In the graph, input parameters can optionally have a constant value. This
@@ -39,14 +90,23 @@ pub enum N3DDataType {
 /// this library makes no attempt to check this consistency. For instance, it is
 /// up to the user code in this example to make sure no parameter is created
 /// with a DataType of Scalar and a ValueType of Vec2.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 #[cfg_attr(feature = "persistence", derive(serde::Serialize, serde::Deserialize))]
 pub enum N3DValueType {
     Scalar { value: f32 },
     Vec2 { value: nalgebra::Vector2<f32> },
     Vec3 { value: nalgebra::Vector3<f32> },
-    SDFPosition { value: String },
-    SDFVolume { value: String },
+    SDFPosition { value: SDFPosition },
+    SDFVolume { value: SDFVolume },
+    SDF2D { value: SDF2D },
+    Text { value: String },
+    /// Text spanning several lines, like source code, for a text input
+    Code { value: String },
+    Material { value: Material },
+    Matcap { value: Matcap },
+    /// Scalar or vector output that depends on values only known while
+    /// rendering, like the playback time. Never used for inline values.
+    Expr { value: Expr },
 }
 
 impl Default for N3DValueType {
@@ -58,40 +118,121 @@ impl Default for N3DValueType {
 }
 
 impl N3DValueType {
+    /// Name of the type of this value, as shown in errors
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            N3DValueType::Scalar { .. } => "scalar",
+            N3DValueType::Vec2 { .. } => "vec2",
+            N3DValueType::Vec3 { .. } => "vec3",
+            N3DValueType::SDFPosition { .. } => "SDF position",
+            N3DValueType::SDFVolume { .. } => "SDF volume",
+            N3DValueType::SDF2D { .. } => "SDF 2D",
+            N3DValueType::Text { .. } | N3DValueType::Code { .. } => "text",
+            N3DValueType::Material { .. } => "material",
+            N3DValueType::Matcap { .. } => "matcap",
+            N3DValueType::Expr { value } => value.kind().glsl(),
+        }
+    }
+    fn mismatch(&self, expected: &'static str) -> EvalError {
+        EvalError::TypeMismatch {
+            expected,
+            found: self.type_name(),
+        }
+    }
     /// Tries to downcast this value type to a vector
-    pub fn try_to_vec2(self) -> anyhow::Result<nalgebra::Vector2<f32>> {
+    pub fn try_to_vec2(self) -> Result<nalgebra::Vector2<f32>, EvalError> {
         if let N3DValueType::Vec2 { value } = self {
             Ok(value)
         } else {
-            anyhow::bail!("Invalid cast from {:?} to vec2", self)
+            Err(self.mismatch("vec2"))
         }
     }
-    pub fn try_to_vec3(self) -> anyhow::Result<nalgebra::Vector3<f32>> {
+    pub fn try_to_vec3(self) -> Result<nalgebra::Vector3<f32>, EvalError> {
         if let N3DValueType::Vec3 { value } = self {
             Ok(value)
         } else {
-            anyhow::bail!("Invalid cast from {:?} to vec3", self)
+            Err(self.mismatch("vec3"))
         }
     }
-    pub fn try_to_scalar(self) -> anyhow::Result<f32> {
+    pub fn try_to_scalar(self) -> Result<f32, EvalError> {
         if let N3DValueType::Scalar { value } = self {
             Ok(value)
         } else {
-            anyhow::bail!("Invalid cast from {:?} to scalar", self)
+            Err(self.mismatch("scalar"))
         }
     }
-    pub fn try_to_sdf_position(self) -> anyhow::Result<String> {
+    pub fn try_to_sdf_position(self) -> Result<SDFPosition, EvalError> {
         if let N3DValueType::SDFPosition { value } = self {
-            Ok(value.clone())
+            Ok(value)
         } else {
-            anyhow::bail!("Invalid cast from {:?} to SDFPosition", self)
+            Err(self.mismatch("SDF position"))
         }
     }
-    pub fn try_to_sdf_volume(self) -> anyhow::Result<String> {
+    pub fn try_to_sdf_volume(self) -> Result<SDFVolume, EvalError> {
         if let N3DValueType::SDFVolume { value } = self {
-            Ok(value.clone())
+            Ok(value)
+        } else {
+            Err(self.mismatch("SDF volume"))
+        }
+    }
+    /// Converts any scalar or vector of the given kind to an expression
+    pub fn try_to_expr(self, kind: ValueKind) -> Result<Expr, EvalError> {
+        let expr = match self {
+            N3DValueType::Scalar { value } => Expr::from(value),
+            N3DValueType::Vec2 { value } => Expr::from(value),
+            N3DValueType::Vec3 { value } => Expr::from(value),
+            N3DValueType::Expr { ref value } => value.clone(),
+            _ => return Err(self.mismatch(kind.glsl())),
+        };
+        if expr.kind() != kind {
+            return Err(self.mismatch(kind.glsl()));
+        }
+        Ok(expr)
+    }
+    /// Inline scalar or vector, the kinds of value that can be keyframed
+    pub fn as_value(&self) -> Option<Value> {
+        match self {
+            N3DValueType::Scalar { value } => Some(Value::Scalar(*value)),
+            N3DValueType::Vec2 { value } => Some(Value::Vec2(*value)),
+            N3DValueType::Vec3 { value } => Some(Value::Vec3(*value)),
+            _ => None,
+        }
+    }
+    /// Inverse of [`Self::try_to_expr`], constants become plain values again
+    pub fn from_expr(value: Expr) -> Self {
+        match value {
+            Expr::Const(Value::Scalar(value)) => N3DValueType::Scalar { value },
+            Expr::Const(Value::Vec2(value)) => N3DValueType::Vec2 { value },
+            Expr::Const(Value::Vec3(value)) => N3DValueType::Vec3 { value },
+            value => N3DValueType::Expr { value },
+        }
+    }
+    pub fn try_to_text(self) -> Result<String, EvalError> {
+        if let N3DValueType::Text { value } | N3DValueType::Code { value } = self {
+            Ok(value)
+        } else {
+            Err(self.mismatch("text"))
+        }
+    }
+    pub fn try_to_sdf_2d(self) -> Result<SDF2D, EvalError> {
+        if let N3DValueType::SDF2D { value } = self {
+            Ok(value)
+        } else {
+            Err(self.mismatch("SDF 2D"))
+        }
+    }
+    pub fn try_to_material(self) -> Result<Material, EvalError> {
+        if let N3DValueType::Material { value } = self {
+            Ok(value)
+        } else {
+            Err(self.mismatch("material"))
+        }
+    }
+    pub fn try_to_matcap(self) -> Result<Matcap, EvalError> {
+        if let N3DValueType::Matcap { value } = self {
+            Ok(value)
         } else {
-            anyhow::bail!("Invalid cast from {:?} to SDFValue", self)
+            Err(self.mismatch("matcap"))
         }
     }
 }
@@ -100,42 +241,83 @@ impl N3DValueType {
 /// will display in the "new node" popup. The user code needs to tell the
 /// library how to convert a NodeTemplate into a Node.
 #[derive(Clone, Copy)]
-#[cfg_attr(feature = "persistence", derive(serde::Serialize, serde::Deserialize))]
 pub enum N3DNodeTemplate {
-    NewScalar,
-    ScalarAdd,
-    ScalarSub,
-    NewVec2,
-    Vec2Add,
-    Vec2Subtract,
-    Vec2ScalarMul,
-    NewVec3,
-    Vec3Add,
-    Vec3Sub,
-    Vec3Dot,
-    Vec3Cross,
-    SDFPosition,
-    SDFTranslate,
-    SDFRotate,
-    SDFScale,
-    SDFBox,
-    SDFSphere,
-    SDFCylinder,
-    SDFUnion,
-    SDFSmoothUnion,
-    SDFDiff,
-    SDFSmoothDiff,
-    SDFViewer,
+    /// Built-in or registered node, see [`nodes`]
+    Node(&'static dyn NodeDefinition),
+    /// Instance of a group from [`MyGraphState::groups`]
+    Group(GroupId),
+}
+
+/// Nodes are stored as unit variants named after their type, so projects
+/// look the same whether a node is built in or registered
+#[cfg(feature = "persistence")]
+impl serde::Serialize for N3DNodeTemplate {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            N3DNodeTemplate::Node(node) => {
+                serializer.serialize_unit_variant("N3DNodeTemplate", 0, node.name())
+            }
+            N3DNodeTemplate::Group(id) => {
+                serializer.serialize_newtype_variant("N3DNodeTemplate", 1, "Group", id)
+            }
+        }
+    }
+}
+
+#[cfg(feature = "persistence")]
+impl<'de> serde::Deserialize<'de> for N3DNodeTemplate {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        use serde::de::{self, VariantAccess};
+
+        /// Name of the variant, read as an identifier like derived enums do
+        struct Name(String);
+        impl<'de> serde::Deserialize<'de> for Name {
+            fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                struct NameVisitor;
+                impl<'de> de::Visitor<'de> for NameVisitor {
+                    type Value = Name;
+                    fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                        f.write_str("a node type")
+                    }
+                    fn visit_str<E: de::Error>(self, name: &str) -> Result<Name, E> {
+                        Ok(Name(name.to_string()))
+                    }
+                }
+                deserializer.deserialize_identifier(NameVisitor)
+            }
+        }
+
+        struct TemplateVisitor;
+        impl<'de> de::Visitor<'de> for TemplateVisitor {
+            type Value = N3DNodeTemplate;
+            fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                f.write_str("a node type")
+            }
+            fn visit_enum<A: de::EnumAccess<'de>>(self, data: A) -> Result<Self::Value, A::Error> {
+                let (Name(name), variant) = data.variant()?;
+                if name == "Group" {
+                    return variant.newtype_variant().map(N3DNodeTemplate::Group);
+                }
+                variant.unit_variant()?;
+                nodes::find(&name).map(N3DNodeTemplate::Node).ok_or_else(|| {
+                    de::Error::custom(format!("unknown node type {}", name))
+                })
+            }
+        }
+        deserializer.deserialize_enum("N3DNodeTemplate", &[], TemplateVisitor)
+    }
 }
 
 /// The response type is used to encode side-effects produced when drawing a
 /// node in the graph. Most side-effects (creating new nodes, deleting existing
 /// nodes, handling connections...) are already handled by the library, but this
 /// mechanism allows creating additional side effects from user code.
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub enum MyResponse {
     SetActiveNode(NodeId),
     ClearActiveNode,
+    EditGroup(GroupId),
+    RenameNode(NodeId, String),
 }
 
 /// The graph 'global' state. This state struct is passed around to the node and
@@ -145,7 +327,22 @@ pub enum MyResponse {
 #[cfg_attr(feature = "persistence", derive(serde::Serialize, serde::Deserialize))]
 pub struct MyGraphState {
     pub active_node: Option<NodeId>,
+    #[cfg_attr(feature = "persistence", serde(skip))]
     pub shader: Shader, 
+    #[cfg_attr(feature = "persistence", serde(default))]
+    pub animation: Animation,
+    /// Frame the graph is evaluated and keyed at
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    pub frame: f32,
+    #[cfg_attr(feature = "persistence", serde(default))]
+    pub groups: NodeGroups,
+    /// Group whose inside is shown in the editor. Groups are not animated,
+    /// so nothing can be keyed while one is edited.
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    pub editing_group: Option<GroupId>,
+    /// Errors of the last evaluation, shown on the nodes they happened in
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    pub diagnostics: HashMap<NodeId, String>,
 }
 
 // =========== Then, you need to implement some traits ============
@@ -159,6 +356,10 @@ impl DataTypeTrait<MyGraphState> for N3DDataType {
             N3DDataType::Vec3 => egui::Color32::from_rgb(148, 255, 0),
             N3DDataType::SDFPosition => egui::Color32::from_rgb(99, 99, 199),
             N3DDataType::SDFVolume => egui::Color32::from_rgb(247, 37, 133),
+            N3DDataType::SDF2D => egui::Color32::from_rgb(255, 140, 66),
+            N3DDataType::Text => egui::Color32::from_rgb(200, 200, 200),
+            N3DDataType::Material => egui::Color32::from_rgb(72, 202, 228),
+            N3DDataType::Matcap => egui::Color32::from_rgb(72, 202, 228),
         }
     }
 
@@ -169,6 +370,10 @@ impl DataTypeTrait<MyGraphState> for N3DDataType {
             N3DDataType::Vec3 => Cow::Borrowed("vec3"),
             N3DDataType::SDFPosition => Cow::Borrowed("SDF position"),
             N3DDataType::SDFVolume => Cow::Borrowed("SDF Volume"),
+            N3DDataType::SDF2D => Cow::Borrowed("SDF 2D"),
+            N3DDataType::Text => Cow::Borrowed("text"),
+            N3DDataType::Material => Cow::Borrowed("material"),
+            N3DDataType::Matcap => Cow::Borrowed("matcap"),
         }
     }
 }
@@ -182,62 +387,21 @@ impl NodeTemplateTrait for N3DNodeTemplate {
     type UserState = MyGraphState;
     type CategoryType = &'static str;
 
-    fn node_finder_label(&self, _user_state: &mut Self::UserState) -> Cow<'_, str> {
-        Cow::Borrowed(match self {
-            N3DNodeTemplate::NewScalar => "New scalar",
-            N3DNodeTemplate::ScalarAdd => "Scalar add",
-            N3DNodeTemplate::ScalarSub => "Scalar subtract",
-            N3DNodeTemplate::NewVec2 => "New Vec2",
-            N3DNodeTemplate::Vec2Add => "Vec2 Add",
-            N3DNodeTemplate::Vec2Subtract => "Vec2 Subtract",
-            N3DNodeTemplate::Vec2ScalarMul => "Vec2 Scalar Multiply",
-            N3DNodeTemplate::NewVec3 => "New Vec3",
-            N3DNodeTemplate::Vec3Add => "Vec3 Add",
-            N3DNodeTemplate::Vec3Sub => "Vec3 Sub",
-            N3DNodeTemplate::Vec3Dot => "Vec3 Dot",
-            N3DNodeTemplate::Vec3Cross => "Vec3 Cross",
-            N3DNodeTemplate::SDFPosition => "SDF Position",
-            N3DNodeTemplate::SDFTranslate => "SDF Translate",
-            N3DNodeTemplate::SDFRotate => "SDF Rotate",
-            N3DNodeTemplate::SDFScale => "SDF Scale",
-            N3DNodeTemplate::SDFBox => "SDF Box",
-            N3DNodeTemplate::SDFSphere => "SDF Sphere",
-            N3DNodeTemplate::SDFCylinder => "SDF Cylinder",
-            N3DNodeTemplate::SDFUnion => "SDF Union",
-            N3DNodeTemplate::SDFSmoothUnion => "SDFSmoothUnion",
-            N3DNodeTemplate::SDFDiff => "SDFDiff",
-            N3DNodeTemplate::SDFSmoothDiff => "SDFSmoothDiff",
-            N3DNodeTemplate::SDFViewer => "SDF Viewer",
-        })
+    fn node_finder_label(&self, user_state: &mut Self::UserState) -> Cow<'_, str> {
+        match self {
+            N3DNodeTemplate::Node(node) => Cow::Borrowed(node.label()),
+            N3DNodeTemplate::Group(id) => match user_state.groups.get(*id) {
+                Some(group) => Cow::Owned(group.name.clone()),
+                None => Cow::Borrowed("Missing group"),
+            },
+        }
     }
 
     // this is what allows the library to show collapsible lists in the node finder.
     fn node_finder_categories(&self, _user_state: &mut Self::UserState) -> Vec<&'static str> {
         match self {
-            N3DNodeTemplate::NewScalar
-            | N3DNodeTemplate::ScalarAdd
-            | N3DNodeTemplate::ScalarSub => vec!["Scalar"],
-            N3DNodeTemplate::NewVec2 | N3DNodeTemplate::Vec2Add | N3DNodeTemplate::Vec2Subtract => {
-                vec!["Vec2"]
-            }
-            N3DNodeTemplate::Vec2ScalarMul => vec!["Vec2", "Scalar"],
-            N3DNodeTemplate::NewVec3
-            | N3DNodeTemplate::Vec3Add
-            | N3DNodeTemplate::Vec3Sub
-            | N3DNodeTemplate::Vec3Dot
-            | N3DNodeTemplate::Vec3Cross => vec!["Vec3"],
-            N3DNodeTemplate::SDFPosition
-            | N3DNodeTemplate::SDFTranslate
-            | N3DNodeTemplate::SDFRotate
-            | N3DNodeTemplate::SDFScale
-            | N3DNodeTemplate::SDFBox
-            | N3DNodeTemplate::SDFSphere
-            | N3DNodeTemplate::SDFCylinder
-            | N3DNodeTemplate::SDFUnion
-            | N3DNodeTemplate::SDFSmoothUnion
-            | N3DNodeTemplate::SDFDiff
-            | N3DNodeTemplate::SDFSmoothDiff
-            | N3DNodeTemplate::SDFViewer => vec!["SDF"],
+            N3DNodeTemplate::Node(node) => node.categories(),
+            N3DNodeTemplate::Group(_) => vec!["Groups"],
         }
     }
 
@@ -248,266 +412,61 @@ impl NodeTemplateTrait for N3DNodeTemplate {
     }
 
     fn user_data(&self, _user_state: &mut Self::UserState) -> Self::NodeData {
-        N3DNodeData { template: *self }
+        N3DNodeData::new(*self)
     }
 
     fn build_node(
         &self,
         graph: &mut Graph<Self::NodeData, Self::DataType, Self::ValueType>,
-        _user_state: &mut Self::UserState,
+        user_state: &mut Self::UserState,
         node_id: NodeId,
     ) {
         // The nodes are created empty by default. This function needs to take
         // care of creating the desired inputs and outputs based on the template
+        match self {
+            N3DNodeTemplate::Node(node) => {
+                node.build(&mut NodeBuilder { graph, node_id });
+                node.update_ports(graph, node_id);
+            }
+            N3DNodeTemplate::Group(id) => {
+                if let Some(group) = user_state.groups.get(*id) {
+                    sync_group_ports(graph, node_id, group);
+                }
+            }
+        }
+    }
+}
 
-        // We define some closures here to avoid boilerplate. Note that this is
-        // entirely optional.
-        let input_scalar = |graph: &mut MyGraph, name: &str| {
-            graph.add_input_param(
-                node_id,
-                name.to_string(),
-                N3DDataType::Scalar,
-                N3DValueType::Scalar { value: 0.0 },
-                InputParamKind::ConnectionOrConstant,
-                true,
-            );
-        };
-        let output_scalar = |graph: &mut MyGraph, name: &str| {
-            graph.add_output_param(node_id, name.to_string(), N3DDataType::Scalar);
-        };
-        let input_sdf_position = |graph: &mut MyGraph, name: &str| {
-            graph.add_input_param(
-                node_id,
-                name.to_string(),
-                N3DDataType::SDFPosition,
-                N3DValueType::SDFPosition { value: "p".to_string() },
-                InputParamKind::ConnectionOrConstant,
-                true,
-            );
-        };
-        let output_sdf_position = |graph: &mut MyGraph, name: &str| {
-            graph.add_output_param(node_id, name.to_string(), N3DDataType::SDFPosition);
-        };
-        let input_sdf_volume = |graph: &mut MyGraph, name: &str| {
-            graph.add_input_param(
-                node_id,
-                name.to_string(),
-                N3DDataType::SDFVolume,
-                N3DValueType::SDFVolume { value: "".to_string() },
-                InputParamKind::ConnectionOnly,
-                true,
-            );
-        };
-        let output_sdf_volume = |graph: &mut MyGraph, name: &str| {
-            graph.add_output_param(node_id, name.to_string(), N3DDataType::SDFVolume);
-        };
-        let input_vec2 = |graph: &mut MyGraph, name: &str| {
-            graph.add_input_param(
-                node_id,
-                name.to_string(),
-                N3DDataType::Vec2,
-                N3DValueType::Vec2 {
-                    value: nalgebra::Vector2::new(0.0, 0.0),
-                },
-                InputParamKind::ConnectionOrConstant,
-                true,
-            );
-        };
-        let output_vec2 = |graph: &mut MyGraph, name: &str| {
-            graph.add_output_param(node_id, name.to_string(), N3DDataType::Vec2);
-        };
-        let input_vec3 = |graph: &mut MyGraph, name: &str| {
-            graph.add_input_param(
-                node_id,
-                name.to_string(),
-                N3DDataType::Vec3,
-                N3DValueType::Vec3 {
-                    value: nalgebra::Vector3::new(0.0, 0.0, 0.0),
-                },
-                InputParamKind::ConnectionOrConstant,
-                true,
-            );
-        };
-        let output_vec3 = |graph: &mut MyGraph, name: &str| {
-            graph.add_output_param(node_id, name.to_string(), N3DDataType::Vec3);
-        };
+/// Every available node and the groups that can be placed in the graph being
+/// edited
+pub struct AllN3DNodeTemplates {
+    groups: Vec<GroupId>,
+}
+
+impl AllN3DNodeTemplates {
+    /// Leaves out the groups being edited and the groups containing them,
+    /// which would end up inside themselves
+    pub fn new(groups: &NodeGroups, editing: &[GroupId]) -> Self {
+        let groups = groups
+            .iter()
+            .map(|(id, _)| id)
+            .filter(|id| !editing.iter().any(|edited| groups.contains(*id, *edited)))
+            .collect();
+        Self { groups }
+    }
+}
 
-        match self {
-            N3DNodeTemplate::ScalarAdd => {
-                // The first input param doesn't use the closure so we can comment
-                // it in more detail.
-                graph.add_input_param(
-                    node_id,
-                    // This is the name of the parameter. Can be later used to
-                    // retrieve the value. Parameter names should be unique.
-                    "A".into(),
-                    // The data type for this input. In this case, a scalar
-                    N3DDataType::Scalar,
-                    // The value type for this input. We store zero as default
-                    N3DValueType::Scalar { value: 0.0 },
-                    // The input parameter kind. This allows defining whether a
-                    // parameter accepts input connections and/or an inline
-                    // widget to set its value.
-                    InputParamKind::ConnectionOrConstant,
-                    true,
-                );
-                input_scalar(graph, "B");
-                output_scalar(graph, "out");
-            }
-            N3DNodeTemplate::ScalarSub => {
-                input_scalar(graph, "A");
-                input_scalar(graph, "B");
-                output_scalar(graph, "out");
-            }
-            N3DNodeTemplate::Vec2ScalarMul => {
-                input_scalar(graph, "scalar");
-                input_vec2(graph, "vector");
-                output_vec2(graph, "out");
-            }
-            N3DNodeTemplate::Vec2Add => {
-                input_vec2(graph, "v1");
-                input_vec2(graph, "v2");
-                output_vec2(graph, "out");
-            }
-            N3DNodeTemplate::Vec2Subtract => {
-                input_vec2(graph, "v1");
-                input_vec2(graph, "v2");
-                output_vec2(graph, "out");
-            }
-            N3DNodeTemplate::NewVec2 => {
-                input_scalar(graph, "x");
-                input_scalar(graph, "y");
-                output_vec2(graph, "out");
-            }
-            N3DNodeTemplate::NewScalar => {
-                input_scalar(graph, "value");
-                output_scalar(graph, "out");
-            }
-            N3DNodeTemplate::NewVec3 => {
-                input_scalar(graph, "x");
-                input_scalar(graph, "y");
-                input_scalar(graph, "z");
-                output_vec3(graph, "out");
-            }
-            N3DNodeTemplate::Vec3Add => {
-                input_vec3(graph, "v1");
-                input_vec3(graph, "v2");
-                output_vec3(graph, "out");
-            }
-            N3DNodeTemplate::Vec3Sub => {
-                input_vec3(graph, "v1");
-                input_vec3(graph, "v2");
-                output_vec3(graph, "out");
-            }
-            N3DNodeTemplate::Vec3Dot => {
-                input_vec3(graph, "v1");
-                input_vec3(graph, "v2");
-                output_scalar(graph, "out");
-            }
-            N3DNodeTemplate::Vec3Cross => {
-                input_vec3(graph, "v1");
-                input_vec3(graph, "v2");
-                output_vec3(graph, "out");
-            }
-            N3DNodeTemplate::SDFPosition => {
-                output_sdf_position(graph, "out");
-            }
-            N3DNodeTemplate::SDFTranslate => {
-                input_vec3(graph, "translation");
-                input_sdf_position(graph, "sdf position");
-                output_sdf_position(graph, "out");
-            }
-            N3DNodeTemplate::SDFRotate => {
-                input_vec3(graph, "rotation");
-                input_sdf_position(graph, "sdf position");
-                output_sdf_position(graph, "out");
-            }
-            N3DNodeTemplate::SDFScale => {
-                input_vec3(graph, "scale");
-                input_sdf_position(graph, "sdf position");
-                output_sdf_position(graph, "out");
-            }
-            N3DNodeTemplate::SDFBox => {
-                input_vec3(graph, "dimensions");
-                input_scalar(graph, "fillet");
-                input_sdf_position(graph, "sdf position");
-                output_sdf_volume(graph, "out");
-            }
-            N3DNodeTemplate::SDFSphere => {
-                input_scalar(graph, "radius");
-                input_sdf_position(graph, "sdf position");
-                output_sdf_volume(graph, "out");
-            }
-            N3DNodeTemplate::SDFCylinder => {
-                input_scalar(graph, "radius");
-                input_scalar(graph, "length");
-                input_scalar(graph, "fillet");
-                input_sdf_position(graph, "sdf position");
-                output_sdf_volume(graph, "out");
-            }
-            N3DNodeTemplate::SDFUnion => {
-                input_sdf_volume(graph, "sdf 1");
-                input_sdf_volume(graph, "sdf 2");
-                output_sdf_volume(graph, "out");
-            }
-            N3DNodeTemplate::SDFSmoothUnion => {
-                input_sdf_volume(graph, "sdf 1");
-                input_sdf_volume(graph, "sdf 2");
-                input_scalar(graph, "fac");
-                output_sdf_volume(graph, "out");
-            }
-            N3DNodeTemplate::SDFDiff => {
-                input_sdf_volume(graph, "sdf 1");
-                input_sdf_volume(graph, "sdf 2");
-                output_sdf_volume(graph, "out");
-            }
-            N3DNodeTemplate::SDFSmoothDiff => {
-                input_sdf_volume(graph, "sdf 1");
-                input_sdf_volume(graph, "sdf 2");
-                input_scalar(graph, "fac");
-                output_sdf_volume(graph, "out");
-            }
-            N3DNodeTemplate::SDFViewer => {
-                input_sdf_volume(graph, "sdf");
-            }
-        }
-    }
-}
-
-pub struct AllN3DNodeTemplates;
 impl NodeTemplateIter for AllN3DNodeTemplates {
     type Item = N3DNodeTemplate;
 
     fn all_kinds(&self) -> Vec<Self::Item> {
         // This function must return a list of node kinds, which the node finder
-        // will use to display it to the user. Crates like strum can reduce the
-        // boilerplate in enumerating all variants of an enum.
-        vec![
-            N3DNodeTemplate::NewScalar,
-            N3DNodeTemplate::NewVec2,
-            N3DNodeTemplate::ScalarAdd,
-            N3DNodeTemplate::ScalarSub,
-            N3DNodeTemplate::Vec2Add,
-            N3DNodeTemplate::Vec2Subtract,
-            N3DNodeTemplate::Vec2ScalarMul,
-            N3DNodeTemplate::NewVec3,
-            N3DNodeTemplate::Vec3Add,
-            N3DNodeTemplate::Vec3Sub,
-            N3DNodeTemplate::Vec3Dot,
-            N3DNodeTemplate::Vec3Cross,
-            N3DNodeTemplate::SDFPosition,
-            N3DNodeTemplate::SDFTranslate,
-            N3DNodeTemplate::SDFRotate,
-            N3DNodeTemplate::SDFScale,
-            N3DNodeTemplate::SDFBox,
-            N3DNodeTemplate::SDFSphere,
-            N3DNodeTemplate::SDFCylinder,
-            N3DNodeTemplate::SDFUnion,
-            N3DNodeTemplate::SDFSmoothUnion,
-            N3DNodeTemplate::SDFDiff,
-            N3DNodeTemplate::SDFSmoothDiff,
-            N3DNodeTemplate::SDFViewer,
-        ]
+        // will use to display it to the user.
+        nodes::all()
+            .into_iter()
+            .map(N3DNodeTemplate::Node)
+            .chain(self.groups.iter().map(|id| N3DNodeTemplate::Group(*id)))
+            .collect()
     }
 }
 
@@ -518,38 +477,57 @@ impl WidgetValueTrait for N3DValueType {
     fn value_widget(
         &mut self,
         param_name: &str,
-        _node_id: NodeId,
+        node_id: NodeId,
         ui: &mut egui::Ui,
-        _user_state: &mut MyGraphState,
-        _node_data: &N3DNodeData,
+        user_state: &mut MyGraphState,
+        node_data: &N3DNodeData,
     ) -> Vec<MyResponse> {
+        // Keyframed values show the sampled value of the current frame
+        let frame = user_state.frame.round();
+        if let Some(value) = user_state.animation.sample(node_id, param_name, user_state.frame) {
+            *self = N3DValueType::from_expr(value.into());
+        }
+        let keyed = user_state
+            .animation
+            .track(node_id, param_name)
+            .map(|track| track.key_at(frame).is_some());
+        let keyable = user_state.editing_group.is_none();
+        let mut edited = false;
+        let mut key_toggled = false;
         // This trait is used to tell the library which UI to display for the
         // inline parameter widgets.
         match self {
             N3DValueType::Vec2 { value } => {
-                ui.label(param_name);
+                ui.horizontal(|ui| {
+                    ui.label(param_name);
+                    key_toggled = keyable && key_toggle(ui, keyed);
+                });
                 ui.horizontal(|ui| {
                     ui.label("x");
-                    ui.add(DragValue::new(&mut value.x).speed(0.1));
+                    edited |= ui.add(DragValue::new(&mut value.x).speed(0.1)).changed();
                     ui.label("y");
-                    ui.add(DragValue::new(&mut value.y).speed(0.1));
+                    edited |= ui.add(DragValue::new(&mut value.y).speed(0.1)).changed();
                 });
             }
             N3DValueType::Vec3 { value } => {
-                ui.label(param_name);
+                ui.horizontal(|ui| {
+                    ui.label(param_name);
+                    key_toggled = keyable && key_toggle(ui, keyed);
+                });
                 ui.horizontal(|ui| {
                     ui.label("x");
-                    ui.add(DragValue::new(&mut value.x).speed(0.1));
+                    edited |= ui.add(DragValue::new(&mut value.x).speed(0.1)).changed();
                     ui.label("y");
-                    ui.add(DragValue::new(&mut value.y).speed(0.1));
+                    edited |= ui.add(DragValue::new(&mut value.y).speed(0.1)).changed();
                     ui.label("z");
-                    ui.add(DragValue::new(&mut value.z).speed(0.1));
+                    edited |= ui.add(DragValue::new(&mut value.z).speed(0.1)).changed();
                 });
             }
             N3DValueType::Scalar { value } => {
                 ui.horizontal(|ui| {
                     ui.label(param_name);
-                    ui.add(DragValue::new(value).speed(0.1));
+                    edited |= ui.add(DragValue::new(value).speed(0.1)).changed();
+                    key_toggled = keyable && key_toggle(ui, keyed);
                 });
             }
             N3DValueType::SDFPosition { value: _ } => {
@@ -562,6 +540,63 @@ impl WidgetValueTrait for N3DValueType {
                     ui.label(param_name);
                 });
             }
+            N3DValueType::SDF2D { value: _ } => {
+                ui.horizontal(|ui| {
+                    ui.label(param_name);
+                });
+            }
+            N3DValueType::Material { value: _ } => {
+                ui.horizontal(|ui| {
+                    ui.label(param_name);
+                });
+            }
+            N3DValueType::Matcap { value } => {
+                ui.horizontal(|ui| {
+                    ui.label(param_name);
+                    egui::ComboBox::from_id_source((node_id, param_name))
+                        .selected_text(value.name())
+                        .show_ui(ui, |ui| {
+                            for matcap in Matcap::ALL {
+                                ui.selectable_value(value, matcap, matcap.name());
+                            }
+                        });
+                });
+            }
+            N3DValueType::Text { value } => {
+                ui.horizontal(|ui| {
+                    ui.label(param_name);
+                    ui.text_edit_singleline(value);
+                });
+            }
+            N3DValueType::Code { value } => {
+                ui.label(param_name);
+                ui.add(
+                    egui::TextEdit::multiline(value)
+                        .code_editor()
+                        .desired_rows(4),
+                );
+            }
+            N3DValueType::Expr { value: _ } => {
+                ui.horizontal(|ui| {
+                    ui.label(param_name);
+                    ui.weak("animated");
+                });
+            }
+        }
+        if let N3DNodeTemplate::Node(node) = node_data.template {
+            if let Some(problem) = node.check_input(param_name, self) {
+                ui.colored_label(egui::Color32::RED, problem);
+            }
+        }
+        if let (Some(value), true) = (self.as_value(), keyable) {
+            let animation = &mut user_state.animation;
+            match (keyed, key_toggled) {
+                (Some(true), true) => animation.remove_key(node_id, param_name, frame),
+                (_, true) => animation.set_key(node_id, param_name, frame, value),
+                // Editing an animated value keys it on the current frame
+                (Some(_), false) if edited => animation.set_key(node_id, param_name, frame, value),
+                _ => {}
+            }
         }
         // This allows you to return your responses from the inline widgets.
         Vec::new()
@@ -595,6 +630,13 @@ impl NodeDataTrait for N3DNodeData {
         // UIs based on that.
 
         let mut responses = vec![];
+        let mut name = self.name.clone();
+        let edit = egui::TextEdit::singleline(&mut name)
+            .hint_text("Name")
+            .desired_width(120.0);
+        if ui.add(edit).changed() {
+            responses.push(NodeResponse::User(MyResponse::RenameNode(node_id, name)));
+        }
         let is_active = user_state
             .active_node
             .map(|id| id == node_id)
@@ -616,11 +658,50 @@ impl NodeDataTrait for N3DNodeData {
                 responses.push(NodeResponse::User(MyResponse::ClearActiveNode));
             }
         }
+        if let N3DNodeTemplate::Group(id) = self.template {
+            if ui.button("Edit group").clicked() {
+                responses.push(NodeResponse::User(MyResponse::EditGroup(id)));
+            }
+        }
+        if let Some(message) = user_state.diagnostics.get(&node_id) {
+            ui.colored_label(egui::Color32::LIGHT_RED, message);
+        }
 
         responses
     }
 }
 
+/// Graph set aside while a group is edited in its place
+struct GroupEdit {
+    group: GroupId,
+    outer: MyEditorState,
+    active_node: Option<NodeId>,
+    animation: Animation,
+    /// Ports of the group when editing started, to carry renames over to
+    /// its instances
+    inputs: Vec<GroupPort>,
+    outputs: Vec<GroupPort>,
+}
+
+/// Everything an edit can change, as kept in the undo history
+#[derive(Clone)]
+struct Snapshot {
+    graph: MyGraph,
+    node_order: Vec<NodeId>,
+    node_positions: HashMap<NodeId, egui::Pos2>,
+    active_node: Option<NodeId>,
+    animation: Animation,
+    groups: NodeGroups,
+}
+
+/// Edit spanning several frames, like dragging a value or a node, recorded
+/// as a single history entry once it ends
+struct Gesture {
+    label: &'static str,
+    /// Input whose value is being edited
+    input: Option<InputId>,
+}
+
 #[derive(Default)]
 pub struct NodeGraphExample {
     // The `GraphEditorState` is the top-level object. You "register" all your
@@ -628,300 +709,1396 @@ pub struct NodeGraphExample {
     state: MyEditorState,
 
     user_state: MyGraphState,
-}
 
-#[cfg(feature = "persistence")]
-const PERSISTENCE_KEY: &str = "egui_node_graph";
+    playback: Playback,
+
+    /// Groups being edited, innermost last
+    editing: Vec<GroupEdit>,
+    /// Name given to the next group made from the selection
+    group_name: String,
+
+    history: History<Snapshot>,
+    /// State after the last recorded edit, what the next one is undone to.
+    /// Only taken again once an edit is recorded, not every frame.
+    settled: Option<Snapshot>,
+    gesture: Option<Gesture>,
+    /// Edit made this frame through the menus or side panels rather than
+    /// the graph editor
+    edited: Option<&'static str>,
+    /// The whole state was replaced this frame, so its changes are not an edit
+    restored: bool,
+
+    /// File the current project was last opened from or saved to
+    #[cfg(feature = "persistence")]
+    project_path: Option<std::path::PathBuf>,
+    #[cfg(feature = "persistence")]
+    project_dialog: Option<ProjectDialog>,
+    /// Result of the last File menu action, shown in the top bar
+    #[cfg(feature = "persistence")]
+    project_status: Option<String>,
+
+    export_settings: ExportSettings,
+    export_dialog: Option<ExportDialog>,
+    /// Export confirmed this frame, started once the scene is evaluated
+    export: Option<(ExportFormat, std::path::PathBuf)>,
+    /// Export meshing and writing its file in the background
+    exporting: Option<JoinHandle<Result<std::path::PathBuf, ExportError>>>,
+    /// Progress or result of the last export, shown in the top bar
+    export_status: Option<String>,
+}
 
 impl NodeGraphExample {
-    /// If the persistence feature is enabled, Called once before the first frame.
-    /// Load previous app state (if any).
     pub fn new() -> Self {
-        Self {
-            state: MyEditorState::default(),
-            user_state: MyGraphState::default(),
+        Self::default()
+    }
+
+    /// Current scene time in seconds, the value of `u_time` and the Time node
+    pub fn time(&self) -> f32 {
+        self.playback.time()
+    }
+
+    /// Discards the current graph and starts from an empty one
+    pub fn new_project(&mut self, camera: &mut Camera) {
+        self.editing.clear();
+        self.reset_history();
+        self.state = MyEditorState::default();
+        self.user_state = MyGraphState::default();
+        *camera = Camera::new();
+        #[cfg(feature = "persistence")]
+        {
+            self.project_path = None;
         }
     }
 
+    /// Replaces the current graph, active node and camera with the ones stored in `path`
+    #[cfg(feature = "persistence")]
+    pub fn open_project(
+        &mut self,
+        path: &std::path::Path,
+        camera: &mut Camera,
+    ) -> Result<(), crate::project::ProjectError> {
+        let project = Project::open(path)?;
+        self.editing.clear();
+        self.reset_history();
+        self.state = project.editor;
+        self.user_state = project.user_state;
+        *camera = project.camera;
+        self.project_path = Some(path.to_path_buf());
+        Ok(())
+    }
+
+    /// Writes the current graph, active node and camera to `path`
+    #[cfg(feature = "persistence")]
+    pub fn save_project(
+        &mut self,
+        path: &std::path::Path,
+        camera: &Camera,
+    ) -> Result<(), crate::project::ProjectError> {
+        self.leave_all_groups();
+        Project::save(path, &self.state, &self.user_state, camera)?;
+        self.project_path = Some(path.to_path_buf());
+        Ok(())
+    }
+
+    #[cfg(feature = "persistence")]
+    fn file_menu(&mut self, ui: &mut egui::Ui, camera: &mut Camera) {
+        ui.menu_button("File", |ui| {
+            if ui.button("New").clicked() {
+                self.new_project(camera);
+                self.project_status = None;
+                ui.close_menu();
+            }
+            if ui.button("Open...").clicked() {
+                self.project_dialog = Some(ProjectDialog::new(
+                    ProjectDialogKind::Open,
+                    self.project_path.as_deref(),
+                ));
+                ui.close_menu();
+            }
+            if ui.button("Save").clicked() {
+                match self.project_path.clone() {
+                    Some(path) => self.report_project_result("Saved", &path, |graph| {
+                        graph.save_project(&path, camera)
+                    }),
+                    None => {
+                        self.project_dialog =
+                            Some(ProjectDialog::new(ProjectDialogKind::SaveAs, None))
+                    }
+                }
+                ui.close_menu();
+            }
+            if ui.button("Save As...").clicked() {
+                self.project_dialog = Some(ProjectDialog::new(
+                    ProjectDialogKind::SaveAs,
+                    self.project_path.as_deref(),
+                ));
+                ui.close_menu();
+            }
+        });
+    }
+
+    #[cfg(feature = "persistence")]
+    fn project_dialog(&mut self, ctx: &egui::Context, camera: &mut Camera) {
+        let Some(dialog) = self.project_dialog.as_mut() else {
+            return;
+        };
+        let kind = dialog.kind;
+        match dialog.show(ctx) {
+            ProjectDialogResponse::Pending => {}
+            ProjectDialogResponse::Cancelled => self.project_dialog = None,
+            ProjectDialogResponse::Confirmed(path) => {
+                self.project_dialog = None;
+                match kind {
+                    ProjectDialogKind::Open => self.report_project_result("Opened", &path, |graph| {
+                        graph.open_project(&path, camera)
+                    }),
+                    ProjectDialogKind::SaveAs => self.report_project_result("Saved", &path, |graph| {
+                        graph.save_project(&path, camera)
+                    }),
+                    ProjectDialogKind::ImportGroups => {
+                        self.report_project_result("Imported", &path, |graph| {
+                            graph.import_groups(&path)
+                        })
+                    }
+                    ProjectDialogKind::ExportGroups => {
+                        self.report_project_result("Exported", &path, |graph| {
+                            graph.user_state.groups.save(&path)
+                        })
+                    }
+                }
+            }
+        }
+    }
+
+    #[cfg(feature = "persistence")]
+    fn report_project_result<F, E>(&mut self, verb: &str, path: &std::path::Path, action: F)
+    where
+        F: FnOnce(&mut Self) -> Result<(), E>,
+        E: std::fmt::Display,
+    {
+        self.project_status = Some(match action(self) {
+            Ok(()) => format!("{} {}", verb, path.display()),
+            Err(err) => format!("Error: {}", err),
+        });
+    }
+
+    /// Adds the groups stored in the library at `path`, replacing and
+    /// updating the instances of groups with the same name
+    #[cfg(feature = "persistence")]
+    pub fn import_groups(
+        &mut self,
+        path: &std::path::Path,
+    ) -> Result<(), crate::group::LibraryError> {
+        let library = NodeGroups::open(path)?;
+        self.edited = Some("Import groups");
+        for id in self.user_state.groups.merge(library) {
+            if let Some(group) = self.user_state.groups.get(id).cloned() {
+                self.sync_instances(id, &group, (&[], &[]));
+            }
+        }
+        Ok(())
+    }
+
+    fn export_menu(&mut self, ui: &mut egui::Ui) {
+        ui.menu_button("Export", |ui| {
+            for format in ExportFormat::ALL {
+                let button = egui::Button::new(format!("{}...", format.label()));
+                if ui.add_enabled(self.exporting.is_none(), button).clicked() {
+                    #[cfg(feature = "persistence")]
+                    let project = self.project_path.as_deref();
+                    #[cfg(not(feature = "persistence"))]
+                    let project = None;
+                    self.export_dialog = Some(ExportDialog::new(format, project));
+                    ui.close_menu();
+                }
+            }
+        });
+    }
+
+    fn export_dialog(&mut self, ctx: &egui::Context) {
+        let Some(dialog) = self.export_dialog.as_mut() else {
+            return;
+        };
+        match dialog.show(ctx, &mut self.export_settings) {
+            ExportDialogResponse::Pending => {}
+            ExportDialogResponse::Cancelled => self.export_dialog = None,
+            ExportDialogResponse::Confirmed(path) => {
+                self.export = Some((dialog.format, path));
+                self.export_dialog = None;
+            }
+        }
+    }
+
+    /// Starts meshing `volume`, the scene at the active node, on another
+    /// thread and writing it to `path`, keeping the editor responsive
+    fn start_export(
+        &mut self,
+        volume: Option<&SDFVolume>,
+        format: ExportFormat,
+        path: std::path::PathBuf,
+    ) {
+        let Some(volume) = volume.cloned() else {
+            self.export_status = Some(format!("Error: {}", ExportError::NoVolume));
+            return;
+        };
+        let settings = self.export_settings.clone();
+        let time = self.time();
+        self.export_status = Some(format!("Exporting {}...", path.display()));
+        self.exporting = Some(std::thread::spawn(move || {
+            settings.export(&volume, time, format, &path)?;
+            Ok(path)
+        }));
+    }
+
+    /// Shows the result of the export running in the background once it
+    /// is done
+    fn poll_export(&mut self, ctx: &egui::Context) {
+        let Some(exporting) = &self.exporting else {
+            return;
+        };
+        if !exporting.is_finished() {
+            ctx.request_repaint_after(std::time::Duration::from_millis(100));
+            return;
+        }
+        let result = self.exporting.take().map(JoinHandle::join);
+        self.export_status = Some(match result {
+            Some(Ok(Ok(path))) => format!("Exported {}", path.display()),
+            Some(Ok(Err(err))) => format!("Error: {}", err),
+            _ => "Error: the export stopped unexpectedly".to_string(),
+        });
+    }
+
+    fn groups_menu(&mut self, ui: &mut egui::Ui) {
+        ui.menu_button("Groups", |ui| {
+            ui.horizontal(|ui| {
+                ui.label("Name");
+                ui.text_edit_singleline(&mut self.group_name);
+            });
+            let selected = !self.state.selected_nodes.is_empty();
+            if ui
+                .add_enabled(selected, egui::Button::new("Group selected"))
+                .clicked()
+            {
+                let name = match self.group_name.trim() {
+                    "" => "Group",
+                    name => name,
+                };
+                collapse_selection(&mut self.state, &mut self.user_state, name);
+                self.edited = Some("Group nodes");
+                ui.close_menu();
+            }
+            ui.separator();
+            let groups: Vec<(GroupId, String)> = self
+                .user_state
+                .groups
+                .iter()
+                .map(|(id, group)| (id, group.name.clone()))
+                .collect();
+            if groups.is_empty() {
+                ui.weak("Select nodes to group them");
+            }
+            for (id, name) in groups {
+                let editing = self.editing.iter().any(|edit| edit.group == id);
+                if ui
+                    .add_enabled(!editing, egui::Button::new(format!("Edit {}", name)))
+                    .clicked()
+                {
+                    self.enter_group(id);
+                    ui.close_menu();
+                }
+            }
+            #[cfg(feature = "persistence")]
+            {
+                ui.separator();
+                if ui.button("Import library...").clicked() {
+                    self.project_dialog =
+                        Some(ProjectDialog::new(ProjectDialogKind::ImportGroups, None));
+                    ui.close_menu();
+                }
+                let any = !self.user_state.groups.is_empty();
+                if ui
+                    .add_enabled(any, egui::Button::new("Export library..."))
+                    .clicked()
+                {
+                    self.project_dialog =
+                        Some(ProjectDialog::new(ProjectDialogKind::ExportGroups, None));
+                    ui.close_menu();
+                }
+            }
+        });
+    }
+
+    /// Shows the inside of group `id` in the editor in place of the current graph
+    fn enter_group(&mut self, id: GroupId) {
+        if self.editing.iter().any(|edit| edit.group == id) {
+            return;
+        }
+        let Some(group) = self.user_state.groups.get(id) else {
+            return;
+        };
+        let mut inner = MyEditorState {
+            node_order: group.graph.iter_nodes().collect(),
+            graph: group.graph.clone(),
+            ..MyEditorState::default()
+        };
+        for (node, position) in &group.positions {
+            inner.node_positions.insert(*node, *position);
+        }
+        let edit = GroupEdit {
+            group: id,
+            inputs: group.inputs.clone(),
+            outputs: group.outputs.clone(),
+            outer: std::mem::replace(&mut self.state, inner),
+            active_node: self.user_state.active_node.take(),
+            animation: std::mem::take(&mut self.user_state.animation),
+        };
+        self.editing.push(edit);
+        self.user_state.editing_group = Some(id);
+        self.reset_history();
+    }
+
+    /// Stores the edited group and returns to the graph it was entered from
+    fn leave_group(&mut self) {
+        let Some(edit) = self.editing.pop() else {
+            return;
+        };
+        self.reset_history();
+        let inner = std::mem::replace(&mut self.state, edit.outer);
+        self.user_state.active_node = edit.active_node;
+        self.user_state.animation = edit.animation;
+        self.user_state.editing_group = self.editing.last().map(|edit| edit.group);
+        let Some(group) = self.user_state.groups.get_mut(edit.group) else {
+            return;
+        };
+        group.positions = inner
+            .graph
+            .iter_nodes()
+            .filter_map(|node| Some((node, *inner.node_positions.get(node)?)))
+            .collect();
+        group.graph = inner.graph;
+        group.prune_ports();
+        let group = group.clone();
+        self.sync_instances(edit.group, &group, (&edit.inputs, &edit.outputs));
+    }
+
+    fn leave_all_groups(&mut self) {
+        while !self.editing.is_empty() {
+            self.leave_group();
+        }
+    }
+
+    /// Updates every instance of group `id` after it changed, in the graphs
+    /// set aside for editing as well as inside other groups. `before` are the
+    /// group's inputs and outputs before the change.
+    fn sync_instances(
+        &mut self,
+        id: GroupId,
+        group: &NodeGroup,
+        before: (&[GroupPort], &[GroupPort]),
+    ) {
+        let after = (group.inputs.as_slice(), group.outputs.as_slice());
+        let graphs = std::iter::once(&mut self.state.graph)
+            .chain(self.editing.iter_mut().map(|edit| &mut edit.outer.graph))
+            .chain(self.user_state.groups.iter_mut().map(|(_, group)| &mut group.graph));
+        for graph in graphs {
+            let instances: Vec<NodeId> = graph
+                .iter_nodes()
+                .filter(|node| {
+                    matches!(graph[*node].user_data.template, N3DNodeTemplate::Group(i) if i == id)
+                })
+                .collect();
+            for node in instances {
+                rename_group_ports(graph, node, before, after);
+                sync_group_ports(graph, node, group);
+            }
+        }
+    }
+
+    /// Name and ports of the group being edited, and the parameters of the
+    /// selected nodes that can still be exposed
+    fn group_panel(&mut self, ctx: &egui::Context) {
+        let Some(id) = self.editing.last().map(|edit| edit.group) else {
+            return;
+        };
+        let mut done = false;
+        egui::SidePanel::right("group").show(ctx, |ui| {
+            let graph = &self.state.graph;
+            let Some(group) = self.user_state.groups.get_mut(id) else {
+                return;
+            };
+            let mut changed = false;
+            ui.horizontal(|ui| {
+                ui.label("Group");
+                changed |= ui.text_edit_singleline(&mut group.name).changed();
+            });
+            ui.separator();
+            ui.label("Inputs");
+            changed |= port_list(ui, &mut group.inputs);
+            ui.label("Outputs");
+            changed |= port_list(ui, &mut group.outputs);
+            ui.separator();
+            ui.label("Expose");
+            for &node in &self.state.selected_nodes {
+                let Some(node) = graph.nodes.get(node) else {
+                    continue;
+                };
+                for (param, input) in &node.inputs {
+                    let exposed = group
+                        .inputs
+                        .iter()
+                        .any(|port| port.node == node.id && port.param == *param);
+                    if exposed || graph.connection(*input).is_some() {
+                        continue;
+                    }
+                    if ui.button(format!("{} {}", node.label, param)).clicked() {
+                        let typ = graph.get_input(*input).typ;
+                        group.inputs.push(GroupPort::new(&group.inputs, node, param, typ));
+                        changed = true;
+                    }
+                }
+                for (param, output) in &node.outputs {
+                    let exposed = group
+                        .outputs
+                        .iter()
+                        .any(|port| port.node == node.id && port.param == *param);
+                    if exposed {
+                        continue;
+                    }
+                    if ui.button(format!("{} {} (out)", node.label, param)).clicked() {
+                        let typ = graph.get_output(*output).typ;
+                        group.outputs.push(GroupPort::new(&group.outputs, node, param, typ));
+                        changed = true;
+                    }
+                }
+            }
+            if changed {
+                self.edited = Some("Edit group");
+            }
+            ui.separator();
+            done = ui.button("Done").clicked();
+        });
+        if done {
+            self.leave_group();
+        }
+    }
+
+    fn snapshot(&self) -> Snapshot {
+        let state = &self.state;
+        Snapshot {
+            graph: state.graph.clone(),
+            node_order: state.node_order.clone(),
+            node_positions: state
+                .graph
+                .iter_nodes()
+                .filter_map(|node| Some((node, *state.node_positions.get(node)?)))
+                .collect(),
+            active_node: self.user_state.active_node,
+            animation: self.user_state.animation.clone(),
+            groups: self.user_state.groups.clone(),
+        }
+    }
+
+    fn restore(&mut self, snapshot: Snapshot) {
+        self.state.graph = snapshot.graph;
+        self.state.node_order = snapshot.node_order;
+        self.state.node_positions.clear();
+        for (node, position) in snapshot.node_positions {
+            self.state.node_positions.insert(node, position);
+        }
+        let graph = &self.state.graph;
+        self.state
+            .selected_nodes
+            .retain(|node| graph.nodes.contains_key(*node));
+        self.state.connection_in_progress = None;
+        self.user_state.active_node = snapshot.active_node;
+        self.user_state.animation = snapshot.animation;
+        self.user_state.groups = snapshot.groups;
+        self.restored = true;
+    }
+
+    /// Reverts the last edit of the graph being shown
+    pub fn undo(&mut self) {
+        self.finish_gesture();
+        let current = self.snapshot();
+        if let Some(snapshot) = self.history.undo(current) {
+            self.restore(snapshot);
+        }
+    }
+
+    /// Reapplies the last undone edit
+    pub fn redo(&mut self) {
+        self.finish_gesture();
+        let current = self.snapshot();
+        if let Some(snapshot) = self.history.redo(current) {
+            self.restore(snapshot);
+        }
+    }
+
+    /// Forgets all edits, used whenever a different graph is shown
+    fn reset_history(&mut self) {
+        self.history.clear();
+        self.gesture = None;
+        self.restored = true;
+    }
+
+    fn finish_gesture(&mut self) {
+        if let Some(gesture) = self.gesture.take() {
+            self.commit(gesture.label);
+        }
+    }
+
+    /// Adds an entry undoing everything since the last one to the history
+    fn commit(&mut self, label: &'static str) {
+        if let Some(before) = self.settled.replace(self.snapshot()) {
+            self.history.push(label, before);
+        }
+    }
+
+    /// Adds the edits made this frame to the history, found by comparing
+    /// with the state after the last recorded edit. Drags of values and
+    /// nodes stay open until the pointer is released, typing until the text
+    /// field loses focus.
+    fn record_history(
+        &mut self,
+        ctx: &egui::Context,
+        responses: &[NodeResponse<MyResponse, N3DNodeData>],
+    ) {
+        if std::mem::take(&mut self.restored) {
+            self.settled = Some(self.snapshot());
+            return;
+        }
+        let Some(before) = &self.settled else {
+            self.settled = Some(self.snapshot());
+            return;
+        };
+        let mut label = self.edited.take();
+        let mut moved = false;
+        let mut renamed = false;
+        for response in responses {
+            match response {
+                NodeResponse::CreatedNode(_) => label = Some("Add node"),
+                NodeResponse::DeleteNodeFull { .. } => label = Some("Delete node"),
+                NodeResponse::ConnectEventEnded { .. } => label = Some("Connect"),
+                NodeResponse::DisconnectEvent { .. } => label = Some("Disconnect"),
+                NodeResponse::User(MyResponse::SetActiveNode(_) | MyResponse::ClearActiveNode) => {
+                    label = Some("Change active node")
+                }
+                NodeResponse::MoveNode { .. } => moved = true,
+                NodeResponse::User(MyResponse::RenameNode(..)) => renamed = true,
+                _ => {}
+            }
+        }
+        if let Some(label) = label {
+            // A gesture still open is undone together with this edit
+            self.gesture = None;
+            self.commit(label);
+            return;
+        }
+
+        // Keyed values follow the playhead, their edits show up as keyframe changes
+        let graph = &self.state.graph;
+        let animation = &self.user_state.animation;
+        let edited = graph
+            .inputs
+            .iter()
+            .find(|(input, param)| {
+                let keyed = graph[param.node]
+                    .inputs
+                    .iter()
+                    .find(|(_, i)| i == input)
+                    .is_some_and(|(name, _)| animation.track(param.node, name).is_some());
+                let old = before.graph.inputs.get(*input);
+                !keyed && old.is_some_and(|old| old.value != param.value)
+            })
+            .map(|(input, _)| input);
+        let continuous = match edited {
+            Some(input) => Some(("Edit value", Some(input))),
+            None if *animation != before.animation => Some(("Edit keyframes", None)),
+            None if moved => Some(("Move node", None)),
+            None if renamed => Some(("Rename node", None)),
+            None => None,
+        };
+        if let Some((label, input)) = continuous {
+            let ongoing = self
+                .gesture
+                .as_ref()
+                .is_some_and(|gesture| gesture.label == label && gesture.input == input);
+            if !ongoing {
+                self.finish_gesture();
+                self.gesture = Some(Gesture { label, input });
+            }
+        }
+        let holding = ctx.input(|i| i.pointer.any_down()) || ctx.memory(|m| m.focus().is_some());
+        if !holding {
+            self.finish_gesture();
+        }
+    }
+
+    fn edit_menu(&mut self, ui: &mut egui::Ui) {
+        ui.menu_button("Edit", |ui| {
+            let undo = self.history.undo_label();
+            let text = undo.map_or("Undo".to_string(), |label| format!("Undo {}", label));
+            let button = egui::Button::new(text).shortcut_text("Ctrl+Z");
+            if ui.add_enabled(undo.is_some(), button).clicked() {
+                self.undo();
+                ui.close_menu();
+            }
+            let redo = self.history.redo_label();
+            let text = redo.map_or("Redo".to_string(), |label| format!("Redo {}", label));
+            let button = egui::Button::new(text).shortcut_text("Ctrl+Shift+Z");
+            if ui.add_enabled(redo.is_some(), button).clicked() {
+                self.redo();
+                ui.close_menu();
+            }
+        });
+    }
+
+    /// Keyframed parameters over the playback range, dragging across a row scrubs
+    fn timeline(&mut self, ctx: &egui::Context) {
+        egui::TopBottomPanel::bottom("timeline").show(ctx, |ui| {
+            let animation = &mut self.user_state.animation;
+            let frame = self.user_state.frame.round();
+            let end = self.playback.duration() * animation.fps;
+            let mut scrub = None;
+            ui.horizontal(|ui| {
+                ui.add_sized([160.0, 18.0], egui::Label::new(format!("frame {}", frame)));
+                ui.add(
+                    DragValue::new(&mut animation.fps)
+                        .clamp_range(1.0..=240.0)
+                        .prefix("fps: "),
+                );
+                scrub = scrub.or(timeline_strip(ui, &[], frame, end));
+            });
+            let mut tracks: Vec<(TrackId, String)> = animation
+                .tracks()
+                .map(|(id, _)| {
+                    let label = format!("{} {}", self.state.graph[id.node].label, id.param);
+                    (id.clone(), label)
+                })
+                .collect();
+            tracks.sort_by(|a, b| a.1.cmp(&b.1));
+            if tracks.is_empty() {
+                ui.weak("Key an inline value to animate it");
+            }
+            let mut removed = None;
+            for (id, label) in tracks {
+                let Some(track) = animation.track_mut(id.node, &id.param) else {
+                    continue;
+                };
+                ui.horizontal(|ui| {
+                    ui.add_sized([160.0, 18.0], egui::Label::new(&label));
+                    if ui.button("remove").clicked() {
+                        removed = Some(id.clone());
+                    }
+                    if let Some(key) = track.key_before_mut(frame) {
+                        interpolation_widget(ui, &label, &mut key.interpolation);
+                    }
+                    scrub = scrub.or(timeline_strip(ui, track.keys(), frame, end));
+                });
+            }
+            if let Some(id) = removed {
+                animation.remove_track(&id);
+            }
+            if let Some(frame) = scrub {
+                self.playback.set_time(frame / animation.fps);
+            }
+        });
+    }
+
     /// Called each time the UI needs repainting, which may be many times per second.
     /// Put your widgets into a `SidePanel`, `TopPanel`, `CentralPanel`, `Window` or `Area`.
-    pub fn update(&mut self, ctx: &egui::Context) -> Option<String> {
-        let mut ret_val: Option<String> = None;
+    pub fn update(&mut self, ctx: &egui::Context, camera: &mut Camera) -> Option<SDFVolume> {
+        let mut ret_val: Option<SDFVolume> = None;
+        self.playback.tick();
+        self.user_state.frame = self.playback.time() * self.user_state.animation.fps;
         egui::TopBottomPanel::top("top").show(ctx, |ui| {
             egui::menu::bar(ui, |ui| {
+                #[cfg(feature = "persistence")]
+                self.file_menu(ui, camera);
+                self.edit_menu(ui);
+                self.groups_menu(ui);
+                self.export_menu(ui);
                 egui::widgets::global_dark_light_mode_switch(ui);
+                ui.separator();
+                self.playback.ui(ui);
+                #[cfg(feature = "persistence")]
+                if let Some(status) = &self.project_status {
+                    ui.label(status);
+                }
+                if let Some(status) = &self.export_status {
+                    ui.label(status);
+                }
             });
         });
+        #[cfg(feature = "persistence")]
+        self.project_dialog(ctx, camera);
+        self.export_dialog(ctx);
+        self.timeline(ctx);
+        self.group_panel(ctx);
+        let editing: Vec<GroupId> = self.editing.iter().map(|edit| edit.group).collect();
         let graph_response = egui::TopBottomPanel::bottom("node_panel")
             .resizable(true)
             //.min_height(256.0)
             .show(ctx, |ui| {
                 self.state.draw_graph_editor(
                     ui,
-                    AllN3DNodeTemplates,
+                    AllN3DNodeTemplates::new(&self.user_state.groups, &editing),
                     &mut self.user_state,
                     Vec::default(),
                 )
             })
             .inner;
-        for node_response in graph_response.node_responses {
+        let nodes: Vec<NodeId> = self.state.graph.iter_nodes().collect();
+        for node in nodes {
+            if let N3DNodeTemplate::Node(definition) = self.state.graph[node].user_data.template {
+                definition.update_ports(&mut self.state.graph, node);
+            }
+        }
+        let graph = &self.state.graph;
+        self.user_state.animation.retain(|id| {
+            graph.nodes.contains_key(id.node) && graph[id.node].get_input(&id.param).is_ok()
+        });
+        for node_response in &graph_response.node_responses {
             // Here, we ignore all other graph events. But you may find
             // some use for them. For example, by playing a sound when a new
             // connection is created
             if let NodeResponse::User(user_event) = node_response {
                 match user_event {
-                    MyResponse::SetActiveNode(node) => self.user_state.active_node = Some(node),
+                    MyResponse::SetActiveNode(node) => self.user_state.active_node = Some(*node),
                     MyResponse::ClearActiveNode => self.user_state.active_node = None,
+                    MyResponse::EditGroup(id) => self.enter_group(*id),
+                    MyResponse::RenameNode(node, name) => {
+                        if let Some(node) = self.state.graph.nodes.get_mut(*node) {
+                            node.user_data.set_name(name.clone());
+                        }
+                    }
                 }
             }
         }
+        self.record_history(ctx, &graph_response.node_responses);
 
+        self.user_state.diagnostics.clear();
         if let Some(node) = self.user_state.active_node {
             if self.state.graph.nodes.contains_key(node) {
-                let text = match evaluate_node(&self.state.graph, node, &mut HashMap::new()) {
-                    Ok(value) => {
-                        if let N3DValueType::SDFVolume { ref value } = value {
-                            ret_val = Some(value.clone());
+                match evaluate_node(
+                    &self.state.graph,
+                    node,
+                    EvalContext::new(&self.user_state),
+                    &mut HashMap::new(),
+                ) {
+                    Ok(N3DValueType::SDFVolume { value }) => ret_val = Some(value),
+                    Ok(_) => {}
+                    Err(err) => {
+                        for node in err.nodes() {
+                            self.user_state.diagnostics.insert(node, err.to_string());
                         }
-                        format!("The result is: {:?}", value)
-                    },
-                    Err(err) => format!("Execution error: {}", err),
-                };
-                ctx.debug_painter().text(
-                    egui::pos2(10.0, 35.0),
-                    egui::Align2::LEFT_TOP,
-                    text,
-                    TextStyle::Button.resolve(&ctx.style()),
-                    egui::Color32::WHITE,
-                );
+                    }
+                }
             } else {
                 self.user_state.active_node = None;
             }
         }
+        if let Some((format, path)) = self.export.take() {
+            self.start_export(ret_val.as_ref(), format, path);
+        }
+        self.poll_export(ctx);
     ret_val
     }
-}
 
-type OutputsCache = HashMap<OutputId, N3DValueType>;
+    /// Shows the errors of a ray marching shader that failed to compile on
+    /// the nodes that caused them, until the scene is evaluated again
+    pub fn show_shader_errors(&mut self, fragment: &str, log: &str) {
+        let errors = shader_errors(&self.state.graph, &self.user_state.groups, fragment, log);
+        for (node, messages) in errors {
+            self.user_state.diagnostics.insert(node, messages.join("\n"));
+        }
+    }
+}
 
-/// Recursively evaluates all dependencies of this node, then evaluates the node itself.
-pub fn evaluate_node(
+/// Errors of a shader compiler `log` caused by nodes of `graph`, an instance
+/// of a group showing those of the nodes inside it
+fn shader_errors(
     graph: &MyGraph,
+    groups: &NodeGroups,
+    fragment: &str,
+    log: &str,
+) -> Vec<(NodeId, Vec<String>)> {
+    let mut errors = Vec::new();
+    for node in graph.iter_nodes() {
+        let messages = match graph[node].user_data.template {
+            N3DNodeTemplate::Node(definition) => {
+                definition.shader_errors(graph, node, fragment, log)
+            }
+            N3DNodeTemplate::Group(id) => match groups.get(id) {
+                Some(group) => shader_errors(&group.graph, groups, fragment, log)
+                    .into_iter()
+                    .flat_map(|(_, messages)| messages)
+                    .map(|message| format!("{}: {}", group.name, message))
+                    .collect(),
+                None => Vec::new(),
+            },
+        };
+        if !messages.is_empty() {
+            errors.push((node, messages));
+        }
+    }
+    errors
+}
+
+/// Editable names of group ports, with a button to stop exposing each.
+/// Returns whether any port changed.
+fn port_list(ui: &mut egui::Ui, ports: &mut Vec<GroupPort>) -> bool {
+    let mut removed = None;
+    let mut renamed = false;
+    for i in 0..ports.len() {
+        ui.horizontal(|ui| {
+            renamed |= ui.text_edit_singleline(&mut ports[i].name).changed();
+            ui.weak(ports[i].typ.name());
+            if ui.button("remove").clicked() {
+                removed = Some(i);
+            }
+        });
+        if ports[..i].iter().any(|port| port.name == ports[i].name) {
+            ui.colored_label(egui::Color32::RED, "Port names must be unique");
+        }
+    }
+    if let Some(i) = removed {
+        ports.remove(i);
+    }
+    renamed || removed.is_some()
+}
+
+/// Row of the timeline with a diamond for every key and the playhead at
+/// `frame`, returns the frame the pointer scrubs to
+fn timeline_strip(ui: &mut egui::Ui, keys: &[Keyframe], frame: f32, end: f32) -> Option<f32> {
+    let (rect, response) = ui.allocate_exact_size(
+        egui::vec2(ui.available_width(), 18.0),
+        egui::Sense::click_and_drag(),
+    );
+    let x = |frame: f32| rect.left() + rect.width() * (frame / end).clamp(0.0, 1.0);
+    let painter = ui.painter_at(rect);
+    painter.rect_filled(rect, 2.0, ui.visuals().extreme_bg_color);
+    for key in keys {
+        let center = egui::pos2(x(key.frame), rect.center().y);
+        let diamond = [(0.0, -5.0), (5.0, 0.0), (0.0, 5.0), (-5.0, 0.0)]
+            .iter()
+            .map(|(dx, dy)| center + egui::vec2(*dx, *dy))
+            .collect();
+        painter.add(egui::Shape::convex_polygon(
+            diamond,
+            egui::Color32::GOLD,
+            egui::Stroke::NONE,
+        ));
+    }
+    painter.vline(
+        x(frame),
+        rect.y_range(),
+        egui::Stroke::new(1.0, egui::Color32::RED),
+    );
+    let pointer = response.interact_pointer_pos()?;
+    Some(((pointer.x - rect.left()) / rect.width()).clamp(0.0, 1.0) * end)
+}
+
+/// Picks how a track leaves the key before the playhead
+fn interpolation_widget(ui: &mut egui::Ui, id: &str, interpolation: &mut Interpolation) {
+    egui::ComboBox::from_id_source(id)
+        .selected_text(interpolation.name())
+        .show_ui(ui, |ui| {
+            for choice in [Interpolation::Linear, Interpolation::Step, Interpolation::BEZIER] {
+                let selected = interpolation.name() == choice.name();
+                if ui.selectable_label(selected, choice.name()).clicked() && !selected {
+                    *interpolation = choice;
+                }
+            }
+        });
+    if let Interpolation::Bezier { ease_out, ease_in } = interpolation {
+        ui.add(
+            DragValue::new(ease_out)
+                .clamp_range(0.0..=1.0)
+                .speed(0.01)
+                .prefix("out: "),
+        );
+        ui.add(
+            DragValue::new(ease_in)
+                .clamp_range(0.0..=1.0)
+                .speed(0.01)
+                .prefix("in: "),
+        );
+    }
+}
+
+/// Button next to inline values that keys them on the current frame, lit when
+/// there already is a key there. `keyed` is `None` for values without a track.
+fn key_toggle(ui: &mut egui::Ui, keyed: Option<bool>) -> bool {
+    let text = match keyed {
+        Some(true) => egui::RichText::new("key").color(egui::Color32::GOLD),
+        Some(false) => egui::RichText::new("key").color(egui::Color32::KHAKI),
+        None => egui::RichText::new("key").weak(),
+    };
+    ui.selectable_label(keyed == Some(true), text)
+        .on_hover_text("Toggle a keyframe on the current frame")
+        .clicked()
+}
+
+/// Keeps the ports of a group instance in line with the ports of its group
+/// and the label with its name. Ports are matched by name and type, new ones
+/// take the value of the parameter they forward to.
+pub fn sync_group_ports(graph: &mut MyGraph, node_id: NodeId, group: &NodeGroup) {
+    let exposed = |ports: &[GroupPort], name: &str, typ: N3DDataType| {
+        ports.iter().any(|port| port.name == name && port.typ == typ)
+    };
+    let stale: Vec<InputId> = graph[node_id]
+        .inputs
+        .iter()
+        .filter(|(name, input)| !exposed(&group.inputs, name, graph.get_input(*input).typ))
+        .map(|(_, input)| *input)
+        .collect();
+    for input in stale {
+        graph.remove_input_param(input);
+    }
+    let stale: Vec<OutputId> = graph[node_id]
+        .outputs
+        .iter()
+        .filter(|(name, output)| !exposed(&group.outputs, name, graph.get_output(*output).typ))
+        .map(|(_, output)| *output)
+        .collect();
+    for output in stale {
+        graph.remove_output_param(output);
+    }
+    for port in &group.inputs {
+        if graph[node_id].get_input(&port.name).is_ok() {
+            continue;
+        }
+        let (value, kind, shown_inline) = group.graph[port.node]
+            .get_input(&port.param)
+            .map(|input| {
+                let param = group.graph.get_input(input);
+                (param.value.clone(), param.kind, param.shown_inline)
+            })
+            .unwrap_or((N3DValueType::default(), InputParamKind::ConnectionOnly, true));
+        graph.add_input_param(node_id, port.name.clone(), port.typ, value, kind, shown_inline);
+    }
+    for port in &group.outputs {
+        if graph[node_id].get_output(&port.name).is_err() {
+            graph.add_output_param(node_id, port.name.clone(), port.typ);
+        }
+    }
+    graph[node_id].label = group.name.clone();
+}
+
+/// Renames the ports of a group instance the same way the ports of the
+/// group went from `before` to `after`, so their connections survive
+fn rename_group_ports(
+    graph: &mut MyGraph,
     node_id: NodeId,
-    outputs_cache: &mut OutputsCache,
-) -> anyhow::Result<N3DValueType> {
-    // To solve a similar problem as creating node types above, we define an
-    // Evaluator as a convenience. It may be overkill for this small example,
-    // but something like this makes the code much more readable when the
-    // number of nodes starts growing.
+    before: (&[GroupPort], &[GroupPort]),
+    after: (&[GroupPort], &[GroupPort]),
+) {
+    let renamed = |before: &[GroupPort], after: &[GroupPort], name: &mut String| {
+        let old = before.iter().find(|port| port.name == *name);
+        let new = old.and_then(|old| {
+            after
+                .iter()
+                .find(|new| new.node == old.node && new.param == old.param)
+        });
+        if let Some(new) = new {
+            *name = new.name.clone();
+        }
+    };
+    let node = &mut graph[node_id];
+    for (name, _) in &mut node.inputs {
+        renamed(before.0, after.0, name);
+    }
+    for (name, _) in &mut node.outputs {
+        renamed(before.1, after.1, name);
+    }
+}
+
+type OutputsCache = HashMap<OutputId, N3DValueType>;
+
+/// Why a node could not be evaluated
+#[derive(thiserror::Error, Debug)]
+pub enum EvalError {
+    #[error("connections form a cycle")]
+    Cycle(Vec<NodeId>),
+    #[error("expected {expected}, found {found}")]
+    TypeMismatch {
+        expected: &'static str,
+        found: &'static str,
+    },
+    #[error("input '{0}' is not connected")]
+    MissingConnection(String),
+    #[error("nothing to view, connect a volume")]
+    NoViewerInput,
+    #[error(transparent)]
+    Formula(#[from] FormulaError),
+    #[error(transparent)]
+    Snippet(#[from] SnippetError),
+    #[error("group {0:?} is missing")]
+    MissingGroup(GroupId),
+    #[error("group {0} has no outputs")]
+    EmptyGroup(String),
+    /// Error inside the graph of a group, shown on its instance
+    #[error("in group {group}: {error}")]
+    Group { group: String, error: Box<EvalError> },
+    #[error(transparent)]
+    Graph(#[from] EguiGraphError),
+}
+
+/// An [`EvalError`] along with the node it happened in
+#[derive(thiserror::Error, Debug)]
+#[error("{error}")]
+pub struct NodeError {
+    pub node: NodeId,
+    pub error: EvalError,
+}
+
+impl NodeError {
+    /// Nodes the error is shown on, every node of a cycle
+    pub fn nodes(&self) -> Vec<NodeId> {
+        match &self.error {
+            EvalError::Cycle(nodes) => nodes.clone(),
+            _ => vec![self.node],
+        }
+    }
+}
+
+/// Nodes of a cycle among the dependencies of `node_id`, each depending on
+/// the next and the last on the first
+pub fn find_cycle(graph: &MyGraph, node_id: NodeId) -> Option<Vec<NodeId>> {
+    fn visit(
+        graph: &MyGraph,
+        node_id: NodeId,
+        path: &mut Vec<NodeId>,
+        done: &mut HashSet<NodeId>,
+    ) -> Option<Vec<NodeId>> {
+        if let Some(start) = path.iter().position(|node| *node == node_id) {
+            return Some(path[start..].to_vec());
+        }
+        if done.contains(&node_id) {
+            return None;
+        }
+        path.push(node_id);
+        for (_, input) in &graph[node_id].inputs {
+            if let Some(output) = graph.connection(*input) {
+                if let Some(cycle) = visit(graph, graph[output].node, path, done) {
+                    return Some(cycle);
+                }
+            }
+        }
+        path.pop();
+        done.insert(node_id);
+        None
+    }
+    visit(graph, node_id, &mut Vec::new(), &mut HashSet::new())
+}
+
+/// What evaluation reads besides the graph itself
+#[derive(Clone, Copy)]
+pub struct EvalContext<'a> {
+    /// Keyframed inline values, sampled at `frame`
+    pub animation: &'a Animation,
+    pub frame: f32,
+    /// Definitions of the groups instanced in the graph
+    pub groups: &'a NodeGroups,
+}
 
-    struct Evaluator<'a> {
+impl<'a> EvalContext<'a> {
+    pub fn new(user_state: &'a MyGraphState) -> Self {
+        Self {
+            animation: &user_state.animation,
+            frame: user_state.frame,
+            groups: &user_state.groups,
+        }
+    }
+}
+
+/// Reads the inputs and stores the outputs of the node being evaluated, see
+/// [`NodeDefinition::evaluate`]
+pub struct Evaluator<'a> {
+    graph: &'a MyGraph,
+    ctx: EvalContext<'a>,
+    outputs_cache: &'a mut OutputsCache,
+    node_id: NodeId,
+}
+impl<'a> Evaluator<'a> {
+    fn new(
         graph: &'a MyGraph,
+        ctx: EvalContext<'a>,
         outputs_cache: &'a mut OutputsCache,
         node_id: NodeId,
+    ) -> Self {
+        Self {
+            graph,
+            ctx,
+            outputs_cache,
+            node_id,
+        }
+    }
+    /// Node being evaluated
+    pub fn node_id(&self) -> NodeId {
+        self.node_id
+    }
+    pub fn evaluate_input(&mut self, name: &str) -> Result<N3DValueType, NodeError> {
+        // Calling `evaluate_input` recursively evaluates other nodes in the
+        // graph until the input value for a paramater has been computed.
+        evaluate_input(self.graph, self.node_id, name, self.ctx, self.outputs_cache)
+    }
+    /// Attaches an error of this node to it
+    pub fn locate<T>(&self, result: Result<T, EvalError>) -> Result<T, NodeError> {
+        result.map_err(|error| NodeError {
+            node: self.node_id,
+            error,
+        })
+    }
+    pub fn populate_output(
+        &mut self,
+        name: &str,
+        value: N3DValueType,
+    ) -> Result<N3DValueType, NodeError> {
+        // After computing an output, we don't just return it, but we also
+        // populate the outputs cache with it. This ensures the evaluation
+        // only ever computes an output once.
+        //
+        // The return value of the function is the "final" output of the
+        // node, the thing we want to get from the evaluation. The example
+        // would be slightly more contrived when we had multiple output
+        // values, as we would need to choose which of the outputs is the
+        // one we want to return. Other outputs could be used as
+        // intermediate values.
+        //
+        // Note that this is just one possible semantic interpretation of
+        // the graphs, you can come up with your own evaluation semantics!
+        let result = populate_output(self.graph, self.outputs_cache, self.node_id, name, value);
+        self.locate(result)
+    }
+    // Scalars and vectors are passed around as expressions so runtime
+    // values like the playback time can flow through the math nodes.
+    // Constant inputs fold back into plain values.
+    pub fn input_expr(&mut self, name: &str, kind: ValueKind) -> Result<Expr, NodeError> {
+        let value = self.evaluate_input(name)?;
+        self.locate(value.try_to_expr(kind))
+    }
+    pub fn output_expr(&mut self, name: &str, value: Expr) -> Result<N3DValueType, NodeError> {
+        self.populate_output(name, N3DValueType::from_expr(value))
+    }
+    pub fn input_vec2(&mut self, name: &str) -> Result<Expr, NodeError> {
+        self.input_expr(name, ValueKind::Vec2)
+    }
+    pub fn output_vec2(&mut self, name: &str, value: Expr) -> Result<N3DValueType, NodeError> {
+        self.output_expr(name, value)
+    }
+    pub fn input_vec3(&mut self, name: &str) -> Result<Expr, NodeError> {
+        self.input_expr(name, ValueKind::Vec3)
+    }
+    pub fn output_vec3(&mut self, name: &str, value: Expr) -> Result<N3DValueType, NodeError> {
+        self.output_expr(name, value)
+    }
+    pub fn input_scalar(&mut self, name: &str) -> Result<Expr, NodeError> {
+        self.input_expr(name, ValueKind::Scalar)
+    }
+    pub fn output_scalar(&mut self, name: &str, value: Expr) -> Result<N3DValueType, NodeError> {
+        self.output_expr(name, value)
     }
-    impl<'a> Evaluator<'a> {
-        fn new(graph: &'a MyGraph, outputs_cache: &'a mut OutputsCache, node_id: NodeId) -> Self {
-            Self {
-                graph,
-                outputs_cache,
-                node_id,
-            }
-        }
-        fn evaluate_input(&mut self, name: &str) -> anyhow::Result<N3DValueType> {
-            // Calling `evaluate_input` recursively evaluates other nodes in the
-            // graph until the input value for a paramater has been computed.
-            evaluate_input(self.graph, self.node_id, name, self.outputs_cache)
-        }
-        fn populate_output(
-            &mut self,
-            name: &str,
-            value: N3DValueType,
-        ) -> anyhow::Result<N3DValueType> {
-            // After computing an output, we don't just return it, but we also
-            // populate the outputs cache with it. This ensures the evaluation
-            // only ever computes an output once.
-            //
-            // The return value of the function is the "final" output of the
-            // node, the thing we want to get from the evaluation. The example
-            // would be slightly more contrived when we had multiple output
-            // values, as we would need to choose which of the outputs is the
-            // one we want to return. Other outputs could be used as
-            // intermediate values.
-            //
-            // Note that this is just one possible semantic interpretation of
-            // the graphs, you can come up with your own evaluation semantics!
-            populate_output(self.graph, self.outputs_cache, self.node_id, name, value)
-        }
-        fn input_vec2(&mut self, name: &str) -> anyhow::Result<nalgebra::Vector2<f32>> {
-            self.evaluate_input(name)?.try_to_vec2()
-        }
-        fn output_vec2(
-            &mut self,
-            name: &str,
-            value: nalgebra::Vector2<f32>,
-        ) -> anyhow::Result<N3DValueType> {
-            self.populate_output(name, N3DValueType::Vec2 { value })
-        }
-        fn input_vec3(&mut self, name: &str) -> anyhow::Result<nalgebra::Vector3<f32>> {
-            self.evaluate_input(name)?.try_to_vec3()
-        }
-        fn output_vec3(
-            &mut self,
-            name: &str,
-            value: nalgebra::Vector3<f32>,
-        ) -> anyhow::Result<N3DValueType> {
-            self.populate_output(name, N3DValueType::Vec3 { value })
-        }
-        fn input_scalar(&mut self, name: &str) -> anyhow::Result<f32> {
-            self.evaluate_input(name)?.try_to_scalar()
-        }
-        fn output_scalar(&mut self, name: &str, value: f32) -> anyhow::Result<N3DValueType> {
-            self.populate_output(name, N3DValueType::Scalar { value })
-        }
-        fn input_sdf_position(&mut self, name: &str) -> anyhow::Result<String> {
-            self.evaluate_input(name)?.try_to_sdf_position()
-        }
-        fn output_sdf_position(&mut self, name: &str, value: String) -> anyhow::Result<N3DValueType> {
-            self.populate_output(name, N3DValueType::SDFPosition { value })
-        }
-        fn input_sdf_volume(&mut self, name: &str) -> anyhow::Result<String> {
-            self.evaluate_input(name)?.try_to_sdf_volume()
-        }
-        fn output_sdf_volume(&mut self, name: &str, value: String) -> anyhow::Result<N3DValueType> {
-            self.populate_output(name, N3DValueType::SDFVolume { value })
-        }
-    }
-
-    let node = &graph[node_id];
-    let mut evaluator = Evaluator::new(graph, outputs_cache, node_id);
-    match node.user_data.template {
-        N3DNodeTemplate::ScalarAdd => {
-            let a = evaluator.input_scalar("A")?;
-            let b = evaluator.input_scalar("B")?;
-            evaluator.output_scalar("out", a + b)
-        }
-        N3DNodeTemplate::ScalarSub => {
-            let a = evaluator.input_scalar("A")?;
-            let b = evaluator.input_scalar("B")?;
-            evaluator.output_scalar("out", a - b)
-        }
-        N3DNodeTemplate::Vec2ScalarMul => {
-            let scalar = evaluator.input_scalar("scalar")?;
-            let vector = evaluator.input_vec2("vector")?;
-            evaluator.output_vec2("out", vector * scalar)
-        }
-        N3DNodeTemplate::Vec2Add => {
-            let v1 = evaluator.input_vec2("v1")?;
-            let v2 = evaluator.input_vec2("v2")?;
-            evaluator.output_vec2("out", v1 + v2)
-        }
-        N3DNodeTemplate::Vec2Subtract => {
-            let v1 = evaluator.input_vec2("v1")?;
-            let v2 = evaluator.input_vec2("v2")?;
-            evaluator.output_vec2("out", v1 - v2)
-        }
-        N3DNodeTemplate::NewVec2 => {
-            let x = evaluator.input_scalar("x")?;
-            let y = evaluator.input_scalar("y")?;
-            evaluator.output_vec2("out", nalgebra::Vector2::new(x, y))
-        }
-        N3DNodeTemplate::NewScalar => {
-            let value = evaluator.input_scalar("value")?;
-            evaluator.output_scalar("out", value)
-        }
-        N3DNodeTemplate::NewVec3 => {
-            let x = evaluator.input_scalar("x")?;
-            let y = evaluator.input_scalar("y")?;
-            let z = evaluator.input_scalar("z")?;
-            evaluator.output_vec3("out", nalgebra::Vector3::new(x, y, z))
-            //evaluator.output_vec3("out", nalgebra::Vector3::new(1.0, 2.0, 3.0))
-        }
-        N3DNodeTemplate::Vec3Add => {
-            let v1 = evaluator.input_vec3("v1")?;
-            let v2 = evaluator.input_vec3("v2")?;
-            evaluator.output_vec3("out", v1 + v2)
-        }
-        N3DNodeTemplate::Vec3Sub => {
-            let v1 = evaluator.input_vec3("v1")?;
-            let v2 = evaluator.input_vec3("v2")?;
-            evaluator.output_vec3("out", v1 - v2)
-        }
-        N3DNodeTemplate::Vec3Dot => {
-            let v1 = evaluator.input_vec3("v1")?;
-            let v2 = evaluator.input_vec3("v2")?;
-            evaluator.output_scalar("out", v1.dot(&v2))
-        }
-        N3DNodeTemplate::Vec3Cross => {
-            let v1 = evaluator.input_vec3("v1")?;
-            let v2 = evaluator.input_vec3("v2")?;
-            evaluator.output_vec3("out", v1.cross(&v2))
-        }
-        N3DNodeTemplate::SDFPosition => {
-            evaluator.output_sdf_position("out", "p".to_string())
-        }
-        N3DNodeTemplate::SDFTranslate => {
-            let t = evaluator.input_vec3("translation")?;
-            let sdfp = evaluator.input_sdf_position("sdf position")?;
-            evaluator.output_sdf_position("out", format!("{} - vec3({}, {}, {})", sdfp, t[0], t[1], t[2]))
-        }
-        N3DNodeTemplate::SDFRotate => {
-            let r = evaluator.input_vec3("rotation")?;
-            let sdfp = evaluator.input_sdf_position("sdf position")?;
-            evaluator.output_sdf_position("out", format!("rotate({}, vec3({}, {}, {}))", sdfp, r[0], r[1], r[2]))
-        }
-        N3DNodeTemplate::SDFScale => {
-            let s = evaluator.input_vec3("scale")?;
-            let sdfp = evaluator.input_sdf_position("sdf position")?;
-            evaluator.output_sdf_position("out", format!("scale({}, vec3({}, {}, {}))", sdfp, s[0], s[1], s[2]))
-        }
-        N3DNodeTemplate::SDFBox => {
-            let dim = evaluator.input_vec3("dimensions")?;
-            let fil = evaluator.input_scalar("fillet")?;
-            let pos = evaluator.input_sdf_position("sdf position")?;
-            evaluator.output_sdf_volume("out", format!("sdf_box({}, vec3({}, {}, {}) - vec3({})) - {}", pos, dim[0], dim[1], dim[2], fil, fil))
-        }
-        N3DNodeTemplate::SDFSphere => {
-            let rad = evaluator.input_scalar("radius")?;
-            let pos = evaluator.input_sdf_position("sdf position")?;
-            evaluator.output_sdf_volume("out", format!("sdf_sphere({}, {})", pos, rad))
-        }
-        N3DNodeTemplate::SDFCylinder => {
-            let rad = evaluator.input_scalar("radius")?;
-            let len = evaluator.input_scalar("length")?;
-            let fillet = evaluator.input_scalar("fillet")?;
-            let pos = evaluator.input_sdf_position("sdf position")?;
-            evaluator.output_sdf_volume("out", format!("sdf_cylinder({}, {} - {}, {} - {}) - {}", pos, len, fillet, rad, fillet, fillet))
-        }
-        N3DNodeTemplate::SDFUnion => {
-            let sdf1 = evaluator.input_sdf_volume("sdf 1")?;
-            let sdf2 = evaluator.input_sdf_volume("sdf 2")?;
-            evaluator.output_sdf_volume("out", format!("op_union({}, {})", sdf1, sdf2))
-        }
-        N3DNodeTemplate::SDFSmoothUnion => {
-            let sdf1 = evaluator.input_sdf_volume("sdf 1")?;
-            let sdf2 = evaluator.input_sdf_volume("sdf 2")?;
-            let fac = evaluator.input_scalar("fac")?;
-            evaluator.output_sdf_volume("out", format!("op_union_smooth({}, {}, {})", sdf1, sdf2, fac))
-        }
-        N3DNodeTemplate::SDFDiff => {
-            let sdf1 = evaluator.input_sdf_volume("sdf 1")?;
-            let sdf2 = evaluator.input_sdf_volume("sdf 2")?;
-            evaluator.output_sdf_volume("out", format!("op_diff({}, {})", sdf1, sdf2))
-        }
-        N3DNodeTemplate::SDFSmoothDiff => {
-            let sdf1 = evaluator.input_sdf_volume("sdf 1")?;
-            let sdf2 = evaluator.input_sdf_volume("sdf 2")?;
-            let fac = evaluator.input_scalar("fac")?;
-            evaluator.output_sdf_volume("out", format!("op_diff_smooth({}, {}, {})", sdf1, sdf2, fac))
-        }
-        N3DNodeTemplate::SDFViewer => {
-            if let Ok(node) = evaluator.input_sdf_volume("sdf") {
-                return Ok(N3DValueType::SDFVolume { value: node } );
-            }
-            anyhow::bail!("Viewer node error")
+    pub fn input_text(&mut self, name: &str) -> Result<String, NodeError> {
+        let value = self.evaluate_input(name)?;
+        self.locate(value.try_to_text())
+    }
+    pub fn input_sdf_position(&mut self, name: &str) -> Result<SDFPosition, NodeError> {
+        let value = self.evaluate_input(name)?;
+        self.locate(value.try_to_sdf_position())
+    }
+    pub fn output_sdf_position(&mut self, name: &str, value: SDFPosition) -> Result<N3DValueType, NodeError> {
+        self.populate_output(name, N3DValueType::SDFPosition { value })
+    }
+    /// Volumes and 2D shapes have no inline value, their placeholder
+    /// means nothing is connected
+    pub fn input_shape(&mut self, name: &str) -> Result<N3DValueType, NodeError> {
+        let value = self.evaluate_input(name)?;
+        match value {
+            N3DValueType::SDFVolume { value: SDFVolume::Empty }
+            | N3DValueType::SDF2D { value: SDF2D::Empty } => {
+                self.locate(Err(EvalError::MissingConnection(name.to_string())))
+            }
+            value => Ok(value),
         }
     }
+    pub fn input_sdf_volume(&mut self, name: &str) -> Result<SDFVolume, NodeError> {
+        let value = self.input_shape(name)?;
+        self.locate(value.try_to_sdf_volume())
+    }
+    pub fn output_sdf_volume(&mut self, name: &str, value: SDFVolume) -> Result<N3DValueType, NodeError> {
+        self.populate_output(name, N3DValueType::SDFVolume { value })
+    }
+    pub fn input_sdf_2d(&mut self, name: &str) -> Result<SDF2D, NodeError> {
+        let value = self.input_shape(name)?;
+        self.locate(value.try_to_sdf_2d())
+    }
+    pub fn output_sdf_2d(&mut self, name: &str, value: SDF2D) -> Result<N3DValueType, NodeError> {
+        self.populate_output(name, N3DValueType::SDF2D { value })
+    }
+    pub fn input_material(&mut self, name: &str) -> Result<Material, NodeError> {
+        let value = self.evaluate_input(name)?;
+        self.locate(value.try_to_material())
+    }
+    pub fn output_material(&mut self, name: &str, value: Material) -> Result<N3DValueType, NodeError> {
+        self.populate_output(name, N3DValueType::Material { value })
+    }
+    pub fn input_matcap(&mut self, name: &str) -> Result<Matcap, NodeError> {
+        let value = self.evaluate_input(name)?;
+        self.locate(value.try_to_matcap())
+    }
+    /// Evaluates the graph inside a group with the inputs of the instance.
+    /// Scalar and vector inputs stand in as arguments and the position input
+    /// as the origin, so volumes come out as the body of the GLSL function
+    /// shared by all instances and other outputs get the inputs substituted
+    /// back. With several position inputs there is no one point to place the
+    /// nodes relative to, those groups are evaluated with the inputs as is.
+    fn evaluate_group(&mut self, group: &NodeGroup) -> Result<N3DValueType, NodeError> {
+        let mut graph = group.graph.clone();
+        let mut values = Vec::new();
+        for port in &group.inputs {
+            let value = self.evaluate_input(&port.name)?;
+            let input = self.locate(graph[port.node].get_input(&port.param).map_err(EvalError::from))?;
+            values.push((port, input, value));
+        }
+        let positions: Vec<&SDFPosition> = values
+            .iter()
+            .filter_map(|(_, _, value)| match value {
+                N3DValueType::SDFPosition { value } => Some(value),
+                _ => None,
+            })
+            .collect();
+        let position = match positions.as_slice() {
+            [] => Some(SDFPosition::Origin),
+            [position] => Some((*position).clone()),
+            _ => None,
+        };
+        let mut args = Vec::new();
+        for (port, input, value) in values {
+            let kind = match port.typ {
+                N3DDataType::Scalar => Some(ValueKind::Scalar),
+                N3DDataType::Vec2 => Some(ValueKind::Vec2),
+                N3DDataType::Vec3 => Some(ValueKind::Vec3),
+                _ => None,
+            };
+            graph[input].value = match (&position, kind, value) {
+                (None, _, value) => value,
+                (Some(_), Some(kind), value) => {
+                    args.push(self.locate(value.try_to_expr(kind))?);
+                    N3DValueType::Expr {
+                        value: Expr::Arg {
+                            index: args.len() - 1,
+                            kind,
+                        },
+                    }
+                }
+                (Some(_), None, N3DValueType::SDFPosition { .. }) => N3DValueType::SDFPosition {
+                    value: SDFPosition::Origin,
+                },
+                (Some(_), None, value) => value,
+            };
+        }
+
+        let still = Animation::default();
+        let ctx = EvalContext {
+            animation: &still,
+            ..self.ctx
+        };
+        let mut cache = OutputsCache::new();
+        let inside = |error: NodeError| EvalError::Group {
+            group: group.name.clone(),
+            error: Box::new(error.error),
+        };
+        let mut result = None;
+        for port in &group.outputs {
+            let value = evaluate_port(&graph, port, ctx, &mut cache).map_err(inside);
+            let value = self.locate(value)?;
+            let value = match &position {
+                None => value,
+                Some(position) => match value {
+                    N3DValueType::SDFVolume { value: body } => N3DValueType::SDFVolume {
+                        value: SDFVolume::Call {
+                            function: group.name.clone(),
+                            position: position.clone(),
+                            args: args.clone(),
+                            body: Box::new(body),
+                        },
+                    },
+                    N3DValueType::SDFPosition { value } => N3DValueType::SDFPosition {
+                        value: value.substitute(position, &args),
+                    },
+                    N3DValueType::SDF2D { value } => N3DValueType::SDF2D {
+                        value: value.substitute(&args),
+                    },
+                    N3DValueType::Material { value } => N3DValueType::Material {
+                        value: value.substitute(&args),
+                    },
+                    N3DValueType::Expr { value } => N3DValueType::from_expr(value.substitute(&args)),
+                    value => value,
+                },
+            };
+            result = Some(self.populate_output(&port.name, value)?);
+        }
+        let result = result.ok_or_else(|| EvalError::EmptyGroup(group.name.clone()));
+        self.locate(result)
+    }
+}
+
+/// Evaluates this node and everything it depends on, once it is known that
+/// the recursion ends.
+pub fn evaluate_node(
+    graph: &MyGraph,
+    node_id: NodeId,
+    ctx: EvalContext,
+    outputs_cache: &mut OutputsCache,
+) -> Result<N3DValueType, NodeError> {
+    if let Some(cycle) = find_cycle(graph, node_id) {
+        return Err(NodeError {
+            node: cycle[0],
+            error: EvalError::Cycle(cycle),
+        });
+    }
+    evaluate_acyclic(graph, node_id, ctx, outputs_cache)
+}
+
+/// Recursively evaluates all dependencies of this node, then evaluates the node itself.
+fn evaluate_acyclic(
+    graph: &MyGraph,
+    node_id: NodeId,
+    ctx: EvalContext,
+    outputs_cache: &mut OutputsCache,
+) -> Result<N3DValueType, NodeError> {
+    let mut evaluator = Evaluator::new(graph, ctx, outputs_cache, node_id);
+    match graph[node_id].user_data.template {
+        N3DNodeTemplate::Node(node) => node.evaluate(&mut evaluator),
+        N3DNodeTemplate::Group(id) => match ctx.groups.get(id) {
+            Some(group) => evaluator.evaluate_group(group),
+            None => evaluator.locate(Err(EvalError::MissingGroup(id))),
+        },
+    }
+}
+
+/// Value of the output a group port forwards to, evaluating its node if needed
+fn evaluate_port(
+    graph: &MyGraph,
+    port: &GroupPort,
+    ctx: EvalContext,
+    outputs_cache: &mut OutputsCache,
+) -> Result<N3DValueType, NodeError> {
+    let output = graph[port.node]
+        .get_output(&port.param)
+        .map_err(|error| NodeError {
+            node: port.node,
+            error: error.into(),
+        })?;
+    if !outputs_cache.contains_key(&output) {
+        evaluate_node(graph, port.node, ctx, outputs_cache)?;
+    }
+    Ok(outputs_cache
+        .get(&output)
+        .expect("Cache should be populated")
+        .clone())
 }
 fn populate_output(
     graph: &MyGraph,
@@ -929,7 +2106,7 @@ fn populate_output(
     node_id: NodeId,
     param_name: &str,
     value: N3DValueType,
-) -> anyhow::Result<N3DValueType> {
+) -> Result<N3DValueType, EvalError> {
     let output_id = graph[node_id].get_output(param_name)?;
     outputs_cache.insert(output_id, value.clone());
     Ok(value)
@@ -940,9 +2117,15 @@ fn evaluate_input(
     graph: &MyGraph,
     node_id: NodeId,
     param_name: &str,
+    ctx: EvalContext,
     outputs_cache: &mut OutputsCache,
-) -> anyhow::Result<N3DValueType> {
-    let input_id = graph[node_id].get_input(param_name)?;
+) -> Result<N3DValueType, NodeError> {
+    let input_id = graph[node_id]
+        .get_input(param_name)
+        .map_err(|error| NodeError {
+            node: node_id,
+            error: error.into(),
+        })?;
 
     // The output of another node is connected.
     if let Some(other_output_id) = graph.connection(input_id) {
@@ -955,7 +2138,7 @@ fn evaluate_input(
         // recursively evaluate it.
         else {
             // Calling this will populate the cache
-            evaluate_node(graph, graph[other_output_id].node, outputs_cache)?;
+            evaluate_acyclic(graph, graph[other_output_id].node, ctx, outputs_cache)?;
 
             // Now that we know the value is cached, return it
             Ok(outputs_cache
@@ -963,8 +2146,64 @@ fn evaluate_input(
                 .expect("Cache should be populated").clone())
         }
     }
-    // No existing connection, take the inline value instead.
-    else {
+    // No existing connection, take the keyframed or inline value instead.
+    else if let Some(value) = ctx.animation.sample(node_id, param_name, ctx.frame) {
+        Ok(N3DValueType::from_expr(value.into()))
+    } else {
         Ok(graph[input_id].value.clone())
     }
 }
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::nodes::{math::ScalarAdd, volume::SDFViewer};
+
+    /// Adds a node the way the node finder does, for the tests of every module
+    pub(crate) fn add(
+        state: &mut MyEditorState,
+        user_state: &mut MyGraphState,
+        template: N3DNodeTemplate,
+    ) -> NodeId {
+        let node = state.graph.add_node(
+            template.node_graph_label(user_state),
+            template.user_data(user_state),
+            |graph, node| template.build_node(graph, user_state, node),
+        );
+        state.node_order.push(node);
+        node
+    }
+
+    /// Connects the `out` output of `from` to the input `param` of `to`
+    pub(crate) fn connect(state: &mut MyEditorState, from: NodeId, to: NodeId, param: &str) {
+        let output = state.graph[from].get_output("out").unwrap();
+        let input = state.graph[to].get_input(param).unwrap();
+        state.graph.add_connection(output, input);
+    }
+
+    #[test]
+    fn errors_are_located() {
+        let mut state = MyEditorState::default();
+        let mut user_state = MyGraphState::default();
+        let a = add(&mut state, &mut user_state, N3DNodeTemplate::Node(&ScalarAdd));
+        let b = add(&mut state, &mut user_state, N3DNodeTemplate::Node(&ScalarAdd));
+        let viewer = add(&mut state, &mut user_state, N3DNodeTemplate::Node(&SDFViewer));
+        connect(&mut state, a, b, "A");
+        connect(&mut state, b, a, "A");
+        let evaluate = |node| {
+            evaluate_node(
+                &state.graph,
+                node,
+                EvalContext::new(&user_state),
+                &mut HashMap::new(),
+            )
+        };
+
+        let err = evaluate(a).unwrap_err();
+        assert!(matches!(err.error, EvalError::Cycle(_)));
+        assert_eq!(err.nodes(), vec![a, b]);
+        let err = evaluate(viewer).unwrap_err();
+        assert!(matches!(err.error, EvalError::NoViewerInput));
+        assert_eq!(err.node, viewer);
+    }
+}
@@ -0,0 +1,423 @@
+//! Node types that can be placed in the graph.
+//!
+//! Every node is a type implementing [`NodeDefinition`], which declares its
+//! ports, where the node finder lists it and how it evaluates. Volumes
+//! evaluate to [`SDFVolume`] trees lowered to GLSL with the rest of the
+//! scene, a node with a distance function of its own returns it as
+//! [`SDFVolume::Glsl`].
+//!
+//! The built-in nodes live in the submodules. Other crates add theirs with
+//! [`register`] before opening a project, after which they are listed and
+//! saved like the built-in ones.
+use std::sync::RwLock;
+
+use egui_node_graph::{InputParamKind, NodeId};
+use nalgebra::{Vector2, Vector3};
+
+use crate::{
+    node_graph::{Evaluator, MyGraph, N3DDataType, N3DValueType, NodeError},
+    sdf::ir::{SDFPosition, SDFVolume, SDF2D},
+};
+
+pub mod material;
+pub mod math;
+pub mod shape;
+pub mod snippet;
+pub mod space;
+pub mod volume;
+
+/// Everything the editor needs to know about one type of node
+pub trait NodeDefinition: Send + Sync {
+    /// Identifies the node type in project files, so it must never change.
+    /// Project files store it as an identifier, so it must be one in Rust
+    /// and RON, like `SDFSphere`, and it can't be `Group`.
+    fn name(&self) -> &'static str;
+    /// Shown in the node finder and as the title of the node
+    fn label(&self) -> &'static str;
+    /// Lists of the node finder the node appears in
+    fn categories(&self) -> Vec<&'static str>;
+    /// Adds the ports of a newly created node
+    fn build(&self, node: &mut NodeBuilder);
+    /// Computes the outputs of the node from its inputs, returning the one
+    /// the node evaluates to
+    fn evaluate(&self, evaluator: &mut Evaluator) -> Result<N3DValueType, NodeError>;
+    /// Adds and removes ports after the node was edited, for nodes whose
+    /// ports depend on their inline values
+    fn update_ports(&self, _graph: &mut MyGraph, _node_id: NodeId) {}
+    /// Problem with the inline value of an input, shown below its widget
+    fn check_input(&self, _name: &str, _value: &N3DValueType) -> Option<String> {
+        None
+    }
+    /// Errors the GLSL compiler reported in `log` for the `fragment` shader
+    /// that were caused by this node, for nodes writing GLSL of their own
+    fn shader_errors(
+        &self,
+        _graph: &MyGraph,
+        _node_id: NodeId,
+        _fragment: &str,
+        _log: &str,
+    ) -> Vec<String> {
+        Vec::new()
+    }
+}
+
+/// Built-in nodes in the order the node finder lists them
+pub const BUILTIN: &[&dyn NodeDefinition] = &[
+    &math::NewScalar,
+    &math::NewVec2,
+    &math::ScalarAdd,
+    &math::ScalarSub,
+    &math::ScalarMul,
+    &math::ScalarDiv,
+    &math::ScalarMin,
+    &math::ScalarMax,
+    &math::ScalarClamp,
+    &math::ScalarMix,
+    &math::ScalarRemap,
+    &math::ScalarAbs,
+    &math::ScalarPow,
+    &math::ScalarSin,
+    &math::ScalarCos,
+    &math::ScalarFloor,
+    &math::ScalarFract,
+    &math::Time,
+    &math::Vec2Add,
+    &math::Vec2Subtract,
+    &math::Vec2ScalarMul,
+    &math::Vec2Length,
+    &math::Vec2Normalize,
+    &math::NewVec3,
+    &math::Vec3Add,
+    &math::Vec3Sub,
+    &math::Vec3Dot,
+    &math::Vec3Cross,
+    &math::Vec3Scale,
+    &math::Vec3Length,
+    &math::Vec3Normalize,
+    &math::Vec3Mix,
+    &math::Expression,
+    &space::NewSDFPosition,
+    &space::SDFTranslate,
+    &space::SDFRotate,
+    &space::SDFScale,
+    &space::SDFRepeat,
+    &space::SDFRepeatLimited,
+    &space::SDFMirror,
+    &space::SDFRepeatPolar,
+    &space::SDFTwist,
+    &space::SDFBend,
+    &space::SDFTaper,
+    &space::SDFElongate,
+    &volume::SDFBox,
+    &volume::SDFSphere,
+    &volume::SDFCylinder,
+    &volume::SDFTorus,
+    &volume::SDFCapsule,
+    &volume::SDFCone,
+    &volume::SDFPlane,
+    &volume::SDFEllipsoid,
+    &volume::SDFHexPrism,
+    &volume::SDFTriPrism,
+    &volume::SDFRoundedCone,
+    &volume::SDFOctahedron,
+    &volume::SDFLink,
+    &volume::SDFPyramid,
+    &volume::SDFUnion,
+    &volume::SDFSmoothUnion,
+    &volume::SDFDiff,
+    &volume::SDFSmoothDiff,
+    &volume::SDFIntersect,
+    &volume::SDFSmoothIntersect,
+    &volume::SDFRound,
+    &volume::SDFShell,
+    &volume::SDFHollow,
+    &snippet::GlslSnippet,
+    &shape::SDF2DCircle,
+    &shape::SDF2DRectangle,
+    &shape::SDF2DRoundedRectangle,
+    &shape::SDF2DPolygon,
+    &shape::SDF2DArc,
+    &shape::SDF2DSegment,
+    &shape::SDF2DUnion,
+    &shape::SDF2DDiff,
+    &shape::SDF2DIntersect,
+    &shape::SDFExtrude,
+    &shape::SDFRevolve,
+    &material::NewMaterial,
+    &material::SDFAssignMaterial,
+    &volume::SDFViewer,
+];
+
+/// Nodes added by [`register`]
+static REGISTERED: RwLock<Vec<&'static dyn NodeDefinition>> = RwLock::new(Vec::new());
+
+/// Makes a node type available to the editor and to project files. A node
+/// with the name of a built-in or earlier registered one replaces it.
+///
+/// Panics if the name could not be saved, see [`NodeDefinition::name`]
+pub fn register(node: &'static dyn NodeDefinition) {
+    assert!(
+        is_valid_name(node.name()),
+        "node type name {:?} is not an identifier or is reserved",
+        node.name()
+    );
+    let mut registered = REGISTERED.write().unwrap();
+    registered.retain(|other| other.name() != node.name());
+    registered.push(node);
+}
+
+/// Removes the node type registered under `name`, bringing back a built-in
+/// one it replaced. Returns whether there was one.
+pub fn unregister(name: &str) -> bool {
+    let mut registered = REGISTERED.write().unwrap();
+    let count = registered.len();
+    registered.retain(|node| node.name() != name);
+    registered.len() != count
+}
+
+/// Whether project files can store a node type named `name`
+fn is_valid_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    let starts = chars
+        .next()
+        .is_some_and(|first| first.is_ascii_alphabetic() || first == '_');
+    starts && chars.all(|c| c.is_ascii_alphanumeric() || c == '_') && name != "Group"
+}
+
+/// Every available node type, the built-in ones first
+pub fn all() -> Vec<&'static dyn NodeDefinition> {
+    let registered = REGISTERED.read().unwrap();
+    BUILTIN
+        .iter()
+        .filter(|node| !registered.iter().any(|other| other.name() == node.name()))
+        .chain(registered.iter())
+        .copied()
+        .collect()
+}
+
+/// Node type stored in project files as `name`
+pub fn find(name: &str) -> Option<&'static dyn NodeDefinition> {
+    let registered = REGISTERED.read().unwrap();
+    registered
+        .iter()
+        .chain(BUILTIN)
+        .find(|node| node.name() == name)
+        .copied()
+}
+
+/// Adds the ports of a node being created, see [`NodeDefinition::build`].
+/// Port names must be unique among the inputs and among the outputs of a
+/// node, they are how evaluation refers to them.
+pub struct NodeBuilder<'a> {
+    pub graph: &'a mut MyGraph,
+    pub node_id: NodeId,
+}
+
+impl<'a> NodeBuilder<'a> {
+    /// Adds an input starting out with `value`. Whether it accepts
+    /// connections, shows a widget to edit the value or both is up to `kind`.
+    pub fn input(
+        &mut self,
+        name: &str,
+        typ: N3DDataType,
+        value: N3DValueType,
+        kind: InputParamKind,
+    ) {
+        self.graph
+            .add_input_param(self.node_id, name.to_string(), typ, value, kind, true);
+    }
+    pub fn output(&mut self, name: &str, typ: N3DDataType) {
+        self.graph
+            .add_output_param(self.node_id, name.to_string(), typ);
+    }
+    pub fn input_scalar(&mut self, name: &str, value: f32) {
+        self.input(
+            name,
+            N3DDataType::Scalar,
+            N3DValueType::Scalar { value },
+            InputParamKind::ConnectionOrConstant,
+        );
+    }
+    pub fn input_vec2(&mut self, name: &str, value: Vector2<f32>) {
+        self.input(
+            name,
+            N3DDataType::Vec2,
+            N3DValueType::Vec2 { value },
+            InputParamKind::ConnectionOrConstant,
+        );
+    }
+    pub fn input_vec3(&mut self, name: &str, value: Vector3<f32>) {
+        self.input(
+            name,
+            N3DDataType::Vec3,
+            N3DValueType::Vec3 { value },
+            InputParamKind::ConnectionOrConstant,
+        );
+    }
+    /// Position input, the origin while nothing is connected
+    pub fn input_sdf_position(&mut self, name: &str) {
+        self.input(
+            name,
+            N3DDataType::SDFPosition,
+            N3DValueType::SDFPosition {
+                value: SDFPosition::Origin,
+            },
+            InputParamKind::ConnectionOrConstant,
+        );
+    }
+    /// Volume input, which has to be connected
+    pub fn input_sdf_volume(&mut self, name: &str) {
+        self.input(
+            name,
+            N3DDataType::SDFVolume,
+            N3DValueType::SDFVolume {
+                value: SDFVolume::Empty,
+            },
+            InputParamKind::ConnectionOnly,
+        );
+    }
+    /// Profile input, which has to be connected
+    pub fn input_sdf_2d(&mut self, name: &str) {
+        self.input(
+            name,
+            N3DDataType::SDF2D,
+            N3DValueType::SDF2D {
+                value: SDF2D::Empty,
+            },
+            InputParamKind::ConnectionOnly,
+        );
+    }
+    pub fn output_scalar(&mut self, name: &str) {
+        self.output(name, N3DDataType::Scalar);
+    }
+    pub fn output_vec2(&mut self, name: &str) {
+        self.output(name, N3DDataType::Vec2);
+    }
+    pub fn output_vec3(&mut self, name: &str) {
+        self.output(name, N3DDataType::Vec3);
+    }
+    pub fn output_sdf_position(&mut self, name: &str) {
+        self.output(name, N3DDataType::SDFPosition);
+    }
+    pub fn output_sdf_volume(&mut self, name: &str) {
+        self.output(name, N3DDataType::SDFVolume);
+    }
+    pub fn output_sdf_2d(&mut self, name: &str) {
+        self.output(name, N3DDataType::SDF2D);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    #[cfg(feature = "persistence")]
+    use crate::{camera::Camera, project::Project};
+    use crate::{
+        node_graph::{
+            evaluate_node, tests::add, EvalContext, MyEditorState, MyGraphState, N3DNodeTemplate,
+        },
+        sdf::{ir::GlslFunction, SDFBuilder},
+    };
+
+    /// Node defined outside the built-in modules, with its own GLSL
+    struct Blob;
+
+    impl NodeDefinition for Blob {
+        fn name(&self) -> &'static str {
+            "Blob"
+        }
+        fn label(&self) -> &'static str {
+            "Blob"
+        }
+        fn categories(&self) -> Vec<&'static str> {
+            vec!["SDF"]
+        }
+        fn build(&self, node: &mut NodeBuilder) {
+            node.input_scalar("size", 1.0);
+            node.input_sdf_position("sdf position");
+            node.output_sdf_volume("out");
+        }
+        fn evaluate(&self, evaluator: &mut Evaluator) -> Result<N3DValueType, NodeError> {
+            let size = evaluator.input_scalar("size")?;
+            let position = evaluator.input_sdf_position("sdf position")?;
+            let function = GlslFunction {
+                name: "blob".to_string(),
+                source: "float blob(vec3 p, float s) {\n    return length(p) - s;\n}\n".to_string(),
+            };
+            let args = vec![size];
+            evaluator.output_sdf_volume(
+                "out",
+                SDFVolume::Glsl {
+                    function,
+                    position,
+                    args,
+                },
+            )
+        }
+    }
+
+    #[test]
+    fn registered_node_lowers_its_glsl() {
+        register(&Blob);
+        assert!(all().iter().any(|node| node.name() == "Blob"));
+        let template = N3DNodeTemplate::Node(find("Blob").unwrap());
+
+        let mut state = MyEditorState::default();
+        let mut user_state = MyGraphState::default();
+        let node = add(&mut state, &mut user_state, template);
+        let value = evaluate_node(
+            &state.graph,
+            node,
+            EvalContext::new(&user_state),
+            &mut HashMap::new(),
+        )
+        .unwrap();
+        let volume = value.try_to_sdf_volume().unwrap();
+        let lowered = SDFBuilder::lower_volume_params(&volume, 0.0);
+        assert_eq!(lowered.scene, "blob(p, u_params[0])");
+        assert!(lowered.functions.starts_with("float blob(vec3 p, float s)"));
+
+        #[cfg(feature = "persistence")]
+        {
+            let dir = std::env::temp_dir().join(format!("n3d-registered-{}", std::process::id()));
+            std::fs::create_dir_all(&dir).unwrap();
+            let path = dir.join("blob.n3d");
+            Project::save(&path, &state, &user_state, &Camera::new()).unwrap();
+            let project = Project::open(&path).unwrap();
+            assert!(matches!(
+                project.editor.graph[node].user_data.template(),
+                N3DNodeTemplate::Node(blob) if blob.name() == "Blob"
+            ));
+            std::fs::remove_dir_all(&dir).unwrap();
+        }
+
+        assert!(unregister("Blob"));
+        assert!(find("Blob").is_none());
+        assert!(!unregister("SDFSphere"));
+    }
+
+    #[cfg(feature = "persistence")]
+    #[test]
+    fn templates_are_stored_by_name() {
+        let template = N3DNodeTemplate::Node(&volume::SDFSphere);
+        let text = ron::to_string(&template).unwrap();
+        assert_eq!(text, "SDFSphere");
+        let N3DNodeTemplate::Node(node) = ron::from_str(&text).unwrap() else {
+            panic!("a node type is read back as a node");
+        };
+        assert_eq!(node.label(), "SDF Sphere");
+        assert!(ron::from_str::<N3DNodeTemplate>("NoSuchNode").is_err());
+    }
+
+    #[test]
+    fn names_must_be_identifiers() {
+        assert!(all().iter().all(|node| is_valid_name(node.name())));
+        assert!(is_valid_name("_Blob2"));
+        assert!(!is_valid_name("My Blob"));
+        assert!(!is_valid_name("2Blob"));
+        assert!(!is_valid_name(""));
+        assert!(!is_valid_name("Group"));
+    }
+}
@@ -0,0 +1,78 @@
+//! Materials and their assignment to volumes
+use egui_node_graph::InputParamKind;
+use nalgebra::Vector3;
+
+use super::{NodeBuilder, NodeDefinition};
+use crate::{
+    node_graph::{Evaluator, N3DDataType, N3DValueType, NodeError},
+    sdf::ir::{Matcap, Material, SDFVolume},
+};
+
+pub struct NewMaterial;
+
+impl NodeDefinition for NewMaterial {
+    fn name(&self) -> &'static str {
+        "Material"
+    }
+    fn label(&self) -> &'static str {
+        "Material"
+    }
+    fn categories(&self) -> Vec<&'static str> {
+        vec!["Material"]
+    }
+    fn build(&self, node: &mut NodeBuilder) {
+        node.input_vec3("color", Vector3::new(1.0, 1.0, 1.0));
+        node.input(
+            "matcap",
+            N3DDataType::Matcap,
+            N3DValueType::Matcap {
+                value: Matcap::default(),
+            },
+            InputParamKind::ConstantOnly,
+        );
+        node.input_scalar("roughness", 0.0);
+        node.output("out", N3DDataType::Material);
+    }
+    fn evaluate(&self, evaluator: &mut Evaluator) -> Result<N3DValueType, NodeError> {
+        let color = evaluator.input_vec3("color")?;
+        let matcap = evaluator.input_matcap("matcap")?;
+        let roughness = evaluator.input_scalar("roughness")?;
+        let material = Material {
+            color,
+            matcap,
+            roughness,
+        };
+        evaluator.output_material("out", material)
+    }
+}
+
+pub struct SDFAssignMaterial;
+
+impl NodeDefinition for SDFAssignMaterial {
+    fn name(&self) -> &'static str {
+        "SDFAssignMaterial"
+    }
+    fn label(&self) -> &'static str {
+        "SDF Assign Material"
+    }
+    fn categories(&self) -> Vec<&'static str> {
+        vec!["SDF", "Material"]
+    }
+    fn build(&self, node: &mut NodeBuilder) {
+        node.input_sdf_volume("sdf");
+        node.input(
+            "material",
+            N3DDataType::Material,
+            N3DValueType::Material {
+                value: Material::default(),
+            },
+            InputParamKind::ConnectionOnly,
+        );
+        node.output_sdf_volume("out");
+    }
+    fn evaluate(&self, evaluator: &mut Evaluator) -> Result<N3DValueType, NodeError> {
+        let volume = Box::new(evaluator.input_sdf_volume("sdf")?);
+        let material = evaluator.input_material("material")?;
+        evaluator.output_sdf_volume("out", SDFVolume::Material { volume, material })
+    }
+}
@@ -0,0 +1,884 @@
+//! Scalar and vector math, the values flowing into the parameters of shapes
+use std::collections::HashMap;
+
+use egui_node_graph::{InputId, InputParamKind, NodeId};
+use nalgebra::{Vector2, Vector3};
+
+use super::{NodeBuilder, NodeDefinition};
+use crate::{
+    node_graph::{EvalError, Evaluator, MyGraph, N3DDataType, N3DValueType, NodeError},
+    sdf::{
+        expr::{Expr, UnaryOp, ValueKind},
+        formula::{Formula, FormulaError},
+    },
+};
+
+pub struct NewScalar;
+
+impl NodeDefinition for NewScalar {
+    fn name(&self) -> &'static str {
+        "NewScalar"
+    }
+    fn label(&self) -> &'static str {
+        "New scalar"
+    }
+    fn categories(&self) -> Vec<&'static str> {
+        vec!["Scalar"]
+    }
+    fn build(&self, node: &mut NodeBuilder) {
+        node.input_scalar("value", 0.0);
+        node.output_scalar("out");
+    }
+    fn evaluate(&self, evaluator: &mut Evaluator) -> Result<N3DValueType, NodeError> {
+        let value = evaluator.input_scalar("value")?;
+        evaluator.output_scalar("out", value)
+    }
+}
+
+pub struct NewVec2;
+
+impl NodeDefinition for NewVec2 {
+    fn name(&self) -> &'static str {
+        "NewVec2"
+    }
+    fn label(&self) -> &'static str {
+        "New Vec2"
+    }
+    fn categories(&self) -> Vec<&'static str> {
+        vec!["Vec2"]
+    }
+    fn build(&self, node: &mut NodeBuilder) {
+        node.input_scalar("x", 0.0);
+        node.input_scalar("y", 0.0);
+        node.output_vec2("out");
+    }
+    fn evaluate(&self, evaluator: &mut Evaluator) -> Result<N3DValueType, NodeError> {
+        let x = evaluator.input_scalar("x")?;
+        let y = evaluator.input_scalar("y")?;
+        evaluator.output_vec2("out", Expr::vec2(x, y))
+    }
+}
+
+pub struct ScalarAdd;
+
+impl NodeDefinition for ScalarAdd {
+    fn name(&self) -> &'static str {
+        "ScalarAdd"
+    }
+    fn label(&self) -> &'static str {
+        "Scalar add"
+    }
+    fn categories(&self) -> Vec<&'static str> {
+        vec!["Scalar"]
+    }
+    fn build(&self, node: &mut NodeBuilder) {
+        node.input_scalar("A", 0.0);
+        node.input_scalar("B", 0.0);
+        node.output_scalar("out");
+    }
+    fn evaluate(&self, evaluator: &mut Evaluator) -> Result<N3DValueType, NodeError> {
+        let a = evaluator.input_scalar("A")?;
+        let b = evaluator.input_scalar("B")?;
+        evaluator.output_scalar("out", a + b)
+    }
+}
+
+pub struct ScalarSub;
+
+impl NodeDefinition for ScalarSub {
+    fn name(&self) -> &'static str {
+        "ScalarSub"
+    }
+    fn label(&self) -> &'static str {
+        "Scalar subtract"
+    }
+    fn categories(&self) -> Vec<&'static str> {
+        vec!["Scalar"]
+    }
+    fn build(&self, node: &mut NodeBuilder) {
+        node.input_scalar("A", 0.0);
+        node.input_scalar("B", 0.0);
+        node.output_scalar("out");
+    }
+    fn evaluate(&self, evaluator: &mut Evaluator) -> Result<N3DValueType, NodeError> {
+        let a = evaluator.input_scalar("A")?;
+        let b = evaluator.input_scalar("B")?;
+        evaluator.output_scalar("out", a - b)
+    }
+}
+
+pub struct ScalarMul;
+
+impl NodeDefinition for ScalarMul {
+    fn name(&self) -> &'static str {
+        "ScalarMul"
+    }
+    fn label(&self) -> &'static str {
+        "Scalar multiply"
+    }
+    fn categories(&self) -> Vec<&'static str> {
+        vec!["Scalar"]
+    }
+    fn build(&self, node: &mut NodeBuilder) {
+        node.input_scalar("A", 0.0);
+        node.input_scalar("B", 0.0);
+        node.output_scalar("out");
+    }
+    fn evaluate(&self, evaluator: &mut Evaluator) -> Result<N3DValueType, NodeError> {
+        let a = evaluator.input_scalar("A")?;
+        let b = evaluator.input_scalar("B")?;
+        evaluator.output_scalar("out", a * b)
+    }
+}
+
+pub struct ScalarDiv;
+
+impl NodeDefinition for ScalarDiv {
+    fn name(&self) -> &'static str {
+        "ScalarDiv"
+    }
+    fn label(&self) -> &'static str {
+        "Scalar divide"
+    }
+    fn categories(&self) -> Vec<&'static str> {
+        vec!["Scalar"]
+    }
+    fn build(&self, node: &mut NodeBuilder) {
+        node.input_scalar("A", 0.0);
+        node.input_scalar("B", 1.0);
+        node.output_scalar("out");
+    }
+    fn evaluate(&self, evaluator: &mut Evaluator) -> Result<N3DValueType, NodeError> {
+        let a = evaluator.input_scalar("A")?;
+        let b = evaluator.input_scalar("B")?;
+        evaluator.output_scalar("out", a / b)
+    }
+}
+
+pub struct ScalarMin;
+
+impl NodeDefinition for ScalarMin {
+    fn name(&self) -> &'static str {
+        "ScalarMin"
+    }
+    fn label(&self) -> &'static str {
+        "Scalar min"
+    }
+    fn categories(&self) -> Vec<&'static str> {
+        vec!["Scalar"]
+    }
+    fn build(&self, node: &mut NodeBuilder) {
+        node.input_scalar("A", 0.0);
+        node.input_scalar("B", 0.0);
+        node.output_scalar("out");
+    }
+    fn evaluate(&self, evaluator: &mut Evaluator) -> Result<N3DValueType, NodeError> {
+        let a = evaluator.input_scalar("A")?;
+        let b = evaluator.input_scalar("B")?;
+        evaluator.output_scalar("out", a.min(b))
+    }
+}
+
+pub struct ScalarMax;
+
+impl NodeDefinition for ScalarMax {
+    fn name(&self) -> &'static str {
+        "ScalarMax"
+    }
+    fn label(&self) -> &'static str {
+        "Scalar max"
+    }
+    fn categories(&self) -> Vec<&'static str> {
+        vec!["Scalar"]
+    }
+    fn build(&self, node: &mut NodeBuilder) {
+        node.input_scalar("A", 0.0);
+        node.input_scalar("B", 0.0);
+        node.output_scalar("out");
+    }
+    fn evaluate(&self, evaluator: &mut Evaluator) -> Result<N3DValueType, NodeError> {
+        let a = evaluator.input_scalar("A")?;
+        let b = evaluator.input_scalar("B")?;
+        evaluator.output_scalar("out", a.max(b))
+    }
+}
+
+pub struct ScalarClamp;
+
+impl NodeDefinition for ScalarClamp {
+    fn name(&self) -> &'static str {
+        "ScalarClamp"
+    }
+    fn label(&self) -> &'static str {
+        "Scalar clamp"
+    }
+    fn categories(&self) -> Vec<&'static str> {
+        vec!["Scalar"]
+    }
+    fn build(&self, node: &mut NodeBuilder) {
+        node.input_scalar("value", 0.0);
+        node.input_scalar("min", 0.0);
+        node.input_scalar("max", 1.0);
+        node.output_scalar("out");
+    }
+    fn evaluate(&self, evaluator: &mut Evaluator) -> Result<N3DValueType, NodeError> {
+        let value = evaluator.input_scalar("value")?;
+        let min = evaluator.input_scalar("min")?;
+        let max = evaluator.input_scalar("max")?;
+        evaluator.output_scalar("out", Expr::clamp(value, min, max))
+    }
+}
+
+pub struct ScalarMix;
+
+impl NodeDefinition for ScalarMix {
+    fn name(&self) -> &'static str {
+        "ScalarMix"
+    }
+    fn label(&self) -> &'static str {
+        "Scalar mix"
+    }
+    fn categories(&self) -> Vec<&'static str> {
+        vec!["Scalar"]
+    }
+    fn build(&self, node: &mut NodeBuilder) {
+        node.input_scalar("A", 0.0);
+        node.input_scalar("B", 1.0);
+        node.input_scalar("t", 0.5);
+        node.output_scalar("out");
+    }
+    fn evaluate(&self, evaluator: &mut Evaluator) -> Result<N3DValueType, NodeError> {
+        let a = evaluator.input_scalar("A")?;
+        let b = evaluator.input_scalar("B")?;
+        let t = evaluator.input_scalar("t")?;
+        evaluator.output_scalar("out", Expr::mix(a, b, t))
+    }
+}
+
+pub struct ScalarRemap;
+
+impl NodeDefinition for ScalarRemap {
+    fn name(&self) -> &'static str {
+        "ScalarRemap"
+    }
+    fn label(&self) -> &'static str {
+        "Scalar remap"
+    }
+    fn categories(&self) -> Vec<&'static str> {
+        vec!["Scalar"]
+    }
+    fn build(&self, node: &mut NodeBuilder) {
+        node.input_scalar("value", 0.0);
+        node.input_scalar("from min", 0.0);
+        node.input_scalar("from max", 1.0);
+        node.input_scalar("to min", 0.0);
+        node.input_scalar("to max", 1.0);
+        node.output_scalar("out");
+    }
+    fn evaluate(&self, evaluator: &mut Evaluator) -> Result<N3DValueType, NodeError> {
+        let value = evaluator.input_scalar("value")?;
+        let from_min = evaluator.input_scalar("from min")?;
+        let from_max = evaluator.input_scalar("from max")?;
+        let to_min = evaluator.input_scalar("to min")?;
+        let to_max = evaluator.input_scalar("to max")?;
+        evaluator.output_scalar("out", value.remap(from_min, from_max, to_min, to_max))
+    }
+}
+
+pub struct ScalarAbs;
+
+impl NodeDefinition for ScalarAbs {
+    fn name(&self) -> &'static str {
+        "ScalarAbs"
+    }
+    fn label(&self) -> &'static str {
+        "Scalar abs"
+    }
+    fn categories(&self) -> Vec<&'static str> {
+        vec!["Scalar"]
+    }
+    fn build(&self, node: &mut NodeBuilder) {
+        node.input_scalar("value", 0.0);
+        node.output_scalar("out");
+    }
+    fn evaluate(&self, evaluator: &mut Evaluator) -> Result<N3DValueType, NodeError> {
+        let value = evaluator.input_scalar("value")?;
+        evaluator.output_scalar("out", Expr::unary(UnaryOp::Abs, value))
+    }
+}
+
+pub struct ScalarPow;
+
+impl NodeDefinition for ScalarPow {
+    fn name(&self) -> &'static str {
+        "ScalarPow"
+    }
+    fn label(&self) -> &'static str {
+        "Scalar power"
+    }
+    fn categories(&self) -> Vec<&'static str> {
+        vec!["Scalar"]
+    }
+    fn build(&self, node: &mut NodeBuilder) {
+        node.input_scalar("base", 0.0);
+        node.input_scalar("exponent", 2.0);
+        node.output_scalar("out");
+    }
+    fn evaluate(&self, evaluator: &mut Evaluator) -> Result<N3DValueType, NodeError> {
+        let base = evaluator.input_scalar("base")?;
+        let exponent = evaluator.input_scalar("exponent")?;
+        evaluator.output_scalar("out", base.pow(exponent))
+    }
+}
+
+pub struct ScalarSin;
+
+impl NodeDefinition for ScalarSin {
+    fn name(&self) -> &'static str {
+        "ScalarSin"
+    }
+    fn label(&self) -> &'static str {
+        "Scalar sin"
+    }
+    fn categories(&self) -> Vec<&'static str> {
+        vec!["Scalar"]
+    }
+    fn build(&self, node: &mut NodeBuilder) {
+        node.input_scalar("value", 0.0);
+        node.output_scalar("out");
+    }
+    fn evaluate(&self, evaluator: &mut Evaluator) -> Result<N3DValueType, NodeError> {
+        let value = evaluator.input_scalar("value")?;
+        evaluator.output_scalar("out", Expr::unary(UnaryOp::Sin, value))
+    }
+}
+
+pub struct ScalarCos;
+
+impl NodeDefinition for ScalarCos {
+    fn name(&self) -> &'static str {
+        "ScalarCos"
+    }
+    fn label(&self) -> &'static str {
+        "Scalar cos"
+    }
+    fn categories(&self) -> Vec<&'static str> {
+        vec!["Scalar"]
+    }
+    fn build(&self, node: &mut NodeBuilder) {
+        node.input_scalar("value", 0.0);
+        node.output_scalar("out");
+    }
+    fn evaluate(&self, evaluator: &mut Evaluator) -> Result<N3DValueType, NodeError> {
+        let value = evaluator.input_scalar("value")?;
+        evaluator.output_scalar("out", Expr::unary(UnaryOp::Cos, value))
+    }
+}
+
+pub struct ScalarFloor;
+
+impl NodeDefinition for ScalarFloor {
+    fn name(&self) -> &'static str {
+        "ScalarFloor"
+    }
+    fn label(&self) -> &'static str {
+        "Scalar floor"
+    }
+    fn categories(&self) -> Vec<&'static str> {
+        vec!["Scalar"]
+    }
+    fn build(&self, node: &mut NodeBuilder) {
+        node.input_scalar("value", 0.0);
+        node.output_scalar("out");
+    }
+    fn evaluate(&self, evaluator: &mut Evaluator) -> Result<N3DValueType, NodeError> {
+        let value = evaluator.input_scalar("value")?;
+        evaluator.output_scalar("out", Expr::unary(UnaryOp::Floor, value))
+    }
+}
+
+pub struct ScalarFract;
+
+impl NodeDefinition for ScalarFract {
+    fn name(&self) -> &'static str {
+        "ScalarFract"
+    }
+    fn label(&self) -> &'static str {
+        "Scalar fract"
+    }
+    fn categories(&self) -> Vec<&'static str> {
+        vec!["Scalar"]
+    }
+    fn build(&self, node: &mut NodeBuilder) {
+        node.input_scalar("value", 0.0);
+        node.output_scalar("out");
+    }
+    fn evaluate(&self, evaluator: &mut Evaluator) -> Result<N3DValueType, NodeError> {
+        let value = evaluator.input_scalar("value")?;
+        evaluator.output_scalar("out", Expr::unary(UnaryOp::Fract, value))
+    }
+}
+
+pub struct Time;
+
+impl NodeDefinition for Time {
+    fn name(&self) -> &'static str {
+        "Time"
+    }
+    fn label(&self) -> &'static str {
+        "Time"
+    }
+    fn categories(&self) -> Vec<&'static str> {
+        vec!["Scalar"]
+    }
+    fn build(&self, node: &mut NodeBuilder) {
+        node.output_scalar("out");
+    }
+    fn evaluate(&self, evaluator: &mut Evaluator) -> Result<N3DValueType, NodeError> {
+        evaluator.output_scalar("out", Expr::Time)
+    }
+}
+
+pub struct Vec2Add;
+
+impl NodeDefinition for Vec2Add {
+    fn name(&self) -> &'static str {
+        "Vec2Add"
+    }
+    fn label(&self) -> &'static str {
+        "Vec2 Add"
+    }
+    fn categories(&self) -> Vec<&'static str> {
+        vec!["Vec2"]
+    }
+    fn build(&self, node: &mut NodeBuilder) {
+        node.input_vec2("v1", Vector2::zeros());
+        node.input_vec2("v2", Vector2::zeros());
+        node.output_vec2("out");
+    }
+    fn evaluate(&self, evaluator: &mut Evaluator) -> Result<N3DValueType, NodeError> {
+        let v1 = evaluator.input_vec2("v1")?;
+        let v2 = evaluator.input_vec2("v2")?;
+        evaluator.output_vec2("out", v1 + v2)
+    }
+}
+
+pub struct Vec2Subtract;
+
+impl NodeDefinition for Vec2Subtract {
+    fn name(&self) -> &'static str {
+        "Vec2Subtract"
+    }
+    fn label(&self) -> &'static str {
+        "Vec2 Subtract"
+    }
+    fn categories(&self) -> Vec<&'static str> {
+        vec!["Vec2"]
+    }
+    fn build(&self, node: &mut NodeBuilder) {
+        node.input_vec2("v1", Vector2::zeros());
+        node.input_vec2("v2", Vector2::zeros());
+        node.output_vec2("out");
+    }
+    fn evaluate(&self, evaluator: &mut Evaluator) -> Result<N3DValueType, NodeError> {
+        let v1 = evaluator.input_vec2("v1")?;
+        let v2 = evaluator.input_vec2("v2")?;
+        evaluator.output_vec2("out", v1 - v2)
+    }
+}
+
+pub struct Vec2ScalarMul;
+
+impl NodeDefinition for Vec2ScalarMul {
+    fn name(&self) -> &'static str {
+        "Vec2ScalarMul"
+    }
+    fn label(&self) -> &'static str {
+        "Vec2 Scalar Multiply"
+    }
+    fn categories(&self) -> Vec<&'static str> {
+        vec!["Vec2", "Scalar"]
+    }
+    fn build(&self, node: &mut NodeBuilder) {
+        node.input_scalar("scalar", 0.0);
+        node.input_vec2("vector", Vector2::zeros());
+        node.output_vec2("out");
+    }
+    fn evaluate(&self, evaluator: &mut Evaluator) -> Result<N3DValueType, NodeError> {
+        let scalar = evaluator.input_scalar("scalar")?;
+        let vector = evaluator.input_vec2("vector")?;
+        evaluator.output_vec2("out", vector * scalar)
+    }
+}
+
+pub struct Vec2Length;
+
+impl NodeDefinition for Vec2Length {
+    fn name(&self) -> &'static str {
+        "Vec2Length"
+    }
+    fn label(&self) -> &'static str {
+        "Vec2 Length"
+    }
+    fn categories(&self) -> Vec<&'static str> {
+        vec!["Vec2"]
+    }
+    fn build(&self, node: &mut NodeBuilder) {
+        node.input_vec2("v", Vector2::zeros());
+        node.output_scalar("out");
+    }
+    fn evaluate(&self, evaluator: &mut Evaluator) -> Result<N3DValueType, NodeError> {
+        let v = evaluator.input_vec2("v")?;
+        evaluator.output_scalar("out", v.length())
+    }
+}
+
+pub struct Vec2Normalize;
+
+impl NodeDefinition for Vec2Normalize {
+    fn name(&self) -> &'static str {
+        "Vec2Normalize"
+    }
+    fn label(&self) -> &'static str {
+        "Vec2 Normalize"
+    }
+    fn categories(&self) -> Vec<&'static str> {
+        vec!["Vec2"]
+    }
+    fn build(&self, node: &mut NodeBuilder) {
+        node.input_vec2("v", Vector2::zeros());
+        node.output_vec2("out");
+    }
+    fn evaluate(&self, evaluator: &mut Evaluator) -> Result<N3DValueType, NodeError> {
+        let v = evaluator.input_vec2("v")?;
+        evaluator.output_vec2("out", v.normalize())
+    }
+}
+
+pub struct NewVec3;
+
+impl NodeDefinition for NewVec3 {
+    fn name(&self) -> &'static str {
+        "NewVec3"
+    }
+    fn label(&self) -> &'static str {
+        "New Vec3"
+    }
+    fn categories(&self) -> Vec<&'static str> {
+        vec!["Vec3"]
+    }
+    fn build(&self, node: &mut NodeBuilder) {
+        node.input_scalar("x", 0.0);
+        node.input_scalar("y", 0.0);
+        node.input_scalar("z", 0.0);
+        node.output_vec3("out");
+    }
+    fn evaluate(&self, evaluator: &mut Evaluator) -> Result<N3DValueType, NodeError> {
+        let x = evaluator.input_scalar("x")?;
+        let y = evaluator.input_scalar("y")?;
+        let z = evaluator.input_scalar("z")?;
+        evaluator.output_vec3("out", Expr::vec3(x, y, z))
+        //evaluator.output_vec3("out", nalgebra::Vector3::new(1.0, 2.0, 3.0))
+    }
+}
+
+pub struct Vec3Add;
+
+impl NodeDefinition for Vec3Add {
+    fn name(&self) -> &'static str {
+        "Vec3Add"
+    }
+    fn label(&self) -> &'static str {
+        "Vec3 Add"
+    }
+    fn categories(&self) -> Vec<&'static str> {
+        vec!["Vec3"]
+    }
+    fn build(&self, node: &mut NodeBuilder) {
+        node.input_vec3("v1", Vector3::zeros());
+        node.input_vec3("v2", Vector3::zeros());
+        node.output_vec3("out");
+    }
+    fn evaluate(&self, evaluator: &mut Evaluator) -> Result<N3DValueType, NodeError> {
+        let v1 = evaluator.input_vec3("v1")?;
+        let v2 = evaluator.input_vec3("v2")?;
+        evaluator.output_vec3("out", v1 + v2)
+    }
+}
+
+pub struct Vec3Sub;
+
+impl NodeDefinition for Vec3Sub {
+    fn name(&self) -> &'static str {
+        "Vec3Sub"
+    }
+    fn label(&self) -> &'static str {
+        "Vec3 Sub"
+    }
+    fn categories(&self) -> Vec<&'static str> {
+        vec!["Vec3"]
+    }
+    fn build(&self, node: &mut NodeBuilder) {
+        node.input_vec3("v1", Vector3::zeros());
+        node.input_vec3("v2", Vector3::zeros());
+        node.output_vec3("out");
+    }
+    fn evaluate(&self, evaluator: &mut Evaluator) -> Result<N3DValueType, NodeError> {
+        let v1 = evaluator.input_vec3("v1")?;
+        let v2 = evaluator.input_vec3("v2")?;
+        evaluator.output_vec3("out", v1 - v2)
+    }
+}
+
+pub struct Vec3Dot;
+
+impl NodeDefinition for Vec3Dot {
+    fn name(&self) -> &'static str {
+        "Vec3Dot"
+    }
+    fn label(&self) -> &'static str {
+        "Vec3 Dot"
+    }
+    fn categories(&self) -> Vec<&'static str> {
+        vec!["Vec3"]
+    }
+    fn build(&self, node: &mut NodeBuilder) {
+        node.input_vec3("v1", Vector3::zeros());
+        node.input_vec3("v2", Vector3::zeros());
+        node.output_scalar("out");
+    }
+    fn evaluate(&self, evaluator: &mut Evaluator) -> Result<N3DValueType, NodeError> {
+        let v1 = evaluator.input_vec3("v1")?;
+        let v2 = evaluator.input_vec3("v2")?;
+        evaluator.output_scalar("out", v1.dot(v2))
+    }
+}
+
+pub struct Vec3Cross;
+
+impl NodeDefinition for Vec3Cross {
+    fn name(&self) -> &'static str {
+        "Vec3Cross"
+    }
+    fn label(&self) -> &'static str {
+        "Vec3 Cross"
+    }
+    fn categories(&self) -> Vec<&'static str> {
+        vec!["Vec3"]
+    }
+    fn build(&self, node: &mut NodeBuilder) {
+        node.input_vec3("v1", Vector3::zeros());
+        node.input_vec3("v2", Vector3::zeros());
+        node.output_vec3("out");
+    }
+    fn evaluate(&self, evaluator: &mut Evaluator) -> Result<N3DValueType, NodeError> {
+        let v1 = evaluator.input_vec3("v1")?;
+        let v2 = evaluator.input_vec3("v2")?;
+        evaluator.output_vec3("out", v1.cross(v2))
+    }
+}
+
+pub struct Vec3Scale;
+
+impl NodeDefinition for Vec3Scale {
+    fn name(&self) -> &'static str {
+        "Vec3Scale"
+    }
+    fn label(&self) -> &'static str {
+        "Vec3 Scale"
+    }
+    fn categories(&self) -> Vec<&'static str> {
+        vec!["Vec3"]
+    }
+    fn build(&self, node: &mut NodeBuilder) {
+        node.input_scalar("scalar", 1.0);
+        node.input_vec3("vector", Vector3::zeros());
+        node.output_vec3("out");
+    }
+    fn evaluate(&self, evaluator: &mut Evaluator) -> Result<N3DValueType, NodeError> {
+        let scalar = evaluator.input_scalar("scalar")?;
+        let vector = evaluator.input_vec3("vector")?;
+        evaluator.output_vec3("out", vector * scalar)
+    }
+}
+
+pub struct Vec3Length;
+
+impl NodeDefinition for Vec3Length {
+    fn name(&self) -> &'static str {
+        "Vec3Length"
+    }
+    fn label(&self) -> &'static str {
+        "Vec3 Length"
+    }
+    fn categories(&self) -> Vec<&'static str> {
+        vec!["Vec3"]
+    }
+    fn build(&self, node: &mut NodeBuilder) {
+        node.input_vec3("v", Vector3::zeros());
+        node.output_scalar("out");
+    }
+    fn evaluate(&self, evaluator: &mut Evaluator) -> Result<N3DValueType, NodeError> {
+        let v = evaluator.input_vec3("v")?;
+        evaluator.output_scalar("out", v.length())
+    }
+}
+
+pub struct Vec3Normalize;
+
+impl NodeDefinition for Vec3Normalize {
+    fn name(&self) -> &'static str {
+        "Vec3Normalize"
+    }
+    fn label(&self) -> &'static str {
+        "Vec3 Normalize"
+    }
+    fn categories(&self) -> Vec<&'static str> {
+        vec!["Vec3"]
+    }
+    fn build(&self, node: &mut NodeBuilder) {
+        node.input_vec3("v", Vector3::zeros());
+        node.output_vec3("out");
+    }
+    fn evaluate(&self, evaluator: &mut Evaluator) -> Result<N3DValueType, NodeError> {
+        let v = evaluator.input_vec3("v")?;
+        evaluator.output_vec3("out", v.normalize())
+    }
+}
+
+pub struct Vec3Mix;
+
+impl NodeDefinition for Vec3Mix {
+    fn name(&self) -> &'static str {
+        "Vec3Mix"
+    }
+    fn label(&self) -> &'static str {
+        "Vec3 Mix"
+    }
+    fn categories(&self) -> Vec<&'static str> {
+        vec!["Vec3"]
+    }
+    fn build(&self, node: &mut NodeBuilder) {
+        node.input_vec3("v1", Vector3::zeros());
+        node.input_vec3("v2", Vector3::zeros());
+        node.input_scalar("t", 0.5);
+        node.output_vec3("out");
+    }
+    fn evaluate(&self, evaluator: &mut Evaluator) -> Result<N3DValueType, NodeError> {
+        let v1 = evaluator.input_vec3("v1")?;
+        let v2 = evaluator.input_vec3("v2")?;
+        let t = evaluator.input_scalar("t")?;
+        evaluator.output_vec3("out", Expr::mix(v1, v2, t))
+    }
+}
+
+pub struct Expression;
+
+impl NodeDefinition for Expression {
+    fn name(&self) -> &'static str {
+        "Expression"
+    }
+    fn label(&self) -> &'static str {
+        "Expression"
+    }
+    fn categories(&self) -> Vec<&'static str> {
+        vec!["Scalar", "Vec2", "Vec3"]
+    }
+    fn build(&self, node: &mut NodeBuilder) {
+        node.input(
+            "formula",
+            N3DDataType::Text,
+            N3DValueType::Text {
+                value: "a + b".to_string(),
+            },
+            InputParamKind::ConstantOnly,
+        );
+        // The variable inputs and the output follow the formula, they are
+        // added by `update_ports`
+    }
+    fn evaluate(&self, evaluator: &mut Evaluator) -> Result<N3DValueType, NodeError> {
+        let text = evaluator.input_text("formula")?;
+        let formula = evaluator.locate(Formula::parse(&text).map_err(EvalError::from))?;
+        let mut variables = HashMap::new();
+        for name in formula.variables() {
+            let value = evaluator.input_scalar(&name)?;
+            variables.insert(name, value);
+        }
+        let value = evaluator.locate(formula.to_expr(&variables).map_err(EvalError::from))?;
+        evaluator.output_expr("out", value)
+    }
+    fn update_ports(&self, graph: &mut MyGraph, node_id: NodeId) {
+        sync_expression_ports(graph, node_id);
+    }
+    fn check_input(&self, name: &str, value: &N3DValueType) -> Option<String> {
+        match value {
+            N3DValueType::Text { value } if name == "formula" => {
+                check_formula(value).err().map(|err| err.to_string())
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Parses and type checks a formula with every variable as a scalar, which
+/// is how the inputs of an Expression node are created
+fn check_formula(text: &str) -> Result<(Formula, ValueKind), FormulaError> {
+    let formula = Formula::parse(text)?;
+    let variables = formula
+        .variables()
+        .into_iter()
+        .map(|name| (name, Expr::from(0.0)))
+        .collect();
+    let kind = formula.to_expr(&variables)?.kind();
+    Ok((formula, kind))
+}
+
+/// Keeps the inputs of an Expression node in line with the variables of its
+/// formula and the type of its output with the formula's result. Ports are
+/// left alone while the formula does not parse, so connections survive typos.
+fn sync_expression_ports(graph: &mut MyGraph, node_id: NodeId) {
+    let formula = match graph[node_id].get_input("formula") {
+        Ok(input) => match &graph[input].value {
+            N3DValueType::Text { value } => value.clone(),
+            _ => return,
+        },
+        Err(_) => return,
+    };
+    let Ok((formula, kind)) = check_formula(&formula) else {
+        return;
+    };
+    let variables = formula.variables();
+    let stale: Vec<InputId> = graph[node_id]
+        .inputs
+        .iter()
+        .filter(|(name, _)| name != "formula" && !variables.contains(name))
+        .map(|(_, input)| *input)
+        .collect();
+    for input in stale {
+        graph.remove_input_param(input);
+    }
+    for name in variables {
+        if graph[node_id].get_input(&name).is_err() {
+            graph.add_input_param(
+                node_id,
+                name,
+                N3DDataType::Scalar,
+                N3DValueType::Scalar { value: 0.0 },
+                InputParamKind::ConnectionOrConstant,
+                true,
+            );
+        }
+    }
+    let data_type = match kind {
+        ValueKind::Scalar => N3DDataType::Scalar,
+        ValueKind::Vec2 => N3DDataType::Vec2,
+        ValueKind::Vec3 => N3DDataType::Vec3,
+    };
+    if let Ok(output) = graph[node_id].get_output("out") {
+        if graph[output].typ == data_type {
+            return;
+        }
+        graph.remove_output_param(output);
+    }
+    graph.add_output_param(node_id, "out".to_string(), data_type);
+}
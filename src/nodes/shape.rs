@@ -0,0 +1,328 @@
+//! 2D profiles and the volumes made by extruding or revolving them
+use nalgebra::{Vector2, Vector3};
+
+use super::{NodeBuilder, NodeDefinition};
+use crate::{
+    node_graph::{Evaluator, N3DValueType, NodeError},
+    sdf::ir::{SDFVolume, SDF2D},
+};
+
+pub struct SDF2DCircle;
+
+impl NodeDefinition for SDF2DCircle {
+    fn name(&self) -> &'static str {
+        "SDF2DCircle"
+    }
+    fn label(&self) -> &'static str {
+        "SDF2D Circle"
+    }
+    fn categories(&self) -> Vec<&'static str> {
+        vec!["SDF 2D"]
+    }
+    fn build(&self, node: &mut NodeBuilder) {
+        node.input_vec2("center", Vector2::zeros());
+        node.input_scalar("radius", 0.5);
+        node.output_sdf_2d("out");
+    }
+    fn evaluate(&self, evaluator: &mut Evaluator) -> Result<N3DValueType, NodeError> {
+        let center = evaluator.input_vec2("center")?;
+        let radius = evaluator.input_scalar("radius")?;
+        evaluator.output_sdf_2d("out", SDF2D::Circle { center, radius })
+    }
+}
+
+pub struct SDF2DRectangle;
+
+impl NodeDefinition for SDF2DRectangle {
+    fn name(&self) -> &'static str {
+        "SDF2DRectangle"
+    }
+    fn label(&self) -> &'static str {
+        "SDF2D Rectangle"
+    }
+    fn categories(&self) -> Vec<&'static str> {
+        vec!["SDF 2D"]
+    }
+    fn build(&self, node: &mut NodeBuilder) {
+        node.input_vec2("center", Vector2::zeros());
+        node.input_vec2("size", Vector2::new(0.5, 0.5));
+        node.output_sdf_2d("out");
+    }
+    fn evaluate(&self, evaluator: &mut Evaluator) -> Result<N3DValueType, NodeError> {
+        let center = evaluator.input_vec2("center")?;
+        let size = evaluator.input_vec2("size")?;
+        evaluator.output_sdf_2d("out", SDF2D::Rectangle { center, size })
+    }
+}
+
+pub struct SDF2DRoundedRectangle;
+
+impl NodeDefinition for SDF2DRoundedRectangle {
+    fn name(&self) -> &'static str {
+        "SDF2DRoundedRectangle"
+    }
+    fn label(&self) -> &'static str {
+        "SDF2D Rounded Rectangle"
+    }
+    fn categories(&self) -> Vec<&'static str> {
+        vec!["SDF 2D"]
+    }
+    fn build(&self, node: &mut NodeBuilder) {
+        node.input_vec2("center", Vector2::zeros());
+        node.input_vec2("size", Vector2::new(0.5, 0.5));
+        node.input_scalar("radius", 0.1);
+        node.output_sdf_2d("out");
+    }
+    fn evaluate(&self, evaluator: &mut Evaluator) -> Result<N3DValueType, NodeError> {
+        let center = evaluator.input_vec2("center")?;
+        let size = evaluator.input_vec2("size")?;
+        let radius = evaluator.input_scalar("radius")?;
+        evaluator.output_sdf_2d(
+            "out",
+            SDF2D::RoundedRectangle {
+                center,
+                size,
+                radius,
+            },
+        )
+    }
+}
+
+pub struct SDF2DPolygon;
+
+impl NodeDefinition for SDF2DPolygon {
+    fn name(&self) -> &'static str {
+        "SDF2DPolygon"
+    }
+    fn label(&self) -> &'static str {
+        "SDF2D Polygon"
+    }
+    fn categories(&self) -> Vec<&'static str> {
+        vec!["SDF 2D"]
+    }
+    fn build(&self, node: &mut NodeBuilder) {
+        node.input_vec2("center", Vector2::zeros());
+        node.input_scalar("radius", 0.5);
+        node.input_scalar("sides", 6.0);
+        node.output_sdf_2d("out");
+    }
+    fn evaluate(&self, evaluator: &mut Evaluator) -> Result<N3DValueType, NodeError> {
+        let center = evaluator.input_vec2("center")?;
+        let radius = evaluator.input_scalar("radius")?;
+        let sides = evaluator.input_scalar("sides")?;
+        evaluator.output_sdf_2d(
+            "out",
+            SDF2D::Polygon {
+                center,
+                radius,
+                sides,
+            },
+        )
+    }
+}
+
+pub struct SDF2DArc;
+
+impl NodeDefinition for SDF2DArc {
+    fn name(&self) -> &'static str {
+        "SDF2DArc"
+    }
+    fn label(&self) -> &'static str {
+        "SDF2D Arc"
+    }
+    fn categories(&self) -> Vec<&'static str> {
+        vec!["SDF 2D"]
+    }
+    fn build(&self, node: &mut NodeBuilder) {
+        node.input_vec2("center", Vector2::zeros());
+        node.input_scalar("radius", 0.5);
+        node.input_scalar("angle", 180.0);
+        node.input_scalar("thickness", 0.05);
+        node.output_sdf_2d("out");
+    }
+    fn evaluate(&self, evaluator: &mut Evaluator) -> Result<N3DValueType, NodeError> {
+        let center = evaluator.input_vec2("center")?;
+        let radius = evaluator.input_scalar("radius")?;
+        let angle = evaluator.input_scalar("angle")?;
+        let thickness = evaluator.input_scalar("thickness")?;
+        evaluator.output_sdf_2d(
+            "out",
+            SDF2D::Arc {
+                center,
+                radius,
+                angle,
+                thickness,
+            },
+        )
+    }
+}
+
+pub struct SDF2DSegment;
+
+impl NodeDefinition for SDF2DSegment {
+    fn name(&self) -> &'static str {
+        "SDF2DSegment"
+    }
+    fn label(&self) -> &'static str {
+        "SDF2D Segment"
+    }
+    fn categories(&self) -> Vec<&'static str> {
+        vec!["SDF 2D"]
+    }
+    fn build(&self, node: &mut NodeBuilder) {
+        node.input_vec2("a", Vector2::new(-0.5, 0.0));
+        node.input_vec2("b", Vector2::new(0.5, 0.0));
+        node.input_scalar("thickness", 0.05);
+        node.output_sdf_2d("out");
+    }
+    fn evaluate(&self, evaluator: &mut Evaluator) -> Result<N3DValueType, NodeError> {
+        let a = evaluator.input_vec2("a")?;
+        let b = evaluator.input_vec2("b")?;
+        let thickness = evaluator.input_scalar("thickness")?;
+        evaluator.output_sdf_2d("out", SDF2D::Segment { a, b, thickness })
+    }
+}
+
+pub struct SDF2DUnion;
+
+impl NodeDefinition for SDF2DUnion {
+    fn name(&self) -> &'static str {
+        "SDF2DUnion"
+    }
+    fn label(&self) -> &'static str {
+        "SDF2D Union"
+    }
+    fn categories(&self) -> Vec<&'static str> {
+        vec!["SDF 2D"]
+    }
+    fn build(&self, node: &mut NodeBuilder) {
+        node.input_sdf_2d("sdf 1");
+        node.input_sdf_2d("sdf 2");
+        node.output_sdf_2d("out");
+    }
+    fn evaluate(&self, evaluator: &mut Evaluator) -> Result<N3DValueType, NodeError> {
+        let a = Box::new(evaluator.input_sdf_2d("sdf 1")?);
+        let b = Box::new(evaluator.input_sdf_2d("sdf 2")?);
+        evaluator.output_sdf_2d("out", SDF2D::Union { a, b })
+    }
+}
+
+pub struct SDF2DDiff;
+
+impl NodeDefinition for SDF2DDiff {
+    fn name(&self) -> &'static str {
+        "SDF2DDiff"
+    }
+    fn label(&self) -> &'static str {
+        "SDF2D Diff"
+    }
+    fn categories(&self) -> Vec<&'static str> {
+        vec!["SDF 2D"]
+    }
+    fn build(&self, node: &mut NodeBuilder) {
+        node.input_sdf_2d("sdf 1");
+        node.input_sdf_2d("sdf 2");
+        node.output_sdf_2d("out");
+    }
+    fn evaluate(&self, evaluator: &mut Evaluator) -> Result<N3DValueType, NodeError> {
+        let a = Box::new(evaluator.input_sdf_2d("sdf 1")?);
+        let b = Box::new(evaluator.input_sdf_2d("sdf 2")?);
+        evaluator.output_sdf_2d("out", SDF2D::Diff { a, b })
+    }
+}
+
+pub struct SDF2DIntersect;
+
+impl NodeDefinition for SDF2DIntersect {
+    fn name(&self) -> &'static str {
+        "SDF2DIntersect"
+    }
+    fn label(&self) -> &'static str {
+        "SDF2D Intersect"
+    }
+    fn categories(&self) -> Vec<&'static str> {
+        vec!["SDF 2D"]
+    }
+    fn build(&self, node: &mut NodeBuilder) {
+        node.input_sdf_2d("sdf 1");
+        node.input_sdf_2d("sdf 2");
+        node.output_sdf_2d("out");
+    }
+    fn evaluate(&self, evaluator: &mut Evaluator) -> Result<N3DValueType, NodeError> {
+        let a = Box::new(evaluator.input_sdf_2d("sdf 1")?);
+        let b = Box::new(evaluator.input_sdf_2d("sdf 2")?);
+        evaluator.output_sdf_2d("out", SDF2D::Intersect { a, b })
+    }
+}
+
+pub struct SDFExtrude;
+
+impl NodeDefinition for SDFExtrude {
+    fn name(&self) -> &'static str {
+        "SDFExtrude"
+    }
+    fn label(&self) -> &'static str {
+        "SDF Extrude"
+    }
+    fn categories(&self) -> Vec<&'static str> {
+        vec!["SDF", "SDF 2D"]
+    }
+    fn build(&self, node: &mut NodeBuilder) {
+        node.input_sdf_2d("shape");
+        node.input_scalar("height", 1.0);
+        node.input_scalar("rounding", 0.0);
+        node.input_sdf_position("sdf position");
+        node.output_sdf_volume("out");
+    }
+    fn evaluate(&self, evaluator: &mut Evaluator) -> Result<N3DValueType, NodeError> {
+        let shape = evaluator.input_sdf_2d("shape")?;
+        let height = evaluator.input_scalar("height")?;
+        let rounding = evaluator.input_scalar("rounding")?;
+        let position = evaluator.input_sdf_position("sdf position")?;
+        evaluator.output_sdf_volume(
+            "out",
+            SDFVolume::Extrude {
+                position,
+                shape,
+                height,
+                rounding,
+            },
+        )
+    }
+}
+
+pub struct SDFRevolve;
+
+impl NodeDefinition for SDFRevolve {
+    fn name(&self) -> &'static str {
+        "SDFRevolve"
+    }
+    fn label(&self) -> &'static str {
+        "SDF Revolve"
+    }
+    fn categories(&self) -> Vec<&'static str> {
+        vec!["SDF", "SDF 2D"]
+    }
+    fn build(&self, node: &mut NodeBuilder) {
+        node.input_sdf_2d("shape");
+        node.input_vec3("axis", Vector3::new(0.0, 0.0, 1.0));
+        node.input_scalar("offset", 0.0);
+        node.input_sdf_position("sdf position");
+        node.output_sdf_volume("out");
+    }
+    fn evaluate(&self, evaluator: &mut Evaluator) -> Result<N3DValueType, NodeError> {
+        let shape = evaluator.input_sdf_2d("shape")?;
+        let axis = evaluator.input_vec3("axis")?;
+        let offset = evaluator.input_scalar("offset")?;
+        let position = evaluator.input_sdf_position("sdf position")?;
+        evaluator.output_sdf_volume(
+            "out",
+            SDFVolume::Revolve {
+                position,
+                shape,
+                axis,
+                offset,
+            },
+        )
+    }
+}
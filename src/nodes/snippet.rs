@@ -0,0 +1,185 @@
+//! Volumes whose distance function is written by hand in GLSL
+use egui_node_graph::{InputId, InputParamKind, NodeId};
+
+use super::{NodeBuilder, NodeDefinition};
+use crate::{
+    node_graph::{EvalError, Evaluator, MyGraph, N3DDataType, N3DValueType, NodeError},
+    sdf::{
+        expr::ValueKind,
+        ir::SDFVolume,
+        snippet::{self, Snippet, SnippetError, SnippetInput},
+    },
+};
+
+/// Inputs of the node that are not declared by the snippet
+const FIXED_INPUTS: [&str; 3] = ["inputs", "body", "sdf position"];
+
+pub struct GlslSnippet;
+
+impl NodeDefinition for GlslSnippet {
+    fn name(&self) -> &'static str {
+        "GlslSnippet"
+    }
+    fn label(&self) -> &'static str {
+        "GLSL snippet"
+    }
+    fn categories(&self) -> Vec<&'static str> {
+        vec!["SDF"]
+    }
+    fn build(&self, node: &mut NodeBuilder) {
+        node.input(
+            "inputs",
+            N3DDataType::Text,
+            N3DValueType::Text {
+                value: "float radius".to_string(),
+            },
+            InputParamKind::ConstantOnly,
+        );
+        node.input(
+            "body",
+            N3DDataType::Text,
+            N3DValueType::Code {
+                value: "return length(p) - radius;".to_string(),
+            },
+            InputParamKind::ConstantOnly,
+        );
+        node.input_sdf_position("sdf position");
+        node.output_sdf_volume("out");
+        // The declared inputs are added by `update_ports`
+    }
+    fn evaluate(&self, evaluator: &mut Evaluator) -> Result<N3DValueType, NodeError> {
+        let inputs = evaluator.input_text("inputs")?;
+        let body = evaluator.input_text("body")?;
+        let snippet = evaluator.locate(parse(&inputs, &body).map_err(EvalError::from))?;
+        let position = evaluator.input_sdf_position("sdf position")?;
+        let mut args = Vec::new();
+        for input in &snippet.inputs {
+            args.push(evaluator.input_expr(&input.name, input.kind)?);
+        }
+        evaluator.output_sdf_volume(
+            "out",
+            SDFVolume::Glsl {
+                function: snippet.function(),
+                position,
+                args,
+            },
+        )
+    }
+    fn update_ports(&self, graph: &mut MyGraph, node_id: NodeId) {
+        sync_snippet_ports(graph, node_id);
+    }
+    fn check_input(&self, name: &str, value: &N3DValueType) -> Option<String> {
+        match (name, value) {
+            ("inputs", N3DValueType::Text { value }) => {
+                parse_inputs(value).err().map(|err| err.to_string())
+            }
+            ("body", N3DValueType::Code { value }) => {
+                Snippet::parse("", value).err().map(|err| err.to_string())
+            }
+            _ => None,
+        }
+    }
+    fn shader_errors(
+        &self,
+        graph: &MyGraph,
+        node_id: NodeId,
+        fragment: &str,
+        log: &str,
+    ) -> Vec<String> {
+        let Some(snippet) = snippet_of(graph, node_id) else {
+            return Vec::new();
+        };
+        snippet
+            .errors(fragment, log)
+            .into_iter()
+            .map(|error| match error.line {
+                0 => format!("inputs: {}", error.message),
+                line => format!("line {}: {}", line, error.message),
+            })
+            .collect()
+    }
+}
+
+/// Input declarations that can also be the names of ports of the node
+fn parse_inputs(text: &str) -> Result<Vec<SnippetInput>, SnippetError> {
+    let inputs = snippet::parse_inputs(text)?;
+    match inputs
+        .iter()
+        .find(|input| FIXED_INPUTS.contains(&input.name.as_str()))
+    {
+        Some(input) => Err(SnippetError::Reserved(input.name.clone())),
+        None => Ok(inputs),
+    }
+}
+
+fn parse(inputs: &str, body: &str) -> Result<Snippet, SnippetError> {
+    parse_inputs(inputs)?;
+    Snippet::parse(inputs, body)
+}
+
+fn text_input(graph: &MyGraph, node_id: NodeId, name: &str) -> Option<String> {
+    let input = graph[node_id].get_input(name).ok()?;
+    match &graph[input].value {
+        N3DValueType::Text { value } | N3DValueType::Code { value } => Some(value.clone()),
+        _ => None,
+    }
+}
+
+/// Snippet of a node, if it is valid
+fn snippet_of(graph: &MyGraph, node_id: NodeId) -> Option<Snippet> {
+    let inputs = text_input(graph, node_id, "inputs")?;
+    let body = text_input(graph, node_id, "body")?;
+    parse(&inputs, &body).ok()
+}
+
+/// Keeps one input per declaration of a snippet node, of the declared type.
+/// Like for expressions, ports are left alone while the declarations do not
+/// parse.
+fn sync_snippet_ports(graph: &mut MyGraph, node_id: NodeId) {
+    let Some(inputs) = text_input(graph, node_id, "inputs") else {
+        return;
+    };
+    let Ok(inputs) = parse_inputs(&inputs) else {
+        return;
+    };
+    let data_type = |kind: ValueKind| match kind {
+        ValueKind::Scalar => N3DDataType::Scalar,
+        ValueKind::Vec2 => N3DDataType::Vec2,
+        ValueKind::Vec3 => N3DDataType::Vec3,
+    };
+    let stale: Vec<InputId> = graph[node_id]
+        .inputs
+        .iter()
+        .filter(|(name, input)| {
+            !FIXED_INPUTS.contains(&name.as_str())
+                && !inputs.iter().any(|declared| {
+                    declared.name == *name && data_type(declared.kind) == graph[*input].typ
+                })
+        })
+        .map(|(_, input)| *input)
+        .collect();
+    for input in stale {
+        graph.remove_input_param(input);
+    }
+    for input in inputs {
+        if graph[node_id].get_input(&input.name).is_err() {
+            let value = match input.kind {
+                ValueKind::Scalar => N3DValueType::Scalar { value: 1.0 },
+                ValueKind::Vec2 => N3DValueType::Vec2 {
+                    value: nalgebra::Vector2::zeros(),
+                },
+                ValueKind::Vec3 => N3DValueType::Vec3 {
+                    value: nalgebra::Vector3::zeros(),
+                },
+            };
+            graph.add_input_param(
+                node_id,
+                input.name,
+                data_type(input.kind),
+                value,
+                InputParamKind::ConnectionOrConstant,
+                true,
+            );
+        }
+    }
+}
@@ -0,0 +1,325 @@
+//! Transforms and deformations of the space volumes are placed in
+use nalgebra::Vector3;
+
+use super::{NodeBuilder, NodeDefinition};
+use crate::{
+    node_graph::{Evaluator, N3DValueType, NodeError},
+    sdf::ir::SDFPosition,
+};
+
+pub struct NewSDFPosition;
+
+impl NodeDefinition for NewSDFPosition {
+    fn name(&self) -> &'static str {
+        "SDFPosition"
+    }
+    fn label(&self) -> &'static str {
+        "SDF Position"
+    }
+    fn categories(&self) -> Vec<&'static str> {
+        vec!["SDF"]
+    }
+    fn build(&self, node: &mut NodeBuilder) {
+        node.output_sdf_position("out");
+    }
+    fn evaluate(&self, evaluator: &mut Evaluator) -> Result<N3DValueType, NodeError> {
+        evaluator.output_sdf_position("out", SDFPosition::Origin)
+    }
+}
+
+pub struct SDFTranslate;
+
+impl NodeDefinition for SDFTranslate {
+    fn name(&self) -> &'static str {
+        "SDFTranslate"
+    }
+    fn label(&self) -> &'static str {
+        "SDF Translate"
+    }
+    fn categories(&self) -> Vec<&'static str> {
+        vec!["SDF"]
+    }
+    fn build(&self, node: &mut NodeBuilder) {
+        node.input_vec3("translation", Vector3::zeros());
+        node.input_sdf_position("sdf position");
+        node.output_sdf_position("out");
+    }
+    fn evaluate(&self, evaluator: &mut Evaluator) -> Result<N3DValueType, NodeError> {
+        let translation = evaluator.input_vec3("translation")?;
+        let position = Box::new(evaluator.input_sdf_position("sdf position")?);
+        evaluator.output_sdf_position(
+            "out",
+            SDFPosition::Translate {
+                position,
+                translation,
+            },
+        )
+    }
+}
+
+pub struct SDFRotate;
+
+impl NodeDefinition for SDFRotate {
+    fn name(&self) -> &'static str {
+        "SDFRotate"
+    }
+    fn label(&self) -> &'static str {
+        "SDF Rotate"
+    }
+    fn categories(&self) -> Vec<&'static str> {
+        vec!["SDF"]
+    }
+    fn build(&self, node: &mut NodeBuilder) {
+        node.input_vec3("rotation", Vector3::zeros());
+        node.input_sdf_position("sdf position");
+        node.output_sdf_position("out");
+    }
+    fn evaluate(&self, evaluator: &mut Evaluator) -> Result<N3DValueType, NodeError> {
+        let rotation = evaluator.input_vec3("rotation")?;
+        let position = Box::new(evaluator.input_sdf_position("sdf position")?);
+        evaluator.output_sdf_position("out", SDFPosition::Rotate { position, rotation })
+    }
+}
+
+pub struct SDFScale;
+
+impl NodeDefinition for SDFScale {
+    fn name(&self) -> &'static str {
+        "SDFScale"
+    }
+    fn label(&self) -> &'static str {
+        "SDF Scale"
+    }
+    fn categories(&self) -> Vec<&'static str> {
+        vec!["SDF"]
+    }
+    fn build(&self, node: &mut NodeBuilder) {
+        node.input_vec3("scale", Vector3::zeros());
+        node.input_sdf_position("sdf position");
+        node.output_sdf_position("out");
+    }
+    fn evaluate(&self, evaluator: &mut Evaluator) -> Result<N3DValueType, NodeError> {
+        let scale = evaluator.input_vec3("scale")?;
+        let position = Box::new(evaluator.input_sdf_position("sdf position")?);
+        evaluator.output_sdf_position("out", SDFPosition::Scale { position, scale })
+    }
+}
+
+pub struct SDFRepeat;
+
+impl NodeDefinition for SDFRepeat {
+    fn name(&self) -> &'static str {
+        "SDFRepeat"
+    }
+    fn label(&self) -> &'static str {
+        "SDF Repeat"
+    }
+    fn categories(&self) -> Vec<&'static str> {
+        vec!["SDF"]
+    }
+    fn build(&self, node: &mut NodeBuilder) {
+        node.input_vec3("spacing", Vector3::new(2.0, 2.0, 0.0));
+        node.input_sdf_position("sdf position");
+        node.output_sdf_position("out");
+    }
+    fn evaluate(&self, evaluator: &mut Evaluator) -> Result<N3DValueType, NodeError> {
+        let spacing = evaluator.input_vec3("spacing")?;
+        let position = Box::new(evaluator.input_sdf_position("sdf position")?);
+        evaluator.output_sdf_position("out", SDFPosition::Repeat { position, spacing })
+    }
+}
+
+pub struct SDFRepeatLimited;
+
+impl NodeDefinition for SDFRepeatLimited {
+    fn name(&self) -> &'static str {
+        "SDFRepeatLimited"
+    }
+    fn label(&self) -> &'static str {
+        "SDF Repeat Limited"
+    }
+    fn categories(&self) -> Vec<&'static str> {
+        vec!["SDF"]
+    }
+    fn build(&self, node: &mut NodeBuilder) {
+        node.input_vec3("spacing", Vector3::new(2.0, 0.0, 0.0));
+        node.input_vec3("count", Vector3::new(3.0, 1.0, 1.0));
+        node.input_sdf_position("sdf position");
+        node.output_sdf_position("out");
+    }
+    fn evaluate(&self, evaluator: &mut Evaluator) -> Result<N3DValueType, NodeError> {
+        let spacing = evaluator.input_vec3("spacing")?;
+        let count = evaluator.input_vec3("count")?;
+        let position = Box::new(evaluator.input_sdf_position("sdf position")?);
+        evaluator.output_sdf_position(
+            "out",
+            SDFPosition::RepeatLimited {
+                position,
+                spacing,
+                count,
+            },
+        )
+    }
+}
+
+pub struct SDFMirror;
+
+impl NodeDefinition for SDFMirror {
+    fn name(&self) -> &'static str {
+        "SDFMirror"
+    }
+    fn label(&self) -> &'static str {
+        "SDF Mirror"
+    }
+    fn categories(&self) -> Vec<&'static str> {
+        vec!["SDF"]
+    }
+    fn build(&self, node: &mut NodeBuilder) {
+        node.input_vec3("normal", Vector3::new(1.0, 0.0, 0.0));
+        node.input_scalar("offset", 0.0);
+        node.input_sdf_position("sdf position");
+        node.output_sdf_position("out");
+    }
+    fn evaluate(&self, evaluator: &mut Evaluator) -> Result<N3DValueType, NodeError> {
+        let normal = evaluator.input_vec3("normal")?;
+        let offset = evaluator.input_scalar("offset")?;
+        let position = Box::new(evaluator.input_sdf_position("sdf position")?);
+        evaluator.output_sdf_position(
+            "out",
+            SDFPosition::Mirror {
+                position,
+                normal,
+                offset,
+            },
+        )
+    }
+}
+
+pub struct SDFRepeatPolar;
+
+impl NodeDefinition for SDFRepeatPolar {
+    fn name(&self) -> &'static str {
+        "SDFRepeatPolar"
+    }
+    fn label(&self) -> &'static str {
+        "SDF Polar Repeat"
+    }
+    fn categories(&self) -> Vec<&'static str> {
+        vec!["SDF"]
+    }
+    fn build(&self, node: &mut NodeBuilder) {
+        node.input_vec3("axis", Vector3::new(0.0, 0.0, 1.0));
+        node.input_scalar("count", 6.0);
+        node.input_sdf_position("sdf position");
+        node.output_sdf_position("out");
+    }
+    fn evaluate(&self, evaluator: &mut Evaluator) -> Result<N3DValueType, NodeError> {
+        let axis = evaluator.input_vec3("axis")?;
+        let count = evaluator.input_scalar("count")?;
+        let position = Box::new(evaluator.input_sdf_position("sdf position")?);
+        evaluator.output_sdf_position(
+            "out",
+            SDFPosition::RepeatPolar {
+                position,
+                axis,
+                count,
+            },
+        )
+    }
+}
+
+pub struct SDFTwist;
+
+impl NodeDefinition for SDFTwist {
+    fn name(&self) -> &'static str {
+        "SDFTwist"
+    }
+    fn label(&self) -> &'static str {
+        "SDF Twist"
+    }
+    fn categories(&self) -> Vec<&'static str> {
+        vec!["SDF"]
+    }
+    fn build(&self, node: &mut NodeBuilder) {
+        node.input_scalar("rate", 45.0);
+        node.input_sdf_position("sdf position");
+        node.output_sdf_position("out");
+    }
+    fn evaluate(&self, evaluator: &mut Evaluator) -> Result<N3DValueType, NodeError> {
+        let rate = evaluator.input_scalar("rate")?;
+        let position = Box::new(evaluator.input_sdf_position("sdf position")?);
+        evaluator.output_sdf_position("out", SDFPosition::Twist { position, rate })
+    }
+}
+
+pub struct SDFBend;
+
+impl NodeDefinition for SDFBend {
+    fn name(&self) -> &'static str {
+        "SDFBend"
+    }
+    fn label(&self) -> &'static str {
+        "SDF Bend"
+    }
+    fn categories(&self) -> Vec<&'static str> {
+        vec!["SDF"]
+    }
+    fn build(&self, node: &mut NodeBuilder) {
+        node.input_scalar("rate", 20.0);
+        node.input_sdf_position("sdf position");
+        node.output_sdf_position("out");
+    }
+    fn evaluate(&self, evaluator: &mut Evaluator) -> Result<N3DValueType, NodeError> {
+        let rate = evaluator.input_scalar("rate")?;
+        let position = Box::new(evaluator.input_sdf_position("sdf position")?);
+        evaluator.output_sdf_position("out", SDFPosition::Bend { position, rate })
+    }
+}
+
+pub struct SDFTaper;
+
+impl NodeDefinition for SDFTaper {
+    fn name(&self) -> &'static str {
+        "SDFTaper"
+    }
+    fn label(&self) -> &'static str {
+        "SDF Taper"
+    }
+    fn categories(&self) -> Vec<&'static str> {
+        vec!["SDF"]
+    }
+    fn build(&self, node: &mut NodeBuilder) {
+        node.input_scalar("rate", 0.25);
+        node.input_sdf_position("sdf position");
+        node.output_sdf_position("out");
+    }
+    fn evaluate(&self, evaluator: &mut Evaluator) -> Result<N3DValueType, NodeError> {
+        let rate = evaluator.input_scalar("rate")?;
+        let position = Box::new(evaluator.input_sdf_position("sdf position")?);
+        evaluator.output_sdf_position("out", SDFPosition::Taper { position, rate })
+    }
+}
+
+pub struct SDFElongate;
+
+impl NodeDefinition for SDFElongate {
+    fn name(&self) -> &'static str {
+        "SDFElongate"
+    }
+    fn label(&self) -> &'static str {
+        "SDF Elongate"
+    }
+    fn categories(&self) -> Vec<&'static str> {
+        vec!["SDF"]
+    }
+    fn build(&self, node: &mut NodeBuilder) {
+        node.input_vec3("size", Vector3::new(0.5, 0.0, 0.0));
+        node.input_sdf_position("sdf position");
+        node.output_sdf_position("out");
+    }
+    fn evaluate(&self, evaluator: &mut Evaluator) -> Result<N3DValueType, NodeError> {
+        let size = evaluator.input_vec3("size")?;
+        let position = Box::new(evaluator.input_sdf_position("sdf position")?);
+        evaluator.output_sdf_position("out", SDFPosition::Elongate { position, size })
+    }
+}
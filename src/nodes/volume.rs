@@ -0,0 +1,699 @@
+//! Primitives, booleans and modifiers of volumes, and the viewer showing one
+use nalgebra::Vector3;
+
+use super::{NodeBuilder, NodeDefinition};
+use crate::{
+    node_graph::{EvalError, Evaluator, N3DValueType, NodeError},
+    sdf::ir::SDFVolume,
+};
+
+pub struct SDFBox;
+
+impl NodeDefinition for SDFBox {
+    fn name(&self) -> &'static str {
+        "SDFBox"
+    }
+    fn label(&self) -> &'static str {
+        "SDF Box"
+    }
+    fn categories(&self) -> Vec<&'static str> {
+        vec!["SDF"]
+    }
+    fn build(&self, node: &mut NodeBuilder) {
+        node.input_vec3("dimensions", Vector3::zeros());
+        node.input_scalar("fillet", 0.0);
+        node.input_sdf_position("sdf position");
+        node.output_sdf_volume("out");
+    }
+    fn evaluate(&self, evaluator: &mut Evaluator) -> Result<N3DValueType, NodeError> {
+        let dimensions = evaluator.input_vec3("dimensions")?;
+        let fillet = evaluator.input_scalar("fillet")?;
+        let position = evaluator.input_sdf_position("sdf position")?;
+        evaluator.output_sdf_volume(
+            "out",
+            SDFVolume::Box {
+                position,
+                dimensions,
+                fillet,
+            },
+        )
+    }
+}
+
+pub struct SDFSphere;
+
+impl NodeDefinition for SDFSphere {
+    fn name(&self) -> &'static str {
+        "SDFSphere"
+    }
+    fn label(&self) -> &'static str {
+        "SDF Sphere"
+    }
+    fn categories(&self) -> Vec<&'static str> {
+        vec!["SDF"]
+    }
+    fn build(&self, node: &mut NodeBuilder) {
+        node.input_scalar("radius", 0.0);
+        node.input_sdf_position("sdf position");
+        node.output_sdf_volume("out");
+    }
+    fn evaluate(&self, evaluator: &mut Evaluator) -> Result<N3DValueType, NodeError> {
+        let radius = evaluator.input_scalar("radius")?;
+        let position = evaluator.input_sdf_position("sdf position")?;
+        evaluator.output_sdf_volume("out", SDFVolume::Sphere { position, radius })
+    }
+}
+
+pub struct SDFCylinder;
+
+impl NodeDefinition for SDFCylinder {
+    fn name(&self) -> &'static str {
+        "SDFCylinder"
+    }
+    fn label(&self) -> &'static str {
+        "SDF Cylinder"
+    }
+    fn categories(&self) -> Vec<&'static str> {
+        vec!["SDF"]
+    }
+    fn build(&self, node: &mut NodeBuilder) {
+        node.input_scalar("radius", 0.0);
+        node.input_scalar("length", 0.0);
+        node.input_scalar("fillet", 0.0);
+        node.input_sdf_position("sdf position");
+        node.output_sdf_volume("out");
+    }
+    fn evaluate(&self, evaluator: &mut Evaluator) -> Result<N3DValueType, NodeError> {
+        let radius = evaluator.input_scalar("radius")?;
+        let length = evaluator.input_scalar("length")?;
+        let fillet = evaluator.input_scalar("fillet")?;
+        let position = evaluator.input_sdf_position("sdf position")?;
+        evaluator.output_sdf_volume(
+            "out",
+            SDFVolume::Cylinder {
+                position,
+                radius,
+                length,
+                fillet,
+            },
+        )
+    }
+}
+
+pub struct SDFTorus;
+
+impl NodeDefinition for SDFTorus {
+    fn name(&self) -> &'static str {
+        "SDFTorus"
+    }
+    fn label(&self) -> &'static str {
+        "SDF Torus"
+    }
+    fn categories(&self) -> Vec<&'static str> {
+        vec!["SDF"]
+    }
+    fn build(&self, node: &mut NodeBuilder) {
+        node.input_scalar("major radius", 1.0);
+        node.input_scalar("minor radius", 0.25);
+        node.input_sdf_position("sdf position");
+        node.output_sdf_volume("out");
+    }
+    fn evaluate(&self, evaluator: &mut Evaluator) -> Result<N3DValueType, NodeError> {
+        let major_radius = evaluator.input_scalar("major radius")?;
+        let minor_radius = evaluator.input_scalar("minor radius")?;
+        let position = evaluator.input_sdf_position("sdf position")?;
+        evaluator.output_sdf_volume(
+            "out",
+            SDFVolume::Torus {
+                position,
+                major_radius,
+                minor_radius,
+            },
+        )
+    }
+}
+
+pub struct SDFCapsule;
+
+impl NodeDefinition for SDFCapsule {
+    fn name(&self) -> &'static str {
+        "SDFCapsule"
+    }
+    fn label(&self) -> &'static str {
+        "SDF Capsule"
+    }
+    fn categories(&self) -> Vec<&'static str> {
+        vec!["SDF"]
+    }
+    fn build(&self, node: &mut NodeBuilder) {
+        node.input_scalar("length", 0.5);
+        node.input_scalar("radius", 0.5);
+        node.input_sdf_position("sdf position");
+        node.output_sdf_volume("out");
+    }
+    fn evaluate(&self, evaluator: &mut Evaluator) -> Result<N3DValueType, NodeError> {
+        let length = evaluator.input_scalar("length")?;
+        let radius = evaluator.input_scalar("radius")?;
+        let position = evaluator.input_sdf_position("sdf position")?;
+        evaluator.output_sdf_volume(
+            "out",
+            SDFVolume::Capsule {
+                position,
+                length,
+                radius,
+            },
+        )
+    }
+}
+
+pub struct SDFCone;
+
+impl NodeDefinition for SDFCone {
+    fn name(&self) -> &'static str {
+        "SDFCone"
+    }
+    fn label(&self) -> &'static str {
+        "SDF Cone"
+    }
+    fn categories(&self) -> Vec<&'static str> {
+        vec!["SDF"]
+    }
+    fn build(&self, node: &mut NodeBuilder) {
+        node.input_scalar("height", 1.0);
+        node.input_scalar("radius", 0.5);
+        node.input_sdf_position("sdf position");
+        node.output_sdf_volume("out");
+    }
+    fn evaluate(&self, evaluator: &mut Evaluator) -> Result<N3DValueType, NodeError> {
+        let height = evaluator.input_scalar("height")?;
+        let radius = evaluator.input_scalar("radius")?;
+        let position = evaluator.input_sdf_position("sdf position")?;
+        evaluator.output_sdf_volume(
+            "out",
+            SDFVolume::Cone {
+                position,
+                height,
+                radius,
+            },
+        )
+    }
+}
+
+pub struct SDFPlane;
+
+impl NodeDefinition for SDFPlane {
+    fn name(&self) -> &'static str {
+        "SDFPlane"
+    }
+    fn label(&self) -> &'static str {
+        "SDF Plane"
+    }
+    fn categories(&self) -> Vec<&'static str> {
+        vec!["SDF"]
+    }
+    fn build(&self, node: &mut NodeBuilder) {
+        node.input_vec3("normal", Vector3::new(0.0, 0.0, 1.0));
+        node.input_scalar("offset", 0.0);
+        node.input_sdf_position("sdf position");
+        node.output_sdf_volume("out");
+    }
+    fn evaluate(&self, evaluator: &mut Evaluator) -> Result<N3DValueType, NodeError> {
+        let normal = evaluator.input_vec3("normal")?;
+        let offset = evaluator.input_scalar("offset")?;
+        let position = evaluator.input_sdf_position("sdf position")?;
+        evaluator.output_sdf_volume(
+            "out",
+            SDFVolume::Plane {
+                position,
+                normal,
+                offset,
+            },
+        )
+    }
+}
+
+pub struct SDFEllipsoid;
+
+impl NodeDefinition for SDFEllipsoid {
+    fn name(&self) -> &'static str {
+        "SDFEllipsoid"
+    }
+    fn label(&self) -> &'static str {
+        "SDF Ellipsoid"
+    }
+    fn categories(&self) -> Vec<&'static str> {
+        vec!["SDF"]
+    }
+    fn build(&self, node: &mut NodeBuilder) {
+        node.input_vec3("radii", Vector3::new(1.0, 0.5, 0.75));
+        node.input_sdf_position("sdf position");
+        node.output_sdf_volume("out");
+    }
+    fn evaluate(&self, evaluator: &mut Evaluator) -> Result<N3DValueType, NodeError> {
+        let radii = evaluator.input_vec3("radii")?;
+        let position = evaluator.input_sdf_position("sdf position")?;
+        evaluator.output_sdf_volume("out", SDFVolume::Ellipsoid { position, radii })
+    }
+}
+
+pub struct SDFHexPrism;
+
+impl NodeDefinition for SDFHexPrism {
+    fn name(&self) -> &'static str {
+        "SDFHexPrism"
+    }
+    fn label(&self) -> &'static str {
+        "SDF Hex Prism"
+    }
+    fn categories(&self) -> Vec<&'static str> {
+        vec!["SDF"]
+    }
+    fn build(&self, node: &mut NodeBuilder) {
+        node.input_scalar("radius", 0.5);
+        node.input_scalar("length", 1.0);
+        node.input_sdf_position("sdf position");
+        node.output_sdf_volume("out");
+    }
+    fn evaluate(&self, evaluator: &mut Evaluator) -> Result<N3DValueType, NodeError> {
+        let radius = evaluator.input_scalar("radius")?;
+        let length = evaluator.input_scalar("length")?;
+        let position = evaluator.input_sdf_position("sdf position")?;
+        evaluator.output_sdf_volume(
+            "out",
+            SDFVolume::HexPrism {
+                position,
+                radius,
+                length,
+            },
+        )
+    }
+}
+
+pub struct SDFTriPrism;
+
+impl NodeDefinition for SDFTriPrism {
+    fn name(&self) -> &'static str {
+        "SDFTriPrism"
+    }
+    fn label(&self) -> &'static str {
+        "SDF Tri Prism"
+    }
+    fn categories(&self) -> Vec<&'static str> {
+        vec!["SDF"]
+    }
+    fn build(&self, node: &mut NodeBuilder) {
+        node.input_scalar("radius", 0.5);
+        node.input_scalar("length", 1.0);
+        node.input_sdf_position("sdf position");
+        node.output_sdf_volume("out");
+    }
+    fn evaluate(&self, evaluator: &mut Evaluator) -> Result<N3DValueType, NodeError> {
+        let radius = evaluator.input_scalar("radius")?;
+        let length = evaluator.input_scalar("length")?;
+        let position = evaluator.input_sdf_position("sdf position")?;
+        evaluator.output_sdf_volume(
+            "out",
+            SDFVolume::TriPrism {
+                position,
+                radius,
+                length,
+            },
+        )
+    }
+}
+
+pub struct SDFRoundedCone;
+
+impl NodeDefinition for SDFRoundedCone {
+    fn name(&self) -> &'static str {
+        "SDFRoundedCone"
+    }
+    fn label(&self) -> &'static str {
+        "SDF Rounded Cone"
+    }
+    fn categories(&self) -> Vec<&'static str> {
+        vec!["SDF"]
+    }
+    fn build(&self, node: &mut NodeBuilder) {
+        node.input_scalar("bottom radius", 0.5);
+        node.input_scalar("top radius", 0.2);
+        node.input_scalar("height", 1.0);
+        node.input_sdf_position("sdf position");
+        node.output_sdf_volume("out");
+    }
+    fn evaluate(&self, evaluator: &mut Evaluator) -> Result<N3DValueType, NodeError> {
+        let bottom_radius = evaluator.input_scalar("bottom radius")?;
+        let top_radius = evaluator.input_scalar("top radius")?;
+        let height = evaluator.input_scalar("height")?;
+        let position = evaluator.input_sdf_position("sdf position")?;
+        evaluator.output_sdf_volume(
+            "out",
+            SDFVolume::RoundedCone {
+                position,
+                bottom_radius,
+                top_radius,
+                height,
+            },
+        )
+    }
+}
+
+pub struct SDFOctahedron;
+
+impl NodeDefinition for SDFOctahedron {
+    fn name(&self) -> &'static str {
+        "SDFOctahedron"
+    }
+    fn label(&self) -> &'static str {
+        "SDF Octahedron"
+    }
+    fn categories(&self) -> Vec<&'static str> {
+        vec!["SDF"]
+    }
+    fn build(&self, node: &mut NodeBuilder) {
+        node.input_scalar("size", 1.0);
+        node.input_sdf_position("sdf position");
+        node.output_sdf_volume("out");
+    }
+    fn evaluate(&self, evaluator: &mut Evaluator) -> Result<N3DValueType, NodeError> {
+        let size = evaluator.input_scalar("size")?;
+        let position = evaluator.input_sdf_position("sdf position")?;
+        evaluator.output_sdf_volume("out", SDFVolume::Octahedron { position, size })
+    }
+}
+
+pub struct SDFLink;
+
+impl NodeDefinition for SDFLink {
+    fn name(&self) -> &'static str {
+        "SDFLink"
+    }
+    fn label(&self) -> &'static str {
+        "SDF Link"
+    }
+    fn categories(&self) -> Vec<&'static str> {
+        vec!["SDF"]
+    }
+    fn build(&self, node: &mut NodeBuilder) {
+        node.input_scalar("length", 0.5);
+        node.input_scalar("major radius", 0.5);
+        node.input_scalar("minor radius", 0.15);
+        node.input_sdf_position("sdf position");
+        node.output_sdf_volume("out");
+    }
+    fn evaluate(&self, evaluator: &mut Evaluator) -> Result<N3DValueType, NodeError> {
+        let length = evaluator.input_scalar("length")?;
+        let major_radius = evaluator.input_scalar("major radius")?;
+        let minor_radius = evaluator.input_scalar("minor radius")?;
+        let position = evaluator.input_sdf_position("sdf position")?;
+        evaluator.output_sdf_volume(
+            "out",
+            SDFVolume::Link {
+                position,
+                length,
+                major_radius,
+                minor_radius,
+            },
+        )
+    }
+}
+
+pub struct SDFPyramid;
+
+impl NodeDefinition for SDFPyramid {
+    fn name(&self) -> &'static str {
+        "SDFPyramid"
+    }
+    fn label(&self) -> &'static str {
+        "SDF Pyramid"
+    }
+    fn categories(&self) -> Vec<&'static str> {
+        vec!["SDF"]
+    }
+    fn build(&self, node: &mut NodeBuilder) {
+        node.input_scalar("base", 1.0);
+        node.input_scalar("height", 1.0);
+        node.input_sdf_position("sdf position");
+        node.output_sdf_volume("out");
+    }
+    fn evaluate(&self, evaluator: &mut Evaluator) -> Result<N3DValueType, NodeError> {
+        let base = evaluator.input_scalar("base")?;
+        let height = evaluator.input_scalar("height")?;
+        let position = evaluator.input_sdf_position("sdf position")?;
+        evaluator.output_sdf_volume(
+            "out",
+            SDFVolume::Pyramid {
+                position,
+                base,
+                height,
+            },
+        )
+    }
+}
+
+pub struct SDFUnion;
+
+impl NodeDefinition for SDFUnion {
+    fn name(&self) -> &'static str {
+        "SDFUnion"
+    }
+    fn label(&self) -> &'static str {
+        "SDF Union"
+    }
+    fn categories(&self) -> Vec<&'static str> {
+        vec!["SDF"]
+    }
+    fn build(&self, node: &mut NodeBuilder) {
+        node.input_sdf_volume("sdf 1");
+        node.input_sdf_volume("sdf 2");
+        node.output_sdf_volume("out");
+    }
+    fn evaluate(&self, evaluator: &mut Evaluator) -> Result<N3DValueType, NodeError> {
+        let a = Box::new(evaluator.input_sdf_volume("sdf 1")?);
+        let b = Box::new(evaluator.input_sdf_volume("sdf 2")?);
+        evaluator.output_sdf_volume("out", SDFVolume::Union { a, b })
+    }
+}
+
+pub struct SDFSmoothUnion;
+
+impl NodeDefinition for SDFSmoothUnion {
+    fn name(&self) -> &'static str {
+        "SDFSmoothUnion"
+    }
+    fn label(&self) -> &'static str {
+        "SDFSmoothUnion"
+    }
+    fn categories(&self) -> Vec<&'static str> {
+        vec!["SDF"]
+    }
+    fn build(&self, node: &mut NodeBuilder) {
+        node.input_sdf_volume("sdf 1");
+        node.input_sdf_volume("sdf 2");
+        node.input_scalar("fac", 0.0);
+        node.output_sdf_volume("out");
+    }
+    fn evaluate(&self, evaluator: &mut Evaluator) -> Result<N3DValueType, NodeError> {
+        let a = Box::new(evaluator.input_sdf_volume("sdf 1")?);
+        let b = Box::new(evaluator.input_sdf_volume("sdf 2")?);
+        let k = evaluator.input_scalar("fac")?;
+        evaluator.output_sdf_volume("out", SDFVolume::SmoothUnion { a, b, k })
+    }
+}
+
+pub struct SDFDiff;
+
+impl NodeDefinition for SDFDiff {
+    fn name(&self) -> &'static str {
+        "SDFDiff"
+    }
+    fn label(&self) -> &'static str {
+        "SDFDiff"
+    }
+    fn categories(&self) -> Vec<&'static str> {
+        vec!["SDF"]
+    }
+    fn build(&self, node: &mut NodeBuilder) {
+        node.input_sdf_volume("sdf 1");
+        node.input_sdf_volume("sdf 2");
+        node.output_sdf_volume("out");
+    }
+    fn evaluate(&self, evaluator: &mut Evaluator) -> Result<N3DValueType, NodeError> {
+        let a = Box::new(evaluator.input_sdf_volume("sdf 1")?);
+        let b = Box::new(evaluator.input_sdf_volume("sdf 2")?);
+        evaluator.output_sdf_volume("out", SDFVolume::Diff { a, b })
+    }
+}
+
+pub struct SDFSmoothDiff;
+
+impl NodeDefinition for SDFSmoothDiff {
+    fn name(&self) -> &'static str {
+        "SDFSmoothDiff"
+    }
+    fn label(&self) -> &'static str {
+        "SDFSmoothDiff"
+    }
+    fn categories(&self) -> Vec<&'static str> {
+        vec!["SDF"]
+    }
+    fn build(&self, node: &mut NodeBuilder) {
+        node.input_sdf_volume("sdf 1");
+        node.input_sdf_volume("sdf 2");
+        node.input_scalar("fac", 0.0);
+        node.output_sdf_volume("out");
+    }
+    fn evaluate(&self, evaluator: &mut Evaluator) -> Result<N3DValueType, NodeError> {
+        let a = Box::new(evaluator.input_sdf_volume("sdf 1")?);
+        let b = Box::new(evaluator.input_sdf_volume("sdf 2")?);
+        let k = evaluator.input_scalar("fac")?;
+        evaluator.output_sdf_volume("out", SDFVolume::SmoothDiff { a, b, k })
+    }
+}
+
+pub struct SDFIntersect;
+
+impl NodeDefinition for SDFIntersect {
+    fn name(&self) -> &'static str {
+        "SDFIntersect"
+    }
+    fn label(&self) -> &'static str {
+        "SDF Intersect"
+    }
+    fn categories(&self) -> Vec<&'static str> {
+        vec!["SDF"]
+    }
+    fn build(&self, node: &mut NodeBuilder) {
+        node.input_sdf_volume("sdf 1");
+        node.input_sdf_volume("sdf 2");
+        node.output_sdf_volume("out");
+    }
+    fn evaluate(&self, evaluator: &mut Evaluator) -> Result<N3DValueType, NodeError> {
+        let a = Box::new(evaluator.input_sdf_volume("sdf 1")?);
+        let b = Box::new(evaluator.input_sdf_volume("sdf 2")?);
+        evaluator.output_sdf_volume("out", SDFVolume::Intersect { a, b })
+    }
+}
+
+pub struct SDFSmoothIntersect;
+
+impl NodeDefinition for SDFSmoothIntersect {
+    fn name(&self) -> &'static str {
+        "SDFSmoothIntersect"
+    }
+    fn label(&self) -> &'static str {
+        "SDF Smooth Intersect"
+    }
+    fn categories(&self) -> Vec<&'static str> {
+        vec!["SDF"]
+    }
+    fn build(&self, node: &mut NodeBuilder) {
+        node.input_sdf_volume("sdf 1");
+        node.input_sdf_volume("sdf 2");
+        node.input_scalar("fac", 0.0);
+        node.output_sdf_volume("out");
+    }
+    fn evaluate(&self, evaluator: &mut Evaluator) -> Result<N3DValueType, NodeError> {
+        let a = Box::new(evaluator.input_sdf_volume("sdf 1")?);
+        let b = Box::new(evaluator.input_sdf_volume("sdf 2")?);
+        let k = evaluator.input_scalar("fac")?;
+        evaluator.output_sdf_volume("out", SDFVolume::SmoothIntersect { a, b, k })
+    }
+}
+
+pub struct SDFRound;
+
+impl NodeDefinition for SDFRound {
+    fn name(&self) -> &'static str {
+        "SDFRound"
+    }
+    fn label(&self) -> &'static str {
+        "SDF Round"
+    }
+    fn categories(&self) -> Vec<&'static str> {
+        vec!["SDF"]
+    }
+    fn build(&self, node: &mut NodeBuilder) {
+        node.input_sdf_volume("sdf");
+        node.input_scalar("radius", 0.1);
+        node.output_sdf_volume("out");
+    }
+    fn evaluate(&self, evaluator: &mut Evaluator) -> Result<N3DValueType, NodeError> {
+        let volume = Box::new(evaluator.input_sdf_volume("sdf")?);
+        let radius = evaluator.input_scalar("radius")?;
+        evaluator.output_sdf_volume("out", SDFVolume::Round { volume, radius })
+    }
+}
+
+pub struct SDFShell;
+
+impl NodeDefinition for SDFShell {
+    fn name(&self) -> &'static str {
+        "SDFShell"
+    }
+    fn label(&self) -> &'static str {
+        "SDF Shell"
+    }
+    fn categories(&self) -> Vec<&'static str> {
+        vec!["SDF"]
+    }
+    fn build(&self, node: &mut NodeBuilder) {
+        node.input_sdf_volume("sdf");
+        node.input_scalar("thickness", 0.05);
+        node.output_sdf_volume("out");
+    }
+    fn evaluate(&self, evaluator: &mut Evaluator) -> Result<N3DValueType, NodeError> {
+        let volume = Box::new(evaluator.input_sdf_volume("sdf")?);
+        let thickness = evaluator.input_scalar("thickness")?;
+        evaluator.output_sdf_volume("out", SDFVolume::Shell { volume, thickness })
+    }
+}
+
+pub struct SDFHollow;
+
+impl NodeDefinition for SDFHollow {
+    fn name(&self) -> &'static str {
+        "SDFHollow"
+    }
+    fn label(&self) -> &'static str {
+        "SDF Hollow"
+    }
+    fn categories(&self) -> Vec<&'static str> {
+        vec!["SDF"]
+    }
+    fn build(&self, node: &mut NodeBuilder) {
+        node.input_sdf_volume("sdf");
+        node.output_sdf_volume("out");
+    }
+    fn evaluate(&self, evaluator: &mut Evaluator) -> Result<N3DValueType, NodeError> {
+        let volume = Box::new(evaluator.input_sdf_volume("sdf")?);
+        evaluator.output_sdf_volume("out", SDFVolume::Hollow { volume })
+    }
+}
+
+pub struct SDFViewer;
+
+impl NodeDefinition for SDFViewer {
+    fn name(&self) -> &'static str {
+        "SDFViewer"
+    }
+    fn label(&self) -> &'static str {
+        "SDF Viewer"
+    }
+    fn categories(&self) -> Vec<&'static str> {
+        vec!["SDF"]
+    }
+    fn build(&self, node: &mut NodeBuilder) {
+        node.input_sdf_volume("sdf");
+    }
+    fn evaluate(&self, evaluator: &mut Evaluator) -> Result<N3DValueType, NodeError> {
+        match evaluator.input_sdf_volume("sdf") {
+            Ok(node) => Ok(N3DValueType::SDFVolume { value: node }),
+            Err(NodeError {
+                node,
+                error: EvalError::MissingConnection(_),
+            }) if node == evaluator.node_id() => evaluator.locate(Err(EvalError::NoViewerInput)),
+            Err(err) => Err(err),
+        }
+    }
+}
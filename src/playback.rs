@@ -0,0 +1,93 @@
+use std::time::Instant;
+
+/// Scene clock behind `u_time` and the Time node
+pub struct Playback {
+    /// Seconds since the start of the preview
+    time: f32,
+    /// Length of the scrub range, playback wraps back to zero past it
+    duration: f32,
+    playing: bool,
+    last_tick: Option<Instant>,
+}
+
+impl Default for Playback {
+    fn default() -> Self {
+        Self {
+            time: 0.0,
+            duration: 10.0,
+            playing: true,
+            last_tick: None,
+        }
+    }
+}
+
+impl Playback {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn time(&self) -> f32 {
+        self.time
+    }
+
+    /// Jumps to `time`, kept within the scrub range
+    pub fn set_time(&mut self, time: f32) {
+        self.time = time.clamp(0.0, self.duration);
+    }
+
+    pub fn duration(&self) -> f32 {
+        self.duration
+    }
+
+    /// Advances the clock by the wall time since the previous tick while playing,
+    /// call once per frame
+    pub fn tick(&mut self) {
+        let now = Instant::now();
+        if let (true, Some(last)) = (self.playing, self.last_tick) {
+            self.advance((now - last).as_secs_f32());
+        }
+        self.last_tick = Some(now);
+    }
+
+    fn advance(&mut self, seconds: f32) {
+        self.time = (self.time + seconds).rem_euclid(self.duration.max(f32::EPSILON));
+    }
+
+    /// Play/pause and reset buttons with a slider to scrub through the preview
+    pub fn ui(&mut self, ui: &mut egui::Ui) {
+        let label = if self.playing { "Pause" } else { "Play" };
+        if ui.button(label).clicked() {
+            self.playing = !self.playing;
+        }
+        if ui.button("Reset").clicked() {
+            self.time = 0.0;
+        }
+        ui.add(
+            egui::Slider::new(&mut self.time, 0.0..=self.duration)
+                .suffix(" s")
+                .text("time"),
+        );
+        ui.add(
+            egui::DragValue::new(&mut self.duration)
+                .clamp_range(0.1..=3600.0)
+                .speed(0.1)
+                .prefix("length: ")
+                .suffix(" s"),
+        );
+        self.time = self.time.min(self.duration);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wraps_at_duration() {
+        let mut playback = Playback::new();
+        playback.advance(4.0);
+        assert_eq!(playback.time(), 4.0);
+        playback.advance(7.0);
+        assert_eq!(playback.time(), 1.0);
+    }
+}
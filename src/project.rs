@@ -0,0 +1,239 @@
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{
+    camera::Camera,
+    group::LIBRARY_EXTENSION,
+    node_graph::{MyEditorState, MyGraphState},
+};
+
+/// Version written to every project file. Bump this whenever the layout of
+/// [`Project`] changes so older files can be detected on load.
+///
+/// 1. Graph, active node and camera
+/// 2. Values hold typed expression trees instead of GLSL strings
+/// 3. 2D shape values
+/// 4. Expression values for inputs that depend on the playback
+/// 5. Text values
+/// 6. Keyframes in the graph state
+/// 7. Material and matcap values
+/// 8. Node groups in the graph state
+/// 9. Node types written by their registered name
+/// 10. Code values
+/// 11. Node names
+pub const PROJECT_VERSION: u32 = 11;
+
+/// Oldest version whose files can still be read. Only versions the tests
+/// open count, so raise it along with [`PROJECT_VERSION`] unless a test
+/// opens a file saved by the version before.
+pub const OLDEST_PROJECT_VERSION: u32 = 11;
+
+/// Extension used for N3D project files
+pub const PROJECT_EXTENSION: &str = "n3d";
+
+#[derive(Error, Debug)]
+pub enum ProjectError {
+    #[error("could not access project file: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("could not parse project file: {0}")]
+    Parse(#[from] ron::error::SpannedError),
+    #[error("could not write project file: {0}")]
+    Serialize(#[from] ron::Error),
+    #[error("project version {0} is newer than supported version {PROJECT_VERSION}")]
+    UnsupportedVersion(u32),
+    #[error(
+        "project version {0} is older than the oldest supported version {OLDEST_PROJECT_VERSION}"
+    )]
+    OutdatedVersion(u32),
+}
+
+/// Everything needed to restore a modeling session: the graph topology, node
+/// positions and inline values (all held by the editor state), the active
+/// node and the camera.
+#[derive(Serialize, Deserialize)]
+pub struct Project {
+    pub version: u32,
+    pub editor: MyEditorState,
+    pub user_state: MyGraphState,
+    pub camera: Camera,
+}
+
+/// Borrowed view of a [`Project`] so saving does not need to clone the graph
+#[derive(Serialize)]
+struct ProjectRef<'a> {
+    version: u32,
+    editor: &'a MyEditorState,
+    user_state: &'a MyGraphState,
+    camera: &'a Camera,
+}
+
+/// Only the version field, read before the rest of the file is parsed
+#[derive(Deserialize)]
+struct ProjectHeader {
+    version: u32,
+}
+
+impl Project {
+    /// Reads a project file from disk, rejecting files written by a newer
+    /// version or in a layout that is no longer supported
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, ProjectError> {
+        let text = std::fs::read_to_string(path)?;
+        let header: ProjectHeader = ron::from_str(&text)?;
+        if header.version > PROJECT_VERSION {
+            return Err(ProjectError::UnsupportedVersion(header.version));
+        }
+        if header.version < OLDEST_PROJECT_VERSION {
+            return Err(ProjectError::OutdatedVersion(header.version));
+        }
+        Ok(ron::from_str(&text)?)
+    }
+
+    /// Writes the given session to disk in the current project version
+    pub fn save<P: AsRef<Path>>(
+        path: P,
+        editor: &MyEditorState,
+        user_state: &MyGraphState,
+        camera: &Camera,
+    ) -> Result<(), ProjectError> {
+        let project = ProjectRef {
+            version: PROJECT_VERSION,
+            editor,
+            user_state,
+            camera,
+        };
+        let text = ron::ser::to_string_pretty(&project, ron::ser::PrettyConfig::default())?;
+        std::fs::write(path, text)?;
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum ProjectDialogKind {
+    Open,
+    SaveAs,
+    /// Group library to merge into the project
+    ImportGroups,
+    /// Group library to write the project's groups to
+    ExportGroups,
+}
+
+pub enum ProjectDialogResponse {
+    Pending,
+    Cancelled,
+    Confirmed(PathBuf),
+}
+
+/// Minimal egui prompt for a project or group library path, used by the
+/// File and Groups menus
+pub struct ProjectDialog {
+    pub kind: ProjectDialogKind,
+    pub path: String,
+}
+
+impl ProjectDialog {
+    pub fn new(kind: ProjectDialogKind, current: Option<&Path>) -> Self {
+        let path = match (current, kind) {
+            (Some(path), _) => path.display().to_string(),
+            (None, ProjectDialogKind::ImportGroups | ProjectDialogKind::ExportGroups) => {
+                format!("groups.{}", LIBRARY_EXTENSION)
+            }
+            (None, _) => format!("untitled.{}", PROJECT_EXTENSION),
+        };
+        Self { kind, path }
+    }
+
+    /// Draws the dialog and reports whether the user confirmed a path
+    pub fn show(&mut self, ctx: &egui::Context) -> ProjectDialogResponse {
+        let title = match self.kind {
+            ProjectDialogKind::Open => "Open Project",
+            ProjectDialogKind::SaveAs => "Save Project As",
+            ProjectDialogKind::ImportGroups => "Import Groups",
+            ProjectDialogKind::ExportGroups => "Export Groups",
+        };
+        let mut response = ProjectDialogResponse::Pending;
+        egui::Window::new(title)
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label("Path");
+                    ui.text_edit_singleline(&mut self.path);
+                });
+                ui.horizontal(|ui| {
+                    let confirm = match self.kind {
+                        ProjectDialogKind::Open => "Open",
+                        ProjectDialogKind::SaveAs => "Save",
+                        ProjectDialogKind::ImportGroups => "Import",
+                        ProjectDialogKind::ExportGroups => "Export",
+                    };
+                    if ui.button(confirm).clicked() && !self.path.trim().is_empty() {
+                        response = ProjectDialogResponse::Confirmed(PathBuf::from(self.path.trim()));
+                    }
+                    if ui.button("Cancel").clicked() {
+                        response = ProjectDialogResponse::Cancelled;
+                    }
+                });
+            });
+        response
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Project, ProjectError, OLDEST_PROJECT_VERSION, PROJECT_VERSION};
+    use crate::{
+        camera::Camera,
+        node_graph::{tests::add, MyEditorState, MyGraphState, N3DNodeTemplate, N3DValueType},
+        nodes::volume::SDFSphere,
+        sdf::expr::Value,
+    };
+
+    #[test]
+    fn saved_project_opens_unchanged() {
+        let mut editor = MyEditorState::default();
+        let mut user_state = MyGraphState::default();
+        let template = N3DNodeTemplate::Node(&SDFSphere);
+        let sphere = add(&mut editor, &mut user_state, template);
+        let radius = editor.graph[sphere].get_input("radius").unwrap();
+        editor.graph[radius].value = N3DValueType::Scalar { value: 0.75 };
+        user_state.active_node = Some(sphere);
+        let other = add(&mut editor, &mut user_state, template);
+        for (node, value) in [(sphere, 0.5), (other, 2.0)] {
+            let animation = &mut user_state.animation;
+            animation.set_key(node, "radius", 0.0, Value::Scalar(value));
+            animation.set_key(node, "radius", 24.0, Value::Scalar(1.0));
+        }
+
+        let dir = std::env::temp_dir().join(format!("n3d-project-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("sphere.n3d");
+        Project::save(&path, &editor, &user_state, &Camera::new()).unwrap();
+        let project = Project::open(&path).unwrap();
+        assert_eq!(project.version, PROJECT_VERSION);
+        assert_eq!(project.user_state.active_node, Some(sphere));
+        let radius = project.editor.graph[sphere].get_input("radius").unwrap();
+        assert!(matches!(
+            project.editor.graph[radius].value,
+            N3DValueType::Scalar { value } if value == 0.75
+        ));
+        assert_eq!(project.user_state.animation, user_state.animation);
+        // Saving again writes the same file
+        let first = std::fs::read_to_string(&path).unwrap();
+        Project::save(&path, &project.editor, &project.user_state, &project.camera).unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), first);
+
+        std::fs::write(&path, format!("(version: {})", PROJECT_VERSION + 1)).unwrap();
+        assert!(matches!(
+            Project::open(&path),
+            Err(ProjectError::UnsupportedVersion(_))
+        ));
+        std::fs::write(&path, format!("(version: {})", OLDEST_PROJECT_VERSION - 1)).unwrap();
+        assert!(matches!(
+            Project::open(&path),
+            Err(ProjectError::OutdatedVersion(version)) if version == OLDEST_PROJECT_VERSION - 1
+        ));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,4 +1,32 @@
+use std::{
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+};
+
 use crate::renderer::{mesh::Mesh, shader::Shader, vert::Vert, texture::Texture};
+use crate::sdf::ir::Matcap;
+
+/// Vertex shader shared by every ray marching program
+pub const RAY_VERT: &str = std::include_str!("../res/shaders/ray.vert");
+
+/// Number of inactive linked programs kept around for reuse
+const MAX_CACHED_PROGRAMS: usize = 32;
+
+/// Texture unit of the first matcap, the others follow in [`Matcap::ALL`] order
+pub const MATCAP_UNIT: u32 = 17;
+
+fn matcap_path(matcap: Matcap) -> &'static str {
+    match matcap {
+        Matcap::Jade => "res/matcap/jade.tga",
+        Matcap::Clay => "res/matcap/clay_studio.tga",
+        Matcap::Ceramic => "res/matcap/ceramic_lightbulb.tga",
+        Matcap::Metal => "res/matcap/metal_shiny.tga",
+        Matcap::CarPaint => "res/matcap/metal_carpaint.tga",
+        Matcap::Pearl => "res/matcap/pearl.tga",
+        Matcap::Resin => "res/matcap/resin.tga",
+        Matcap::Toon => "res/matcap/toon.tga",
+    }
+}
 
 pub struct RayMarcher {
     /// Plane which ray marching is drawn to
@@ -7,7 +35,14 @@ pub struct RayMarcher {
     pub shader: Shader,
     /// Uniform camera zoom sent to shader
     pub zoom: f32,
-    pub matcap: Texture,
+    /// One texture per [`Matcap`], bound to `u_matcaps`
+    pub matcaps: Vec<Texture>,
+    /// Hash of the fragment source `shader` was linked from
+    pub source_hash: Option<u64>,
+    /// Previously linked programs keyed by source hash, least recently used first
+    pub programs: Vec<(u64, Shader)>,
+    /// Hash and compiler log of the last source that failed to compile
+    pub failed: Option<(u64, String)>,
 }
 
 impl RayMarcher {
@@ -23,11 +58,73 @@ impl RayMarcher {
             mesh,
             shader: Shader::from("res/shaders/ray.vert", "res/shaders/ray.frag"),
             zoom: 1.0,
-            //matcap: Texture::open("res/matcap/metal_shiny.tga").unwrap(),
-            //matcap: Texture::open("res/matcap/default.png").unwrap(),
-            matcap: Texture::open("res/matcap/jade.tga").unwrap(),
-            //matcap: Texture::open("res/matcap/metal_carpaint.tga").unwrap(),
-            //matcap: Texture::open("res/matcap/reflection_check_horizontal.tga").unwrap(),
+            matcaps: Matcap::ALL
+                .iter()
+                .map(|matcap| Texture::open(matcap_path(*matcap)).unwrap())
+                .collect(),
+            source_hash: None,
+            programs: Vec::new(),
+            failed: None,
+        }
+    }
+
+    /// Uploads the matcaps, call once the GL context exists
+    pub fn gen_matcaps(&mut self) {
+        for (i, matcap) in self.matcaps.iter_mut().enumerate() {
+            Texture::set_active_unit(gl::TEXTURE0 + MATCAP_UNIT + i as u32);
+            matcap.gen();
+        }
+    }
+
+    /// Binds the matcaps to their units and points `u_matcaps` of the active program at them
+    pub fn bind_matcaps(&self) {
+        for (i, matcap) in self.matcaps.iter().enumerate() {
+            let unit = MATCAP_UNIT + i as u32;
+            Texture::set_active_unit(gl::TEXTURE0 + unit);
+            matcap.bind().unwrap();
+            self.shader
+                .uniform_tex(&format!("u_matcaps[{}]", i), unit as i32);
+        }
+    }
+
+    pub fn hash_source(fragment: &str) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        fragment.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// Makes the program linked from `fragment` active. A program is only
+    /// compiled the first time its source is seen, later requests reuse the
+    /// cached one. Returns true if the active program changed, in which case
+    /// its uniforms need to be set again. A source that does not compile
+    /// leaves the active program alone and returns the compiler's log, which
+    /// is kept so the source is not compiled again every frame.
+    pub fn use_source(&mut self, fragment: &str) -> Result<bool, String> {
+        let hash = Self::hash_source(fragment);
+        if self.source_hash == Some(hash) {
+            return Ok(false);
+        }
+        match self.failed {
+            Some((failed, ref log)) if failed == hash => return Err(log.clone()),
+            _ => {}
+        }
+        let shader = match self.programs.iter().position(|(h, _)| *h == hash) {
+            Some(index) => self.programs.remove(index).1,
+            None => match Shader::try_new(RAY_VERT, fragment) {
+                Ok(shader) => shader,
+                Err(log) => {
+                    self.failed = Some((hash, log.clone()));
+                    return Err(log);
+                }
+            },
+        };
+        let previous = std::mem::replace(&mut self.shader, shader);
+        if let Some(previous_hash) = self.source_hash.replace(hash) {
+            self.programs.push((previous_hash, previous));
+            if self.programs.len() > MAX_CACHED_PROGRAMS {
+                self.programs.remove(0);
+            }
         }
+        Ok(true)
     }
 }
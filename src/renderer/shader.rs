@@ -26,6 +26,16 @@ impl Shader {
             gl::Uniform1f(gl::GetUniformLocation(self.id, name.as_ptr()), value);
         }
     }
+    pub fn uniform_f32v(&self, name: &str, values: &[f32]) {
+        let name = CString::new(name).unwrap();
+        unsafe {
+            gl::Uniform1fv(
+                gl::GetUniformLocation(self.id, name.as_ptr()),
+                values.len() as i32,
+                values.as_ptr(),
+            );
+        }
+    }
     pub fn uniform_tex<N: Into<i32>>(&self, name: &str, value: N) {
         let name = CString::new(name).unwrap();
         unsafe {
@@ -210,6 +220,82 @@ impl Shader {
         Shader { id: id }
     }
 
+    /// Like [`Self::new`], but a fragment shader that does not compile or
+    /// link is returned as the compiler's log rather than printed
+    pub fn try_new(vs: &str, fs: &str) -> Result<Shader, String> {
+        let vs = Self::compile(gl::VERTEX_SHADER, vs)?;
+        let fs = match Self::compile(gl::FRAGMENT_SHADER, fs) {
+            Ok(fs) => fs,
+            Err(log) => {
+                unsafe { gl::DeleteShader(vs) };
+                return Err(log);
+            }
+        };
+        let id = unsafe { gl::CreateProgram() };
+        let mut success = 0;
+        unsafe {
+            gl::AttachShader(id, vs);
+            gl::AttachShader(id, fs);
+            gl::LinkProgram(id);
+            gl::GetProgramiv(id, gl::LINK_STATUS, &mut success);
+            gl::DetachShader(id, vs);
+            gl::DetachShader(id, fs);
+            gl::DeleteShader(vs);
+            gl::DeleteShader(fs);
+        }
+        let shader = Shader { id };
+        if success == 0 {
+            let mut length = 0;
+            unsafe { gl::GetProgramiv(id, gl::INFO_LOG_LENGTH, &mut length) };
+            let mut log = vec![0u8; length.max(1) as usize];
+            unsafe {
+                gl::GetProgramInfoLog(
+                    id,
+                    length,
+                    std::ptr::null_mut(),
+                    log.as_mut_ptr() as *mut GLchar,
+                )
+            };
+            return Err(Self::log_string(log));
+        }
+        Ok(shader)
+    }
+
+    /// Compiles one stage, returning the compiler's log if it fails
+    fn compile(kind: GLenum, source: &str) -> Result<GLuint, String> {
+        let source = CString::new(source).map_err(|err| err.to_string())?;
+        let shader = unsafe { gl::CreateShader(kind) };
+        let mut success = 0;
+        unsafe {
+            gl::ShaderSource(shader, 1, &source.as_ptr(), std::ptr::null());
+            gl::CompileShader(shader);
+            gl::GetShaderiv(shader, gl::COMPILE_STATUS, &mut success);
+        }
+        if success != 0 {
+            return Ok(shader);
+        }
+        let mut length = 0;
+        unsafe { gl::GetShaderiv(shader, gl::INFO_LOG_LENGTH, &mut length) };
+        let mut log = vec![0u8; length.max(1) as usize];
+        unsafe {
+            gl::GetShaderInfoLog(
+                shader,
+                length,
+                std::ptr::null_mut(),
+                log.as_mut_ptr() as *mut GLchar,
+            );
+            gl::DeleteShader(shader);
+        }
+        Err(Self::log_string(log))
+    }
+
+    fn log_string(mut log: Vec<u8>) -> String {
+        if let Some(end) = log.iter().position(|b| *b == 0) {
+            log.truncate(end);
+        }
+        String::from_utf8_lossy(&log).into_owned()
+    }
+
     fn print_shader_compilation<P: AsRef<std::path::Path>>(shader: u32, path: P) {
         let mut success = 0;
         unsafe {
@@ -282,6 +368,11 @@ impl Shader {
 
 impl Drop for Shader {
     fn drop(&mut self) {
+        // No program was made, and there may be no GL context to delete it
+        // from when running headless
+        if self.id == 0 {
+            return;
+        }
         unsafe {
             gl::DeleteProgram(self.id);
         }
@@ -44,6 +44,16 @@ impl VAO {
                 (Vert::OFFSET_COL) as *const std::ffi::c_void,
             );
             gl::EnableVertexAttribArray(2);
+            // Normal
+            gl::VertexAttribPointer(
+                3,
+                3,
+                gl::FLOAT,
+                gl::FALSE,
+                (std::mem::size_of::<Vert>()) as i32,
+                (Vert::OFFSET_NORM) as *const std::ffi::c_void,
+            );
+            gl::EnableVertexAttribArray(3);
         }
         self.unbind();
     }
@@ -1,10 +1,12 @@
-use nalgebra::{Point2, Point3};
+use nalgebra::{Point2, Point3, Vector3};
 
 #[derive(Debug)]
 pub struct Vert {
     pub pos: Point3<f32>,
     pub uv: Point2<f32>,
     pub col: Point3<f32>,
+    /// Unit normal, zero for geometry that is not shaded
+    pub norm: Vector3<f32>,
 }
 impl Vert {
     pub fn new() -> Self {
@@ -12,6 +14,7 @@ impl Vert {
             pos: Point3::new(0.0, 0.0, 0.0),
             uv: Point2::new(0.0, 0.0),
             col: Point3::new(0.0, 0.0, 0.0),
+            norm: Vector3::zeros(),
         }
     }
     pub fn from(coord: [f32; 3], uv: [f32; 2], col: [f32; 3]) -> Self {
@@ -19,6 +22,7 @@ impl Vert {
             pos: Point3::new(coord[0], coord[1], coord[2]),
             uv: Point2::new(uv[0], uv[1]),
             col: Point3::new(col[0], col[1], col[2]),
+            norm: Vector3::zeros(),
         }
     }
     pub fn from_pos(coord: [f32; 3]) -> Self {
@@ -26,6 +30,7 @@ impl Vert {
             pos: Point3::new(coord[0], coord[1], coord[2]),
             uv: Point2::new(0.0, 0.0),
             col: Point3::new(0.0, 0.0, 0.0),
+            norm: Vector3::zeros(),
         }
     }
     pub fn from_pos_with_uv(coord: [f32; 3], uv: [f32; 2]) -> Self {
@@ -33,6 +38,7 @@ impl Vert {
             pos: Point3::new(coord[0], coord[1], coord[2]),
             uv: Point2::new(uv[0], uv[1]),
             col: Point3::new(0.0, 0.0, 0.0),
+            norm: Vector3::zeros(),
         }
     }
     pub fn from_pos_with_col(coord: [f32; 3], col: [f32; 3]) -> Self {
@@ -40,6 +46,7 @@ impl Vert {
             pos: Point3::new(coord[0], coord[1], coord[2]),
             uv: Point2::new(0.0, 0.0),
             col: Point3::new(col[0], col[1], col[2]),
+            norm: Vector3::zeros(),
         }
     }
     pub const OFFSET_POS: usize = unsafe {
@@ -72,4 +79,14 @@ impl Vert {
 
         col_u8_ptr.offset_from(vert_u8_ptr) as usize
     };
+    pub const OFFSET_NORM: usize = unsafe {
+        let vert = std::mem::MaybeUninit::uninit();
+        let vert_ptr: *const Vert = vert.as_ptr();
+
+        // cast to u8 pointers so we get offset in bytes
+        let vert_u8_ptr = vert_ptr as *const u8;
+        let norm_u8_ptr = std::ptr::addr_of!((*vert_ptr).norm) as *const u8;
+
+        norm_u8_ptr.offset_from(vert_u8_ptr) as usize
+    };
 }
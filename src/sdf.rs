@@ -1,3 +1,19 @@
+pub mod cpu;
+pub mod expr;
+pub mod formula;
+pub mod ir;
+pub mod lower;
+pub mod snippet;
+
+use ir::{SDFPosition, SDFVolume};
+use lower::{LoweredScene, Lowering};
+
+/// Surface of a scene without materials, everything gets the default one
+const DEFAULT_SURFACE: &str = "op_material(scene(p), 0.0)";
+
+/// Step shortening set by the renderer, see [`LoweredScene::distortion`]
+const DISTORTION_UNIFORM: &str = "uniform float     u_distortion;\n\n";
+
 pub enum Shading {
     Matcap,
     Diffuse,
@@ -21,7 +37,9 @@ impl SDFBuilder {
         definitions.push_str(std::include_str!("sdf/strings/math.frag"));
         definitions.push_str(std::include_str!("sdf/strings/space.frag"));
         definitions.push_str(std::include_str!("sdf/strings/primitives.frag"));
+        definitions.push_str(std::include_str!("sdf/strings/primitives2d.frag"));
         definitions.push_str(std::include_str!("sdf/strings/ops.frag"));
+        definitions.push_str(std::include_str!("sdf/strings/material.frag"));
         definitions.push_str(std::include_str!("sdf/strings/ray.frag"));
         definitions.push_str(std::include_str!("sdf/strings/camera.frag"));
         Self {
@@ -44,6 +62,12 @@ impl SDFBuilder {
             Some(pos) => format!("rotate({}, vec3({}, {}, {}))", pos, rotation[0], rotation[1], rotation[2]),
         }
     }
+    pub fn scale<S: std::fmt::Display>(position: Option<S>, scale: [f32; 3]) -> String {
+        match position {
+            None => format!("scale(p, vec3({}, {}, {}))", scale[0], scale[1], scale[2]),
+            Some(pos) => format!("scale({}, vec3({}, {}, {}))", pos, scale[0], scale[1], scale[2]),
+        }
+    }
     pub fn p_box<S: std::fmt::Display>(position: Option<S>, size: [f32; 3], fillet: f32) -> String {
         match position {
             None => format!("sdf_box(p, vec3({}, {}, {})) - {}", size[0] - fillet, size[1] - fillet, size[2] - fillet, fillet),
@@ -90,26 +114,263 @@ impl SDFBuilder {
         self.scene = format!("op_int_smooth({}, {}, {})", self.scene, operand, smooth);
         self
     }
+    /// Lowers a position tree to a GLSL `vec3` expression with constants inlined
+    pub fn lower_position(position: &SDFPosition) -> String {
+        Lowering::inline().position(position)
+    }
+    /// Lowers a volume tree to a GLSL `float` distance expression with
+    /// constants and node groups inlined. Functions written in GLSL are
+    /// called but not defined, [`Self::build_volume`] adds their definitions.
+    pub fn lower_volume(volume: &SDFVolume) -> String {
+        Lowering::inline().volume(&volume.inlined())
+    }
+    /// Lowers a volume tree with every constant moved to a `u_params` slot.
+    /// The resulting scene only depends on the topology of the tree, `time`
+    /// is only used to bound the distortion of animated deformations.
+    pub fn lower_volume_params(volume: &SDFVolume, time: f32) -> LoweredScene {
+        let mut lowering = Lowering::with_params();
+        // Without materials the marcher keeps sampling the plain distance,
+        // otherwise the distance is read off the surface
+        let (scene, surface) = if volume.has_material() {
+            ("scene_surface(p).x".to_string(), lowering.surface(volume))
+        } else {
+            (lowering.volume(volume), DEFAULT_SURFACE.to_string())
+        };
+        let materials = lowering.material_function();
+        LoweredScene {
+            scene,
+            surface,
+            materials,
+            functions: lowering.functions(),
+            params: lowering.into_params(),
+            distortion: volume.distortion(time),
+        }
+    }
     pub fn build(&self) -> String {
-        let mut ans = self.prelude.to_owned();
-        ans.push_str(self.declarations.as_str());
-        ans.push_str(self.definitions.as_str());
-        ans.push_str("float scene(vec3 p) {\n    return ");
-        ans.push_str(self.scene.as_str());
-        ans.push_str(";}\n\n");
-        ans.push_str(self.main.as_str());
-
-        ans
+        self.build_with(&self.scene)
     }
     pub fn build_with(&self, scene: &str) -> String {
+        let materials = Lowering::inline().material_function();
+        self.assemble(DISTORTION_UNIFORM, "", scene, DEFAULT_SURFACE, &materials)
+    }
+    /// Joins the shader sections around the scene specific functions
+    fn assemble(
+        &self,
+        uniforms: &str,
+        functions: &str,
+        scene: &str,
+        surface: &str,
+        materials: &str,
+    ) -> String {
         let mut ans = self.prelude.to_owned();
+        ans.push_str(uniforms);
         ans.push_str(self.declarations.as_str());
         ans.push_str(self.definitions.as_str());
+        ans.push_str(functions);
         ans.push_str("float scene(vec3 p) {\n    return ");
         ans.push_str(scene);
         ans.push_str(";}\n\n");
+        ans.push_str("vec4 scene_surface(vec3 p) {\n    return ");
+        ans.push_str(surface);
+        ans.push_str(";}\n\n");
+        ans.push_str(materials);
+        ans.push('\n');
         ans.push_str(self.main.as_str());
 
         ans
     }
+    /// Builds the full fragment shader with `volume` at `time` as the scene
+    /// and its constants inlined, so it runs without any `u_params` or
+    /// `u_distortion`
+    pub fn build_volume(&self, volume: &SDFVolume, time: f32) -> String {
+        let distortion = format!(
+            "const float       u_distortion = {:?};\n\n",
+            volume.distortion(time)
+        );
+        let mut lowering = Lowering::inline();
+        let (scene, surface) = if volume.has_material() {
+            ("scene_surface(p).x".to_string(), lowering.surface(volume))
+        } else {
+            (lowering.volume(volume), DEFAULT_SURFACE.to_string())
+        };
+        let materials = lowering.material_function();
+        self.assemble(&distortion, &lowering.functions(), &scene, &surface, &materials)
+    }
+    /// Builds the full fragment shader for a scene lowered with `u_params` slots
+    pub fn build_lowered(&self, lowered: &LoweredScene) -> String {
+        let uniforms = format!(
+            "uniform float     u_params[{}];\n{}",
+            lowered.params.len().max(1),
+            DISTORTION_UNIFORM
+        );
+        self.assemble(
+            &uniforms,
+            &lowered.functions,
+            &lowered.scene,
+            &lowered.surface,
+            &lowered.materials,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        expr::{BinaryOp, Expr, UnaryOp, ValueKind},
+        ir::{Matcap, Material, SDFPosition, SDFVolume},
+        SDFBuilder,
+    };
+    use nalgebra::Vector3;
+    #[test]
+    fn lower_translated_sphere() {
+        let volume = SDFVolume::Sphere {
+            position: SDFPosition::Translate {
+                position: Box::new(SDFPosition::Origin),
+                translation: Vector3::new(1.0, 2.0, 3.0).into(),
+            },
+            radius: 0.5.into(),
+        };
+        assert_eq!(
+            SDFBuilder::lower_volume(&volume),
+            "sdf_sphere(translate(p, vec3(1, 2, 3)), 0.5)"
+        );
+    }
+    #[test]
+    fn lower_union() {
+        let sphere = SDFVolume::Sphere {
+            position: SDFPosition::Origin,
+            radius: 1.0.into(),
+        };
+        let volume = SDFVolume::Union {
+            a: Box::new(sphere.clone()),
+            b: Box::new(SDFVolume::Empty),
+        };
+        assert_eq!(
+            SDFBuilder::lower_volume(&volume),
+            "op_union(sdf_sphere(p, 1), 1e10)"
+        );
+    }
+    #[test]
+    fn lower_params_shares_source() {
+        let sphere = |radius: f32| SDFVolume::Sphere {
+            position: SDFPosition::Origin,
+            radius: radius.into(),
+        };
+        let small = SDFBuilder::lower_volume_params(&sphere(0.5), 0.0);
+        let large = SDFBuilder::lower_volume_params(&sphere(2.0), 0.0);
+        assert_eq!(small.scene, "sdf_sphere(p, u_params[0])");
+        assert_eq!(small.scene, large.scene);
+        assert_eq!(large.params, vec![2.0]);
+    }
+    #[test]
+    fn lower_animated_radius() {
+        let wave = Expr::unary(UnaryOp::Sin, Expr::Time);
+        let volume = SDFVolume::Sphere {
+            position: SDFPosition::Origin,
+            radius: Expr::binary(BinaryOp::Add, 1.0.into(), wave),
+        };
+        let lowered = SDFBuilder::lower_volume_params(&volume, 0.0);
+        assert_eq!(lowered.scene, "sdf_sphere(p, (u_params[0] + sin(u_time)))");
+        assert_eq!(lowered.params, vec![1.0]);
+    }
+    #[test]
+    fn lower_material_surface() {
+        let sphere = SDFVolume::Sphere {
+            position: SDFPosition::Origin,
+            radius: 1.0.into(),
+        };
+        let painted = SDFVolume::Material {
+            volume: Box::new(sphere.clone()),
+            material: Material {
+                matcap: Matcap::Metal,
+                ..Material::default()
+            },
+        };
+        let volume = SDFVolume::Union {
+            a: Box::new(painted),
+            b: Box::new(sphere),
+        };
+        let lowered = SDFBuilder::lower_volume_params(&volume, 0.0);
+        assert_eq!(lowered.scene, "scene_surface(p).x");
+        assert_eq!(
+            lowered.surface,
+            "op_union(op_material(sdf_sphere(p, u_params[0]), 1.0), op_material(sdf_sphere(p, u_params[1]), 0.0))"
+        );
+        assert!(lowered
+            .materials
+            .contains("case 1: return material(vec3(u_params[2], u_params[3], u_params[4]), u_params[5], 3);"));
+        let prelude = include_str!("sdf/strings/prelude.frag");
+        assert!(prelude.contains(&format!("u_matcaps[{}]", Matcap::ALL.len())));
+    }
+    #[test]
+    fn lower_group_once() {
+        let radius = Expr::Arg {
+            index: 0,
+            kind: ValueKind::Scalar,
+        };
+        let call = |x: f32, r: f32| {
+            let position = SDFPosition::Translate {
+                position: Box::new(SDFPosition::Origin),
+                translation: Vector3::new(x, 0.0, 0.0).into(),
+            };
+            SDFVolume::Call {
+                function: "Bolt".to_string(),
+                position,
+                args: vec![r.into()],
+                body: Box::new(SDFVolume::Sphere {
+                    position: SDFPosition::Origin,
+                    radius: radius.clone(),
+                }),
+            }
+        };
+        let volume = SDFVolume::Union {
+            a: Box::new(call(-1.0, 0.5)),
+            b: Box::new(call(1.0, 0.25)),
+        };
+        let lowered = SDFBuilder::lower_volume_params(&volume, 0.0);
+        assert_eq!(
+            lowered.functions,
+            "float group_bolt_0(vec3 p, float a0) {\n    return sdf_sphere(p, a0);\n}\n\n"
+        );
+        assert_eq!(
+            lowered.scene,
+            "op_union(group_bolt_0(translate(p, vec3(u_params[0], u_params[1], u_params[2])), u_params[3]), \
+             group_bolt_0(translate(p, vec3(u_params[4], u_params[5], u_params[6])), u_params[7]))"
+        );
+        assert_eq!(
+            SDFBuilder::lower_volume(&volume),
+            "op_union(sdf_sphere(translate(p, vec3(-1, 0, 0)), 0.5), sdf_sphere(translate(p, vec3(1, 0, 0)), 0.25))"
+        );
+        let shader = SDFBuilder::new().build_volume(&volume, 0.0);
+        assert!(shader.contains(&lowered.functions));
+        assert!(shader.contains(
+            "op_union(group_bolt_0(translate(p, vec3(-1, 0, 0)), 0.5), \
+             group_bolt_0(translate(p, vec3(1, 0, 0)), 0.25))"
+        ));
+    }
+    #[test]
+    fn distortion_takes_worst_primitive() {
+        let twisted = SDFVolume::Sphere {
+            position: SDFPosition::Twist {
+                position: Box::new(SDFPosition::Origin),
+                rate: 90.0.into(),
+            },
+            radius: 1.0.into(),
+        };
+        let volume = SDFVolume::Union {
+            a: Box::new(twisted),
+            b: Box::new(SDFVolume::Sphere {
+                position: SDFPosition::Origin,
+                radius: 1.0.into(),
+            }),
+        };
+        let expected = (1.0 + std::f32::consts::FRAC_PI_2.powi(2)).sqrt();
+        assert!((volume.distortion(0.0) - expected).abs() < 1e-6);
+        let lowered = SDFBuilder::lower_volume_params(&volume, 0.0);
+        assert_eq!(lowered.distortion, volume.distortion(0.0));
+        let shader = SDFBuilder::new().build_volume(&volume, 0.0);
+        let constant = format!("const float       u_distortion = {:?};", volume.distortion(0.0));
+        assert!(shader.contains(&constant));
+        assert!(!shader.contains("uniform float     u_distortion;"));
+    }
 }
@@ -0,0 +1,629 @@
+//! Evaluation of [`SDFVolume`] trees on the CPU.
+//!
+//! The shader is the reference, [`library`] ports the GLSL functions the
+//! lowered scene calls so that a model can be sampled without a GPU, for
+//! tests, queries and meshing. Node groups are evaluated through the volume
+//! with their arguments substituted. Functions written in GLSL by nodes have
+//! no Rust counterpart and are rejected up front.
+use nalgebra::{Vector2, Vector3};
+use thiserror::Error;
+
+use super::{
+    expr::Expr,
+    ir::{Material, SDFPosition, SDFVolume, SDF2D},
+};
+
+pub mod library;
+
+use library::*;
+
+/// Distance of an empty volume, the same as in the shader
+const EMPTY_DISTANCE: f32 = 1e10;
+
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum CpuError {
+    #[error("the GLSL function {0} can only be evaluated on the GPU")]
+    Glsl(String),
+}
+
+/// Distance and gradient of a field at a point
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Sample {
+    pub distance: f32,
+    /// Unit length for an exact distance field, pointing away from the surface
+    pub gradient: Vector3<f32>,
+}
+
+/// Distance at a point together with the materials blended between there,
+/// like the `vec4` surfaces of the shader. `None` is the default material.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Surface<'a> {
+    pub distance: f32,
+    pub a: Option<&'a Material>,
+    pub b: Option<&'a Material>,
+    /// Weight of `b`
+    pub blend: f32,
+}
+
+impl<'a> Surface<'a> {
+    fn new(distance: f32, material: Option<&'a Material>) -> Self {
+        Self {
+            distance,
+            a: material,
+            b: material,
+            blend: 0.0,
+        }
+    }
+    fn with_distance(self, distance: f32) -> Self {
+        Self { distance, ..self }
+    }
+    /// Material weighing the most, `dominant_material` in GLSL
+    pub fn material(&self) -> Option<&'a Material> {
+        if self.blend < 0.5 {
+            self.a
+        } else {
+            self.b
+        }
+    }
+}
+
+/// Samples a volume at a fixed playback time
+#[derive(Clone, Debug)]
+pub struct CpuEvaluator {
+    volume: SDFVolume,
+    time: f32,
+}
+
+impl CpuEvaluator {
+    /// Step of the central differences the gradient is estimated with, the
+    /// one `scene_normal` uses in the shader
+    pub const GRADIENT_STEP: f32 = 0.001;
+
+    pub fn new(volume: &SDFVolume, time: f32) -> Result<Self, CpuError> {
+        if let Some(name) = glsl_function(volume) {
+            return Err(CpuError::Glsl(name.to_string()));
+        }
+        Ok(Self {
+            volume: volume.inlined(),
+            time,
+        })
+    }
+
+    pub fn distance(&self, p: Vector3<f32>) -> f32 {
+        distance(&self.volume, p, self.time)
+    }
+
+    pub fn gradient(&self, p: Vector3<f32>) -> Vector3<f32> {
+        let h = Self::GRADIENT_STEP;
+        Vector3::from_fn(|i, _| {
+            let mut step = Vector3::zeros();
+            step[i] = h;
+            (self.distance(p + step) - self.distance(p - step)) / (2.0 * h)
+        })
+    }
+
+    pub fn sample(&self, p: Vector3<f32>) -> Sample {
+        Sample {
+            distance: self.distance(p),
+            gradient: self.gradient(p),
+        }
+    }
+
+    /// Distance and materials at `p`, `scene_surface` in GLSL
+    pub fn surface(&self, p: Vector3<f32>) -> Surface<'_> {
+        surface(&self.volume, p, self.time)
+    }
+
+    /// Linear color at `p`, blended like the shader blends materials
+    pub fn color(&self, p: Vector3<f32>) -> Vector3<f32> {
+        let surface = self.surface(p);
+        let color = |material: Option<&Material>| match material {
+            Some(material) => vec3(&material.color, self.time),
+            None => vec3(&Material::default().color, self.time),
+        };
+        color(surface.a).lerp(&color(surface.b), surface.blend)
+    }
+
+    /// Factor distances overestimate the true distance by, see
+    /// [`SDFVolume::distortion`]
+    pub fn distortion(&self) -> f32 {
+        self.volume.distortion(self.time)
+    }
+}
+
+/// Name of a function written in GLSL called anywhere in `volume`
+fn glsl_function(volume: &SDFVolume) -> Option<&str> {
+    match volume {
+        SDFVolume::Glsl { function, .. } => Some(&function.name),
+        SDFVolume::Union { a, b }
+        | SDFVolume::SmoothUnion { a, b, .. }
+        | SDFVolume::Diff { a, b }
+        | SDFVolume::SmoothDiff { a, b, .. }
+        | SDFVolume::Intersect { a, b }
+        | SDFVolume::SmoothIntersect { a, b, .. } => glsl_function(a).or_else(|| glsl_function(b)),
+        SDFVolume::Round { volume, .. }
+        | SDFVolume::Shell { volume, .. }
+        | SDFVolume::Hollow { volume }
+        | SDFVolume::Material { volume, .. } => glsl_function(volume),
+        SDFVolume::Call { body, .. } => glsl_function(body),
+        _ => None,
+    }
+}
+
+fn scalar(expr: &Expr, time: f32) -> f32 {
+    expr.eval(time).to_scalar()
+}
+fn vec2(expr: &Expr, time: f32) -> Vector2<f32> {
+    expr.eval(time).to_vec2()
+}
+fn vec3(expr: &Expr, time: f32) -> Vector3<f32> {
+    expr.eval(time).to_vec3()
+}
+
+/// Point the distance functions of `position` are sampled at
+fn domain(position: &SDFPosition, p: Vector3<f32>, time: f32) -> Vector3<f32> {
+    match position {
+        SDFPosition::Origin => p,
+        SDFPosition::Translate {
+            position,
+            translation,
+        } => translate(domain(position, p, time), vec3(translation, time)),
+        SDFPosition::Rotate { position, rotation } => {
+            rotate(domain(position, p, time), vec3(rotation, time))
+        }
+        SDFPosition::Scale {
+            position,
+            scale: factor,
+        } => scale(domain(position, p, time), vec3(factor, time)),
+        SDFPosition::Repeat { position, spacing } => {
+            repeat(domain(position, p, time), vec3(spacing, time))
+        }
+        SDFPosition::RepeatLimited {
+            position,
+            spacing,
+            count,
+        } => repeat_limited(
+            domain(position, p, time),
+            vec3(spacing, time),
+            vec3(count, time),
+        ),
+        SDFPosition::Mirror {
+            position,
+            normal,
+            offset,
+        } => mirror(
+            domain(position, p, time),
+            vec3(normal, time),
+            scalar(offset, time),
+        ),
+        SDFPosition::RepeatPolar {
+            position,
+            axis,
+            count,
+        } => repeat_polar(
+            domain(position, p, time),
+            vec3(axis, time),
+            scalar(count, time),
+        ),
+        SDFPosition::Twist { position, rate } => {
+            twist(domain(position, p, time), scalar(rate, time))
+        }
+        SDFPosition::Bend { position, rate } => bend(domain(position, p, time), scalar(rate, time)),
+        SDFPosition::Taper { position, rate } => {
+            taper(domain(position, p, time), scalar(rate, time))
+        }
+        SDFPosition::Elongate { position, size } => {
+            elongate(domain(position, p, time), vec3(size, time))
+        }
+    }
+}
+
+/// Distance from `p` to `volume`, the expression built by
+/// [`Lowering::volume`](super::lower::Lowering::volume)
+fn distance(volume: &SDFVolume, p: Vector3<f32>, time: f32) -> f32 {
+    let at = |position: &SDFPosition| domain(position, p, time);
+    let s = |expr: &Expr| scalar(expr, time);
+    let v = |expr: &Expr| vec3(expr, time);
+    match volume {
+        SDFVolume::Empty => EMPTY_DISTANCE,
+        SDFVolume::Sphere { position, radius } => sdf_sphere(at(position), s(radius)),
+        SDFVolume::Box {
+            position,
+            dimensions,
+            fillet,
+        } => {
+            let fillet = s(fillet);
+            sdf_box(at(position), v(dimensions).add_scalar(-fillet)) - fillet
+        }
+        SDFVolume::Cylinder {
+            position,
+            radius,
+            length,
+            fillet,
+        } => {
+            let fillet = s(fillet);
+            sdf_cylinder(at(position), s(length) - fillet, s(radius) - fillet) - fillet
+        }
+        SDFVolume::Torus {
+            position,
+            major_radius,
+            minor_radius,
+        } => sdf_torus(at(position), s(major_radius), s(minor_radius)),
+        SDFVolume::Capsule {
+            position,
+            length,
+            radius,
+        } => sdf_capsule(at(position), s(length), s(radius)),
+        SDFVolume::Cone {
+            position,
+            height,
+            radius,
+        } => sdf_cone(at(position), s(height), s(radius)),
+        SDFVolume::Plane {
+            position,
+            normal,
+            offset,
+        } => sdf_plane(at(position), v(normal), s(offset)),
+        SDFVolume::Ellipsoid { position, radii } => sdf_ellipsoid(at(position), v(radii)),
+        SDFVolume::HexPrism {
+            position,
+            radius,
+            length,
+        } => sdf_hex_prism(at(position), s(radius), s(length)),
+        SDFVolume::TriPrism {
+            position,
+            radius,
+            length,
+        } => sdf_tri_prism(at(position), s(radius), s(length)),
+        SDFVolume::RoundedCone {
+            position,
+            bottom_radius,
+            top_radius,
+            height,
+        } => sdf_rounded_cone(at(position), s(bottom_radius), s(top_radius), s(height)),
+        SDFVolume::Octahedron { position, size } => sdf_octahedron(at(position), s(size)),
+        SDFVolume::Link {
+            position,
+            length,
+            major_radius,
+            minor_radius,
+        } => sdf_link(at(position), s(length), s(major_radius), s(minor_radius)),
+        SDFVolume::Pyramid {
+            position,
+            base,
+            height,
+        } => sdf_pyramid(at(position), s(base), s(height)),
+        SDFVolume::Union { a, b } => op_union(distance(a, p, time), distance(b, p, time)),
+        SDFVolume::SmoothUnion { a, b, k } => {
+            op_union_smooth(distance(a, p, time), distance(b, p, time), s(k))
+        }
+        SDFVolume::Diff { a, b } => op_diff(distance(a, p, time), distance(b, p, time)),
+        SDFVolume::SmoothDiff { a, b, k } => {
+            op_diff_smooth(distance(a, p, time), distance(b, p, time), s(k))
+        }
+        SDFVolume::Intersect { a, b } => op_int(distance(a, p, time), distance(b, p, time)),
+        SDFVolume::SmoothIntersect { a, b, k } => {
+            op_int_smooth(distance(a, p, time), distance(b, p, time), s(k))
+        }
+        SDFVolume::Round {
+            volume: inner,
+            radius,
+        } => op_round(distance(inner, p, time), s(radius)),
+        SDFVolume::Shell {
+            volume: inner,
+            thickness,
+        } => op_shell(distance(inner, p, time), s(thickness)),
+        SDFVolume::Hollow { volume: inner } => op_hollow(distance(inner, p, time)),
+        SDFVolume::Extrude {
+            position,
+            shape,
+            height,
+            rounding,
+        } => {
+            let q = at(position);
+            op_extrude(profile(shape, q.xy(), time), q.z, s(height), s(rounding))
+        }
+        SDFVolume::Revolve {
+            position,
+            shape,
+            axis,
+            offset,
+        } => profile(shape, revolve(at(position), v(axis), s(offset)), time),
+        SDFVolume::Material { volume: inner, .. } => distance(inner, p, time),
+        // Inlined or rejected by `CpuEvaluator::new`
+        SDFVolume::Call { .. } | SDFVolume::Glsl { .. } => EMPTY_DISTANCE,
+    }
+}
+
+/// Distance from `p` to a profile, the expression built by
+/// [`Lowering::shape`](super::lower::Lowering::shape)
+fn profile(shape: &SDF2D, p: Vector2<f32>, time: f32) -> f32 {
+    let s = |expr: &Expr| scalar(expr, time);
+    let v = |expr: &Expr| vec2(expr, time);
+    match shape {
+        SDF2D::Empty => EMPTY_DISTANCE,
+        SDF2D::Circle { center, radius } => sdf2d_circle(p - v(center), s(radius)),
+        SDF2D::Rectangle { center, size } => sdf2d_rectangle(p - v(center), v(size)),
+        SDF2D::RoundedRectangle {
+            center,
+            size,
+            radius,
+        } => sdf2d_rounded_rectangle(p - v(center), v(size), s(radius)),
+        SDF2D::Polygon {
+            center,
+            radius,
+            sides,
+        } => sdf2d_polygon(p - v(center), s(radius), s(sides)),
+        SDF2D::Arc {
+            center,
+            radius,
+            angle,
+            thickness,
+        } => sdf2d_arc(p - v(center), s(radius), s(angle), s(thickness)),
+        SDF2D::Segment { a, b, thickness } => sdf2d_segment(p, v(a), v(b), s(thickness)),
+        SDF2D::Union { a, b } => op_union(profile(a, p, time), profile(b, p, time)),
+        SDF2D::Diff { a, b } => op_diff(profile(a, p, time), profile(b, p, time)),
+        SDF2D::Intersect { a, b } => op_int(profile(a, p, time), profile(b, p, time)),
+    }
+}
+
+/// Surface at `p`, the expression built by
+/// [`Lowering::surface`](super::lower::Lowering::surface)
+fn surface(volume: &SDFVolume, p: Vector3<f32>, time: f32) -> Surface<'_> {
+    match volume {
+        _ if !volume.has_material() => Surface::new(distance(volume, p, time), None),
+        SDFVolume::Material {
+            volume: inner,
+            material,
+        } => Surface::new(distance(inner, p, time), Some(material)),
+        SDFVolume::Union { a, b } => {
+            let a = surface(a, p, time);
+            let b = surface(b, p, time);
+            if a.distance < b.distance {
+                a
+            } else {
+                b
+            }
+        }
+        SDFVolume::SmoothUnion { a, b, k } => {
+            let k = scalar(k, time);
+            let a = surface(a, p, time);
+            let b = surface(b, p, time);
+            Surface {
+                distance: op_union_smooth(a.distance, b.distance, k),
+                a: b.material(),
+                b: a.material(),
+                blend: union_smooth_blend(a.distance, b.distance, k),
+            }
+        }
+        SDFVolume::Diff { a, b } => {
+            let a = surface(a, p, time);
+            a.with_distance(op_diff(a.distance, distance(b, p, time)))
+        }
+        SDFVolume::SmoothDiff { a, b, k } => {
+            let a = surface(a, p, time);
+            a.with_distance(op_diff_smooth(
+                a.distance,
+                distance(b, p, time),
+                scalar(k, time),
+            ))
+        }
+        SDFVolume::Intersect { a, b } => {
+            let a = surface(a, p, time);
+            let b = surface(b, p, time);
+            if a.distance > b.distance {
+                a
+            } else {
+                b
+            }
+        }
+        SDFVolume::SmoothIntersect { a, b, k } => {
+            let k = scalar(k, time);
+            let a = surface(a, p, time);
+            let b = surface(b, p, time);
+            Surface {
+                distance: op_int_smooth(a.distance, b.distance, k),
+                a: b.material(),
+                b: a.material(),
+                blend: int_smooth_blend(a.distance, b.distance, k),
+            }
+        }
+        SDFVolume::Round {
+            volume: inner,
+            radius,
+        } => {
+            let s = surface(inner, p, time);
+            s.with_distance(op_round(s.distance, scalar(radius, time)))
+        }
+        SDFVolume::Shell {
+            volume: inner,
+            thickness,
+        } => {
+            let s = surface(inner, p, time);
+            s.with_distance(op_shell(s.distance, scalar(thickness, time)))
+        }
+        SDFVolume::Hollow { volume: inner } => {
+            let s = surface(inner, p, time);
+            s.with_distance(op_hollow(s.distance))
+        }
+        // Primitives never contain a material
+        _ => Surface::new(distance(volume, p, time), None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::Vector3;
+
+    use super::{CpuError, CpuEvaluator};
+    use crate::sdf::{
+        expr::{Expr, ValueKind},
+        ir::{GlslFunction, Material, SDFPosition, SDFVolume},
+    };
+
+    fn sphere(x: f32, radius: f32) -> SDFVolume {
+        SDFVolume::Sphere {
+            position: SDFPosition::Translate {
+                position: Box::new(SDFPosition::Origin),
+                translation: Vector3::new(x, 0.0, 0.0).into(),
+            },
+            radius: radius.into(),
+        }
+    }
+
+    #[test]
+    fn distance_and_gradient() {
+        let cpu = CpuEvaluator::new(&sphere(1.0, 0.5), 0.0).unwrap();
+        let sample = cpu.sample(Vector3::new(1.0, 2.0, 0.0));
+        assert!((sample.distance - 1.5).abs() < 1e-6);
+        assert!((sample.gradient - Vector3::new(0.0, 1.0, 0.0)).norm() < 1e-3);
+
+        let cube = SDFVolume::Box {
+            position: SDFPosition::Rotate {
+                position: Box::new(SDFPosition::Origin),
+                rotation: Vector3::new(0.0, 0.0, 45.0).into(),
+            },
+            dimensions: Vector3::new(1.0, 1.0, 1.0).into(),
+            fillet: 0.0.into(),
+        };
+        let cpu = CpuEvaluator::new(&cube, 0.0).unwrap();
+        // A corner of the rotated cube lies on the x axis
+        let corner = 2f32.sqrt();
+        assert!(cpu.distance(Vector3::new(corner, 0.0, 0.0)).abs() < 1e-5);
+        assert!(cpu.distance(Vector3::new(0.0, 0.0, 1.0)).abs() < 1e-6);
+    }
+
+    #[test]
+    fn primitives_contain_their_center() {
+        let o = || SDFPosition::Origin;
+        let one = || Expr::from(1.0);
+        let primitives = [
+            SDFVolume::Cylinder {
+                position: o(),
+                radius: one(),
+                length: one(),
+                fillet: 0.1.into(),
+            },
+            SDFVolume::Torus {
+                position: SDFPosition::Translate {
+                    position: Box::new(o()),
+                    translation: Vector3::new(1.0, 0.0, 0.0).into(),
+                },
+                major_radius: one(),
+                minor_radius: 0.25.into(),
+            },
+            SDFVolume::Capsule {
+                position: o(),
+                length: one(),
+                radius: 0.5.into(),
+            },
+            SDFVolume::Cone {
+                position: o(),
+                height: one(),
+                radius: one(),
+            },
+            SDFVolume::Ellipsoid {
+                position: o(),
+                radii: Vector3::new(1.0, 2.0, 3.0).into(),
+            },
+            SDFVolume::HexPrism {
+                position: o(),
+                radius: one(),
+                length: one(),
+            },
+            SDFVolume::TriPrism {
+                position: o(),
+                radius: one(),
+                length: one(),
+            },
+            SDFVolume::RoundedCone {
+                position: o(),
+                bottom_radius: one(),
+                top_radius: 0.5.into(),
+                height: one(),
+            },
+            SDFVolume::Octahedron {
+                position: o(),
+                size: one(),
+            },
+            SDFVolume::Pyramid {
+                position: SDFPosition::Translate {
+                    position: Box::new(o()),
+                    translation: Vector3::new(0.0, -0.25, 0.0).into(),
+                },
+                base: one(),
+                height: one(),
+            },
+        ];
+        for primitive in primitives {
+            let cpu = CpuEvaluator::new(&primitive, 0.0).unwrap();
+            // Off the exact center, where the ellipsoid divides by zero
+            let center = Vector3::new(0.01, 0.02, 0.03);
+            assert!(cpu.distance(center) < 0.0, "{:?}", primitive);
+            let far = cpu.distance(Vector3::new(10.0, 10.0, 10.0));
+            assert!(far > 10.0 && far < 20.0, "{:?}", primitive);
+        }
+    }
+
+    #[test]
+    fn groups_evaluate_inlined() {
+        let call = SDFVolume::Call {
+            function: "Ball".to_string(),
+            position: SDFPosition::Origin,
+            args: vec![2.0.into()],
+            body: Box::new(SDFVolume::Sphere {
+                position: SDFPosition::Origin,
+                radius: Expr::Arg {
+                    index: 0,
+                    kind: ValueKind::Scalar,
+                },
+            }),
+        };
+        let volume = SDFVolume::SmoothUnion {
+            a: Box::new(call),
+            b: Box::new(sphere(4.0, 1.0)),
+            k: 0.5.into(),
+        };
+        let cpu = CpuEvaluator::new(&volume, 0.0).unwrap();
+        assert!((cpu.distance(Vector3::new(-3.0, 0.0, 0.0)) - 1.0).abs() < 1e-6);
+        // Halfway between the spheres the fillet pulls the surface closer
+        assert!(cpu.distance(Vector3::new(2.5, 0.0, 0.0)) < 0.5);
+    }
+
+    #[test]
+    fn materials_follow_the_closest_side() {
+        let metal = Material {
+            color: Vector3::new(1.0, 0.0, 0.0).into(),
+            ..Material::default()
+        };
+        let volume = SDFVolume::Union {
+            a: Box::new(SDFVolume::Material {
+                volume: Box::new(sphere(-2.0, 1.0)),
+                material: metal.clone(),
+            }),
+            b: Box::new(sphere(2.0, 1.0)),
+        };
+        let cpu = CpuEvaluator::new(&volume, 0.0).unwrap();
+        assert_eq!(
+            cpu.surface(Vector3::new(-2.0, 1.0, 0.0)).material(),
+            Some(&metal)
+        );
+        assert_eq!(cpu.surface(Vector3::new(2.0, 1.0, 0.0)).material(), None);
+    }
+
+    #[test]
+    fn glsl_is_rejected() {
+        let volume = SDFVolume::Glsl {
+            function: GlslFunction {
+                name: "blob".to_string(),
+                source: String::new(),
+            },
+            position: SDFPosition::Origin,
+            args: Vec::new(),
+        };
+        assert_eq!(
+            CpuEvaluator::new(&volume, 0.0).unwrap_err(),
+            CpuError::Glsl("blob".to_string())
+        );
+    }
+}
@@ -0,0 +1,382 @@
+//! Rust versions of the GLSL functions in `primitives.frag`,
+//! `primitives2d.frag`, `ops.frag` and `space.frag`, line by line where the
+//! languages allow. Keep both sides in sync when changing either.
+use std::f32::consts::PI;
+
+use nalgebra::{Matrix3, Vector2, Vector3};
+
+// GLSL built-ins that differ from their Rust namesakes
+
+/// `sign`, which is 0 for 0 unlike [`f32::signum`]
+fn sign(x: f32) -> f32 {
+    if x > 0.0 {
+        1.0
+    } else if x < 0.0 {
+        -1.0
+    } else {
+        0.0
+    }
+}
+
+/// `mod`, which takes the sign of `y` unlike `%`
+fn modulo(x: f32, y: f32) -> f32 {
+    x - y * (x / y).floor()
+}
+
+/// `clamp`, which unlike [`f32::clamp`] does not panic when `lo > hi`
+fn clamp(x: f32, lo: f32, hi: f32) -> f32 {
+    x.max(lo).min(hi)
+}
+
+fn mix(a: f32, b: f32, t: f32) -> f32 {
+    a + (b - a) * t
+}
+
+fn step(edge: f32, x: f32) -> f32 {
+    if x < edge {
+        0.0
+    } else {
+        1.0
+    }
+}
+
+/// `mat3` from its entries in GLSL order, column by column
+#[allow(clippy::too_many_arguments)]
+fn mat3(a: f32, b: f32, c: f32, d: f32, e: f32, f: f32, g: f32, h: f32, i: f32) -> Matrix3<f32> {
+    Matrix3::new(a, d, g, b, e, h, c, f, i)
+}
+
+fn vmax(v: Vector3<f32>) -> f32 {
+    v.max()
+}
+
+fn dot2(v: Vector2<f32>) -> f32 {
+    v.dot(&v)
+}
+
+fn max2(v: Vector2<f32>, m: f32) -> Vector2<f32> {
+    v.map(|x| x.max(m))
+}
+
+// primitives.frag
+
+pub fn sdf_sphere(p: Vector3<f32>, r: f32) -> f32 {
+    p.norm() - r
+}
+
+pub fn sdf_box(p: Vector3<f32>, r: Vector3<f32>) -> f32 {
+    let q = p.abs() - r;
+    q.map(|x| x.max(0.0)).norm() + vmax(q).min(0.0)
+}
+
+pub fn sdf_cylinder(p: Vector3<f32>, height: f32, radius: f32) -> f32 {
+    let d = Vector2::new(p.xz().norm(), p.y).abs() - Vector2::new(radius, height);
+    d.x.max(d.y).min(0.0) + max2(d, 0.0).norm()
+}
+
+pub fn sdf_torus(p: Vector3<f32>, major_radius: f32, minor_radius: f32) -> f32 {
+    let q = Vector2::new(p.xz().norm() - major_radius, p.y);
+    q.norm() - minor_radius
+}
+
+pub fn sdf_capsule(mut p: Vector3<f32>, height: f32, radius: f32) -> f32 {
+    p.y -= clamp(p.y, -height, height);
+    p.norm() - radius
+}
+
+pub fn sdf_capped_cone(p: Vector3<f32>, height: f32, r1: f32, r2: f32) -> f32 {
+    let q = Vector2::new(p.xz().norm(), p.y);
+    let k1 = Vector2::new(r2, height);
+    let k2 = Vector2::new(r2 - r1, 2.0 * height);
+    let ca = Vector2::new(
+        q.x - q.x.min(if q.y < 0.0 { r1 } else { r2 }),
+        q.y.abs() - height,
+    );
+    let cb = q - k1 + k2 * clamp((k1 - q).dot(&k2) / dot2(k2), 0.0, 1.0);
+    let s = if cb.x < 0.0 && ca.y < 0.0 { -1.0 } else { 1.0 };
+    s * dot2(ca).min(dot2(cb)).sqrt()
+}
+
+pub fn sdf_cone(p: Vector3<f32>, height: f32, radius: f32) -> f32 {
+    sdf_capped_cone(p, height, radius, 0.0)
+}
+
+pub fn sdf_plane(p: Vector3<f32>, normal: Vector3<f32>, offset: f32) -> f32 {
+    p.dot(&normal.normalize()) - offset
+}
+
+pub fn sdf_ellipsoid(p: Vector3<f32>, r: Vector3<f32>) -> f32 {
+    let k0 = p.component_div(&r).norm();
+    let k1 = p.component_div(&r.component_mul(&r)).norm();
+    k0 * (k0 - 1.0) / k1
+}
+
+pub fn sdf_hex_prism(p: Vector3<f32>, radius: f32, height: f32) -> f32 {
+    let k = Vector3::new(-0.8660254, 0.5, 0.57735);
+    let mut p = p.abs();
+    let xy = p.xy() - 2.0 * k.xy().dot(&p.xy()).min(0.0) * k.xy();
+    p.x = xy.x;
+    p.y = xy.y;
+    let d = Vector2::new(
+        (p.xy() - Vector2::new(clamp(p.x, -k.z * radius, k.z * radius), radius)).norm()
+            * sign(p.y - radius),
+        p.z - height,
+    );
+    d.x.max(d.y).min(0.0) + max2(d, 0.0).norm()
+}
+
+pub fn sdf_tri_prism(p: Vector3<f32>, radius: f32, height: f32) -> f32 {
+    let q = p.abs();
+    (q.z - height).max((q.x * 0.866025 + p.y * 0.5).max(-p.y) - radius * 0.5)
+}
+
+pub fn sdf_rounded_cone(p: Vector3<f32>, r1: f32, r2: f32, height: f32) -> f32 {
+    // a cone of no height is a sphere, keep the slope finite
+    let height = height.max(1e-4);
+    let b = (r1 - r2) / height;
+    let a = (1.0 - b * b).sqrt();
+    let q = Vector2::new(p.xz().norm(), p.y);
+    let k = q.dot(&Vector2::new(-b, a));
+    if k < 0.0 {
+        return q.norm() - r1;
+    }
+    if k > a * height {
+        return (q - Vector2::new(0.0, height)).norm() - r2;
+    }
+    q.dot(&Vector2::new(a, b)) - r1
+}
+
+pub fn sdf_octahedron(p: Vector3<f32>, size: f32) -> f32 {
+    let p = p.abs();
+    let m = p.x + p.y + p.z - size;
+    let q = if 3.0 * p.x < m {
+        p.xyz()
+    } else if 3.0 * p.y < m {
+        p.yzx()
+    } else if 3.0 * p.z < m {
+        p.zxy()
+    } else {
+        return m * 0.57735027;
+    };
+    let k = clamp(0.5 * (q.z - q.y + size), 0.0, size);
+    Vector3::new(q.x, q.y - size + k, q.z - k).norm()
+}
+
+pub fn sdf_link(p: Vector3<f32>, len: f32, major_radius: f32, minor_radius: f32) -> f32 {
+    let q = Vector3::new(p.x, (p.y.abs() - len).max(0.0), p.z);
+    Vector2::new(q.xy().norm() - major_radius, q.z).norm() - minor_radius
+}
+
+pub fn sdf_pyramid(p: Vector3<f32>, base: f32, height: f32) -> f32 {
+    // unit base pyramid scaled to the requested base width, which must not
+    // be zero
+    let base = base.max(1e-4);
+    let mut p = p / base;
+    let h = height / base;
+    let m2 = h * h + 0.25;
+    p.x = p.x.abs();
+    p.z = p.z.abs();
+    if p.z > p.x {
+        p.swap_rows(0, 2);
+    }
+    p.x -= 0.5;
+    p.z -= 0.5;
+    let q = Vector3::new(p.z, h * p.y - 0.5 * p.x, h * p.x + 0.5 * p.y);
+    let s = (-q.x).max(0.0);
+    let t = clamp((q.y - 0.5 * p.z) / (m2 + 0.25), 0.0, 1.0);
+    let a = m2 * (q.x + s) * (q.x + s) + q.y * q.y;
+    let b = m2 * (q.x + 0.5 * t) * (q.x + 0.5 * t) + (q.y - m2 * t) * (q.y - m2 * t);
+    let d2 = if q.y.min(-q.x * m2 - q.y * 0.5) > 0.0 {
+        0.0
+    } else {
+        a.min(b)
+    };
+    base * ((d2 + q.z * q.z) / m2).sqrt() * sign(q.z.max(-p.y))
+}
+
+// primitives2d.frag
+
+pub fn sdf2d_circle(p: Vector2<f32>, radius: f32) -> f32 {
+    p.norm() - radius
+}
+
+pub fn sdf2d_rectangle(p: Vector2<f32>, size: Vector2<f32>) -> f32 {
+    let d = p.abs() - size;
+    max2(d, 0.0).norm() + d.x.max(d.y).min(0.0)
+}
+
+pub fn sdf2d_rounded_rectangle(p: Vector2<f32>, size: Vector2<f32>, radius: f32) -> f32 {
+    sdf2d_rectangle(p, size.add_scalar(-radius)) - radius
+}
+
+// regular polygon with its corners on a circle of `radius`
+pub fn sdf2d_polygon(p: Vector2<f32>, radius: f32, sides: f32) -> f32 {
+    let an = PI / sides.round().max(3.0);
+    let acs = Vector2::new(an.cos(), an.sin());
+    let bn = modulo(p.x.atan2(p.y), 2.0 * an) - an;
+    let mut p = p.norm() * Vector2::new(bn.cos(), bn.sin().abs());
+    p -= radius * acs;
+    p.y += clamp(-p.y, 0.0, radius * acs.y);
+    p.norm() * sign(p.x)
+}
+
+// arc of `angle` degrees opening symmetrically around the +y axis
+pub fn sdf2d_arc(mut p: Vector2<f32>, radius: f32, angle: f32, thickness: f32) -> f32 {
+    let a = clamp(angle, 0.0, 360.0).to_radians() * 0.5;
+    let sc = Vector2::new(a.sin(), a.cos());
+    p.x = p.x.abs();
+    let d = if sc.y * p.x > sc.x * p.y {
+        (p - sc * radius).norm()
+    } else {
+        (p.norm() - radius).abs()
+    };
+    d - thickness
+}
+
+pub fn sdf2d_segment(p: Vector2<f32>, a: Vector2<f32>, b: Vector2<f32>, thickness: f32) -> f32 {
+    let pa = p - a;
+    let ba = b - a;
+    let h = clamp(pa.dot(&ba) / ba.dot(&ba).max(1e-12), 0.0, 1.0);
+    (pa - ba * h).norm() - thickness
+}
+
+// `d` is the distance to the profile in the xy plane, the extrusion is centered on z = 0
+pub fn op_extrude(d: f32, z: f32, height: f32, rounding: f32) -> f32 {
+    let w = Vector2::new(d + rounding, z.abs() - height * 0.5 + rounding);
+    w.x.max(w.y).min(0.0) + max2(w, 0.0).norm() - rounding
+}
+
+// profile coordinates of `p` revolved around `axis`: the distance from the
+// axis less `offset` along x and the height along the axis along y
+pub fn revolve(p: Vector3<f32>, axis: Vector3<f32>, offset: f32) -> Vector2<f32> {
+    let a = axis.normalize();
+    let h = p.dot(&a);
+    Vector2::new((p - h * a).norm() - offset, h)
+}
+
+// ops.frag
+
+pub fn op_union_smooth(d1: f32, d2: f32, k: f32) -> f32 {
+    let h = clamp(0.5 + 0.5 * (d2 - d1) / k, 0.0, 1.0);
+    mix(d2, d1, h) - k * h * (1.0 - h)
+}
+pub fn op_union(d1: f32, d2: f32) -> f32 {
+    d1.min(d2)
+}
+pub fn op_diff_smooth(d1: f32, d2: f32, k: f32) -> f32 {
+    let h = clamp(0.5 - 0.5 * (-d2 - d1) / k, 0.0, 1.0);
+    mix(-d2, d1, h) + k * h * (1.0 - h)
+}
+pub fn op_diff(d1: f32, d2: f32) -> f32 {
+    d1.max(-d2)
+}
+pub fn op_int_smooth(d1: f32, d2: f32, k: f32) -> f32 {
+    let h = clamp(0.5 - 0.5 * (d2 - d1) / k, 0.0, 1.0);
+    mix(d2, d1, h) + k * h * (1.0 - h)
+}
+pub fn op_int(d1: f32, d2: f32) -> f32 {
+    d1.max(d2)
+}
+pub fn op_round(d: f32, radius: f32) -> f32 {
+    d - radius
+}
+pub fn op_shell(d: f32, thickness: f32) -> f32 {
+    d.abs() - thickness
+}
+pub fn op_hollow(d: f32) -> f32 {
+    d.abs()
+}
+
+/// Blend factor of `op_union_smooth` on surfaces
+pub fn union_smooth_blend(d1: f32, d2: f32, k: f32) -> f32 {
+    clamp(0.5 + 0.5 * (d2 - d1) / k, 0.0, 1.0)
+}
+/// Blend factor of `op_int_smooth` on surfaces
+pub fn int_smooth_blend(d1: f32, d2: f32, k: f32) -> f32 {
+    clamp(0.5 - 0.5 * (d2 - d1) / k, 0.0, 1.0)
+}
+
+// space.frag
+
+pub fn translate(p: Vector3<f32>, translation: Vector3<f32>) -> Vector3<f32> {
+    p - translation
+}
+
+pub fn rotate(p: Vector3<f32>, rotation: Vector3<f32>) -> Vector3<f32> {
+    let (sx, cx) = rotation.x.to_radians().sin_cos();
+    let (sy, cy) = rotation.y.to_radians().sin_cos();
+    let (sz, cz) = rotation.z.to_radians().sin_cos();
+    mat3(1.0, 0.0, 0.0, 0.0, cx, -sx, 0.0, sx, cx)
+        * mat3(cy, 0.0, sy, 0.0, 1.0, 0.0, -sy, 0.0, cy)
+        * mat3(cz, -sz, 0.0, sz, cz, 0.0, 0.0, 0.0, 1.0)
+        * p
+}
+
+pub fn scale(p: Vector3<f32>, scale: Vector3<f32>) -> Vector3<f32> {
+    p.component_mul(&scale)
+}
+
+// axes with a spacing of zero are not repeated
+pub fn repeat(p: Vector3<f32>, spacing: Vector3<f32>) -> Vector3<f32> {
+    Vector3::from_fn(|i, _| {
+        let s = spacing[i].max(1e-6);
+        let q = p[i] - s * (p[i] / s).round();
+        mix(p[i], q, step(1e-6, spacing[i]))
+    })
+}
+
+// `count` copies along each axis, centered on the origin
+pub fn repeat_limited(p: Vector3<f32>, spacing: Vector3<f32>, count: Vector3<f32>) -> Vector3<f32> {
+    Vector3::from_fn(|i, _| {
+        let s = spacing[i].max(1e-6);
+        let n = count[i].round().max(1.0) - 1.0;
+        let mut q = p[i] + s * n * 0.5;
+        q -= s * clamp((q / s).round(), 0.0, n);
+        mix(p[i], q, step(1e-6, spacing[i]))
+    })
+}
+
+// points behind the plane are reflected onto the front side
+pub fn mirror(p: Vector3<f32>, normal: Vector3<f32>, offset: f32) -> Vector3<f32> {
+    let n = normal.normalize();
+    p - 2.0 * (p.dot(&n) - offset).min(0.0) * n
+}
+
+// `count` copies spaced evenly around `axis`, the first one in the direction of the local x axis
+pub fn repeat_polar(p: Vector3<f32>, axis: Vector3<f32>, count: f32) -> Vector3<f32> {
+    let a = axis.normalize();
+    let up = if a.y.abs() < 0.999 {
+        Vector3::new(0.0, 1.0, 0.0)
+    } else {
+        Vector3::new(0.0, 0.0, 1.0)
+    };
+    let t = up.cross(&a).normalize();
+    let b = a.cross(&t);
+    let q = Vector2::new(p.dot(&t), p.dot(&b));
+    let sector = 2.0 * PI / count.round().max(1.0);
+    let angle = modulo(q.y.atan2(q.x) + sector * 0.5, sector) - sector * 0.5;
+    let q = q.norm() * Vector2::new(angle.cos(), angle.sin());
+    q.x * t + q.y * b + p.dot(&a) * a
+}
+
+// deformations along the y axis, `rate` is in degrees per unit length
+pub fn twist(p: Vector3<f32>, rate: f32) -> Vector3<f32> {
+    let (s, c) = (rate.to_radians() * p.y).sin_cos();
+    Vector3::new(c * p.x - s * p.z, p.y, s * p.x + c * p.z)
+}
+
+pub fn bend(p: Vector3<f32>, rate: f32) -> Vector3<f32> {
+    let (s, c) = (rate.to_radians() * p.y).sin_cos();
+    Vector3::new(c * p.x - s * p.y, s * p.x + c * p.y, p.z)
+}
+
+// cross sections grow by `rate` per unit length up the y axis
+pub fn taper(p: Vector3<f32>, rate: f32) -> Vector3<f32> {
+    let s = (1.0 + rate * p.y).max(0.05);
+    Vector3::new(p.x / s, p.y, p.z / s)
+}
+
+// stretches the shape by inserting `size` worth of space at its center on each axis
+pub fn elongate(p: Vector3<f32>, size: Vector3<f32>) -> Vector3<f32> {
+    p - Vector3::from_fn(|i, _| clamp(p[i], -size[i], size[i]))
+}
@@ -0,0 +1,523 @@
+//! Scalar and vector parameters of an SDF model.
+//!
+//! Most parameters are constants, but some depend on values only known while
+//! rendering, like the playback time. Those are kept as expression trees which
+//! are folded to constants wherever possible and otherwise lowered to GLSL.
+use nalgebra::{Vector2, Vector3};
+
+/// Type of a value, ordered so that the wider of two kinds is the larger one
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "persistence", derive(serde::Serialize, serde::Deserialize))]
+pub enum ValueKind {
+    Scalar,
+    Vec2,
+    Vec3,
+}
+
+impl ValueKind {
+    /// GLSL type name
+    pub fn glsl(&self) -> &'static str {
+        match self {
+            ValueKind::Scalar => "float",
+            ValueKind::Vec2 => "vec2",
+            ValueKind::Vec3 => "vec3",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "persistence", derive(serde::Serialize, serde::Deserialize))]
+pub enum Value {
+    Scalar(f32),
+    Vec2(Vector2<f32>),
+    Vec3(Vector3<f32>),
+}
+
+impl Value {
+    pub fn kind(&self) -> ValueKind {
+        match self {
+            Value::Scalar(_) => ValueKind::Scalar,
+            Value::Vec2(_) => ValueKind::Vec2,
+            Value::Vec3(_) => ValueKind::Vec3,
+        }
+    }
+    /// The value itself for scalars, the x component for vectors
+    pub fn to_scalar(self) -> f32 {
+        match self {
+            Value::Scalar(value) => value,
+            Value::Vec2(value) => value.x,
+            Value::Vec3(value) => value.x,
+        }
+    }
+    /// Scalars are splat across both components, a vec3 loses its z component
+    pub fn to_vec2(self) -> Vector2<f32> {
+        match self {
+            Value::Scalar(value) => Vector2::repeat(value),
+            Value::Vec2(value) => value,
+            Value::Vec3(value) => value.xy(),
+        }
+    }
+    /// Scalars are splat across all components, a vec2 gets a zero z component
+    pub fn to_vec3(self) -> Vector3<f32> {
+        match self {
+            Value::Scalar(value) => Vector3::repeat(value),
+            Value::Vec2(value) => Vector3::new(value.x, value.y, 0.0),
+            Value::Vec3(value) => value,
+        }
+    }
+    /// Converts to `kind` the same way GLSL constructors do
+    pub fn to_kind(self, kind: ValueKind) -> Value {
+        match kind {
+            ValueKind::Scalar => Value::Scalar(self.to_scalar()),
+            ValueKind::Vec2 => Value::Vec2(self.to_vec2()),
+            ValueKind::Vec3 => Value::Vec3(self.to_vec3()),
+        }
+    }
+    /// Blends linearly towards `other`, a scalar is splatted to the wider kind
+    pub fn lerp(self, other: Value, t: f32) -> Value {
+        self.zip(other, |a, b| a + (b - a) * t)
+    }
+    fn map(self, f: impl Fn(f32) -> f32) -> Value {
+        match self {
+            Value::Scalar(value) => Value::Scalar(f(value)),
+            Value::Vec2(value) => Value::Vec2(value.map(f)),
+            Value::Vec3(value) => Value::Vec3(value.map(f)),
+        }
+    }
+    /// Component-wise combination, a scalar operand is splat to the other's kind
+    fn zip(self, other: Value, f: impl Fn(f32, f32) -> f32) -> Value {
+        match self.kind().max(other.kind()) {
+            ValueKind::Scalar => Value::Scalar(f(self.to_scalar(), other.to_scalar())),
+            ValueKind::Vec2 => Value::Vec2(self.to_vec2().zip_map(&other.to_vec2(), f)),
+            ValueKind::Vec3 => Value::Vec3(self.to_vec3().zip_map(&other.to_vec3(), f)),
+        }
+    }
+    fn length(self) -> f32 {
+        match self {
+            Value::Scalar(value) => value.abs(),
+            Value::Vec2(value) => value.magnitude(),
+            Value::Vec3(value) => value.magnitude(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "persistence", derive(serde::Serialize, serde::Deserialize))]
+pub enum UnaryOp {
+    Neg,
+    Abs,
+    Sin,
+    Cos,
+    Floor,
+    Fract,
+    Sqrt,
+    /// Scalar length of a vector
+    Length,
+    Normalize,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "persistence", derive(serde::Serialize, serde::Deserialize))]
+pub enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Min,
+    Max,
+    Pow,
+    /// Scalar dot product of two vectors of the same kind
+    Dot,
+    Cross,
+}
+
+/// Scalar or vector valued expression. Operands of component-wise operations
+/// may mix scalars and vectors, the scalar is then splat across the vector.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "persistence", derive(serde::Serialize, serde::Deserialize))]
+pub enum Expr {
+    Const(Value),
+    /// Seconds of playback, `u_time` in GLSL
+    Time,
+    /// Argument `index` of the GLSL function generated for a node group,
+    /// `a{index}` in GLSL. Only meaningful inside [`SDFVolume::Call`].
+    ///
+    /// [`SDFVolume::Call`]: super::ir::SDFVolume::Call
+    Arg {
+        index: usize,
+        kind: ValueKind,
+    },
+    Unary {
+        op: UnaryOp,
+        a: Box<Expr>,
+    },
+    Binary {
+        op: BinaryOp,
+        a: Box<Expr>,
+        b: Box<Expr>,
+    },
+    Clamp {
+        value: Box<Expr>,
+        min: Box<Expr>,
+        max: Box<Expr>,
+    },
+    /// Linear interpolation from `a` to `b` by `t`
+    Mix {
+        a: Box<Expr>,
+        b: Box<Expr>,
+        t: Box<Expr>,
+    },
+    Vec2 {
+        x: Box<Expr>,
+        y: Box<Expr>,
+    },
+    Vec3 {
+        x: Box<Expr>,
+        y: Box<Expr>,
+        z: Box<Expr>,
+    },
+}
+
+impl Default for Expr {
+    fn default() -> Self {
+        Self::Const(Value::Scalar(0.0))
+    }
+}
+
+impl From<f32> for Expr {
+    fn from(value: f32) -> Self {
+        Self::Const(Value::Scalar(value))
+    }
+}
+
+impl From<Vector2<f32>> for Expr {
+    fn from(value: Vector2<f32>) -> Self {
+        Self::Const(Value::Vec2(value))
+    }
+}
+
+impl From<Vector3<f32>> for Expr {
+    fn from(value: Vector3<f32>) -> Self {
+        Self::Const(Value::Vec3(value))
+    }
+}
+
+impl From<Value> for Expr {
+    fn from(value: Value) -> Self {
+        Self::Const(value)
+    }
+}
+
+impl Expr {
+    pub fn unary(op: UnaryOp, a: Expr) -> Self {
+        Self::Unary { op, a: Box::new(a) }.folded()
+    }
+    pub fn binary(op: BinaryOp, a: Expr, b: Expr) -> Self {
+        Self::Binary {
+            op,
+            a: Box::new(a),
+            b: Box::new(b),
+        }
+        .folded()
+    }
+    pub fn clamp(value: Expr, min: Expr, max: Expr) -> Self {
+        Self::Clamp {
+            value: Box::new(value),
+            min: Box::new(min),
+            max: Box::new(max),
+        }
+        .folded()
+    }
+    pub fn mix(a: Expr, b: Expr, t: Expr) -> Self {
+        Self::Mix {
+            a: Box::new(a),
+            b: Box::new(b),
+            t: Box::new(t),
+        }
+        .folded()
+    }
+    pub fn vec2(x: Expr, y: Expr) -> Self {
+        Self::Vec2 {
+            x: Box::new(x),
+            y: Box::new(y),
+        }
+        .folded()
+    }
+    pub fn vec3(x: Expr, y: Expr, z: Expr) -> Self {
+        Self::Vec3 {
+            x: Box::new(x),
+            y: Box::new(y),
+            z: Box::new(z),
+        }
+        .folded()
+    }
+
+    /// Replaces the expression by its value if it does not depend on anything
+    /// only known while rendering
+    pub fn folded(self) -> Self {
+        match self.constant() {
+            Some(value) => Self::Const(value),
+            None => self,
+        }
+    }
+
+    /// The value of the expression if it is the same at every time
+    pub fn constant(&self) -> Option<Value> {
+        if self.is_constant() {
+            Some(self.eval(0.0))
+        } else {
+            None
+        }
+    }
+
+    pub fn is_constant(&self) -> bool {
+        match self {
+            Expr::Const(_) => true,
+            Expr::Time | Expr::Arg { .. } => false,
+            Expr::Unary { a, .. } => a.is_constant(),
+            Expr::Binary { a, b, .. } | Expr::Vec2 { x: a, y: b } => {
+                a.is_constant() && b.is_constant()
+            }
+            Expr::Clamp {
+                value: a,
+                min: b,
+                max: c,
+            }
+            | Expr::Mix { a, b, t: c }
+            | Expr::Vec3 { x: a, y: b, z: c } => {
+                a.is_constant() && b.is_constant() && c.is_constant()
+            }
+        }
+    }
+
+    pub fn kind(&self) -> ValueKind {
+        match self {
+            Expr::Const(value) => value.kind(),
+            Expr::Time => ValueKind::Scalar,
+            Expr::Arg { kind, .. } => *kind,
+            Expr::Unary {
+                op: UnaryOp::Length,
+                ..
+            } => ValueKind::Scalar,
+            Expr::Unary { a, .. } => a.kind(),
+            Expr::Binary {
+                op: BinaryOp::Dot, ..
+            } => ValueKind::Scalar,
+            Expr::Binary {
+                op: BinaryOp::Cross,
+                ..
+            } => ValueKind::Vec3,
+            Expr::Binary { a, b, .. } => a.kind().max(b.kind()),
+            Expr::Clamp { value, min, max } => value.kind().max(min.kind()).max(max.kind()),
+            Expr::Mix { a, b, .. } => a.kind().max(b.kind()),
+            Expr::Vec2 { .. } => ValueKind::Vec2,
+            Expr::Vec3 { .. } => ValueKind::Vec3,
+        }
+    }
+
+    /// Whether the expression reads a group function argument
+    pub fn uses_args(&self) -> bool {
+        match self {
+            Expr::Const(_) | Expr::Time => false,
+            Expr::Arg { .. } => true,
+            Expr::Unary { a, .. } => a.uses_args(),
+            Expr::Binary { a, b, .. } | Expr::Vec2 { x: a, y: b } => a.uses_args() || b.uses_args(),
+            Expr::Clamp {
+                value: a,
+                min: b,
+                max: c,
+            }
+            | Expr::Mix { a, b, t: c }
+            | Expr::Vec3 { x: a, y: b, z: c } => a.uses_args() || b.uses_args() || c.uses_args(),
+        }
+    }
+
+    /// The expression with each [`Expr::Arg`] replaced by its value in
+    /// `args`, folded where that makes it constant
+    pub fn substitute(&self, args: &[Expr]) -> Expr {
+        match self {
+            Expr::Const(_) | Expr::Time => self.clone(),
+            Expr::Arg { index, .. } => args.get(*index).cloned().unwrap_or_else(|| self.clone()),
+            Expr::Unary { op, a } => Expr::unary(*op, a.substitute(args)),
+            Expr::Binary { op, a, b } => Expr::binary(*op, a.substitute(args), b.substitute(args)),
+            Expr::Clamp { value, min, max } => Expr::clamp(
+                value.substitute(args),
+                min.substitute(args),
+                max.substitute(args),
+            ),
+            Expr::Mix { a, b, t } => {
+                Expr::mix(a.substitute(args), b.substitute(args), t.substitute(args))
+            }
+            Expr::Vec2 { x, y } => Expr::vec2(x.substitute(args), y.substitute(args)),
+            Expr::Vec3 { x, y, z } => {
+                Expr::vec3(x.substitute(args), y.substitute(args), z.substitute(args))
+            }
+        }
+    }
+
+    /// Value of the expression at `time` seconds of playback. Arguments have
+    /// no value outside their function and evaluate to zero.
+    pub fn eval(&self, time: f32) -> Value {
+        match self {
+            Expr::Const(value) => *value,
+            Expr::Time => Value::Scalar(time),
+            Expr::Arg { kind, .. } => Value::Scalar(0.0).to_kind(*kind),
+            Expr::Unary { op, a } => {
+                let a = a.eval(time);
+                match op {
+                    UnaryOp::Neg => a.map(|a| -a),
+                    UnaryOp::Abs => a.map(f32::abs),
+                    UnaryOp::Sin => a.map(f32::sin),
+                    UnaryOp::Cos => a.map(f32::cos),
+                    UnaryOp::Floor => a.map(f32::floor),
+                    UnaryOp::Fract => a.map(|a| a - a.floor()),
+                    UnaryOp::Sqrt => a.map(f32::sqrt),
+                    UnaryOp::Length => Value::Scalar(a.length()),
+                    UnaryOp::Normalize => {
+                        let length = a.length();
+                        a.map(|a| a / length)
+                    }
+                }
+            }
+            Expr::Binary { op, a, b } => {
+                let a = a.eval(time);
+                let b = b.eval(time);
+                match op {
+                    BinaryOp::Add => a.zip(b, |a, b| a + b),
+                    BinaryOp::Sub => a.zip(b, |a, b| a - b),
+                    BinaryOp::Mul => a.zip(b, |a, b| a * b),
+                    BinaryOp::Div => a.zip(b, |a, b| a / b),
+                    BinaryOp::Min => a.zip(b, f32::min),
+                    BinaryOp::Max => a.zip(b, f32::max),
+                    BinaryOp::Pow => a.zip(b, f32::powf),
+                    BinaryOp::Dot => {
+                        let product = a.zip(b, |a, b| a * b);
+                        Value::Scalar(match product {
+                            Value::Scalar(value) => value,
+                            Value::Vec2(value) => value.sum(),
+                            Value::Vec3(value) => value.sum(),
+                        })
+                    }
+                    BinaryOp::Cross => Value::Vec3(a.to_vec3().cross(&b.to_vec3())),
+                }
+            }
+            Expr::Clamp { value, min, max } => {
+                let value = value.eval(time);
+                value
+                    .zip(min.eval(time), f32::max)
+                    .zip(max.eval(time), f32::min)
+            }
+            Expr::Mix { a, b, t } => {
+                let a = a.eval(time);
+                let b = b.eval(time);
+                b.zip(a, |b, a| b - a)
+                    .zip(t.eval(time), |d, t| d * t)
+                    .zip(a, |d, a| a + d)
+            }
+            Expr::Vec2 { x, y } => Value::Vec2(Vector2::new(
+                x.eval(time).to_scalar(),
+                y.eval(time).to_scalar(),
+            )),
+            Expr::Vec3 { x, y, z } => Value::Vec3(Vector3::new(
+                x.eval(time).to_scalar(),
+                y.eval(time).to_scalar(),
+                z.eval(time).to_scalar(),
+            )),
+        }
+    }
+}
+
+impl std::ops::Add for Expr {
+    type Output = Expr;
+    fn add(self, rhs: Expr) -> Expr {
+        Expr::binary(BinaryOp::Add, self, rhs)
+    }
+}
+
+impl std::ops::Sub for Expr {
+    type Output = Expr;
+    fn sub(self, rhs: Expr) -> Expr {
+        Expr::binary(BinaryOp::Sub, self, rhs)
+    }
+}
+
+impl std::ops::Mul for Expr {
+    type Output = Expr;
+    fn mul(self, rhs: Expr) -> Expr {
+        Expr::binary(BinaryOp::Mul, self, rhs)
+    }
+}
+
+impl std::ops::Div for Expr {
+    type Output = Expr;
+    fn div(self, rhs: Expr) -> Expr {
+        Expr::binary(BinaryOp::Div, self, rhs)
+    }
+}
+
+impl std::ops::Neg for Expr {
+    type Output = Expr;
+    fn neg(self) -> Expr {
+        Expr::unary(UnaryOp::Neg, self)
+    }
+}
+
+impl Expr {
+    pub fn dot(self, other: Expr) -> Expr {
+        Expr::binary(BinaryOp::Dot, self, other)
+    }
+    pub fn cross(self, other: Expr) -> Expr {
+        Expr::binary(BinaryOp::Cross, self, other)
+    }
+    pub fn min(self, other: Expr) -> Expr {
+        Expr::binary(BinaryOp::Min, self, other)
+    }
+    pub fn max(self, other: Expr) -> Expr {
+        Expr::binary(BinaryOp::Max, self, other)
+    }
+    pub fn pow(self, exponent: Expr) -> Expr {
+        Expr::binary(BinaryOp::Pow, self, exponent)
+    }
+    pub fn length(self) -> Expr {
+        Expr::unary(UnaryOp::Length, self)
+    }
+    pub fn normalize(self) -> Expr {
+        Expr::unary(UnaryOp::Normalize, self)
+    }
+    /// Maps `self` linearly from the range `from_min..from_max` to `to_min..to_max`
+    pub fn remap(self, from_min: Expr, from_max: Expr, to_min: Expr, to_max: Expr) -> Expr {
+        let t = (self - from_min.clone()) / (from_max - from_min);
+        Expr::mix(to_min, to_max, t)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BinaryOp, Expr, UnaryOp, Value};
+    use nalgebra::Vector3;
+    #[test]
+    fn constants_fold() {
+        let sum = Expr::binary(BinaryOp::Add, 1.0.into(), 2.0.into());
+        assert_eq!(sum, Expr::Const(Value::Scalar(3.0)));
+        let scaled = Expr::binary(
+            BinaryOp::Mul,
+            Vector3::new(1.0, 2.0, 3.0).into(),
+            2.0.into(),
+        );
+        assert_eq!(
+            scaled,
+            Expr::Const(Value::Vec3(Vector3::new(2.0, 4.0, 6.0)))
+        );
+    }
+    #[test]
+    fn remap_range() {
+        let remapped = Expr::from(5.0).remap(0.0.into(), 10.0.into(), 1.0.into(), 3.0.into());
+        assert_eq!(remapped, Expr::Const(Value::Scalar(2.0)));
+    }
+    #[test]
+    fn time_is_kept() {
+        let wave = Expr::unary(UnaryOp::Sin, Expr::Time);
+        assert!(!wave.is_constant());
+        assert_eq!(wave.eval(0.0), Value::Scalar(0.0));
+    }
+}
@@ -0,0 +1,480 @@
+//! Parser for the arithmetic formulas of the Expression node.
+//!
+//! A formula like `2*r + gap` is parsed into a [`Formula`] tree whose free
+//! variables become the node's inputs. Once the inputs are known the tree is
+//! type checked and converted to an [`Expr`], which folds to a constant when
+//! every input is constant and is lowered to GLSL otherwise.
+use std::collections::HashMap;
+
+use thiserror::Error;
+
+use super::expr::{BinaryOp, Expr, UnaryOp, ValueKind};
+
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum FormulaError {
+    #[error("unexpected '{found}' at column {column}")]
+    Unexpected { found: char, column: usize },
+    #[error("expected {expected} at column {column}")]
+    Expected {
+        expected: &'static str,
+        column: usize,
+    },
+    #[error("unknown function '{0}'")]
+    UnknownFunction(String),
+    #[error("{function} takes {expected} arguments, got {found}")]
+    ArgumentCount {
+        function: String,
+        expected: usize,
+        found: usize,
+    },
+    #[error("{function} can not be applied to {found}")]
+    Type {
+        function: String,
+        found: &'static str,
+    },
+    #[error("no value for variable '{0}'")]
+    MissingVariable(String),
+}
+
+/// Parsed formula, variables are resolved by [`Formula::to_expr`]
+#[derive(Clone, Debug, PartialEq)]
+pub enum Formula {
+    Number(f32),
+    Variable(String),
+    Neg(Box<Formula>),
+    Binary {
+        op: BinaryOp,
+        a: Box<Formula>,
+        b: Box<Formula>,
+    },
+    Call {
+        function: String,
+        args: Vec<Formula>,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Number(f32),
+    Ident(String),
+    Symbol(char),
+}
+
+fn tokenize(text: &str) -> Result<Vec<(Token, usize)>, FormulaError> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let column = i + 1;
+        if c.is_whitespace() {
+            i += 1;
+        } else if c.is_ascii_digit() || c == '.' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.') {
+                i += 1;
+            }
+            let literal: String = chars[start..i].iter().collect();
+            let value = literal
+                .parse()
+                .map_err(|_| FormulaError::Unexpected { found: c, column })?;
+            tokens.push((Token::Number(value), column));
+        } else if c.is_alphabetic() || c == '_' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            tokens.push((Token::Ident(chars[start..i].iter().collect()), column));
+        } else if "+-*/^(),".contains(c) {
+            tokens.push((Token::Symbol(c), column));
+            i += 1;
+        } else {
+            return Err(FormulaError::Unexpected { found: c, column });
+        }
+    }
+    Ok(tokens)
+}
+
+/// Recursive descent parser, from lowest to highest precedence:
+/// `+ -`, `* /`, unary `-`, `^` (right associative), atoms
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    next: usize,
+    /// Column just past the end of the text, reported for missing tokens
+    end: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.next).map(|(token, _)| token)
+    }
+    fn column(&self) -> usize {
+        self.tokens
+            .get(self.next)
+            .map(|(_, column)| *column)
+            .unwrap_or(self.end)
+    }
+    fn eat(&mut self, symbol: char) -> bool {
+        if self.peek() == Some(&Token::Symbol(symbol)) {
+            self.next += 1;
+            true
+        } else {
+            false
+        }
+    }
+    fn expect(&mut self, symbol: char, expected: &'static str) -> Result<(), FormulaError> {
+        if self.eat(symbol) {
+            Ok(())
+        } else {
+            Err(FormulaError::Expected {
+                expected,
+                column: self.column(),
+            })
+        }
+    }
+
+    fn sum(&mut self) -> Result<Formula, FormulaError> {
+        let mut formula = self.product()?;
+        loop {
+            let op = if self.eat('+') {
+                BinaryOp::Add
+            } else if self.eat('-') {
+                BinaryOp::Sub
+            } else {
+                return Ok(formula);
+            };
+            let b = self.product()?;
+            formula = Formula::Binary {
+                op,
+                a: Box::new(formula),
+                b: Box::new(b),
+            };
+        }
+    }
+    fn product(&mut self) -> Result<Formula, FormulaError> {
+        let mut formula = self.unary()?;
+        loop {
+            let op = if self.eat('*') {
+                BinaryOp::Mul
+            } else if self.eat('/') {
+                BinaryOp::Div
+            } else {
+                return Ok(formula);
+            };
+            let b = self.unary()?;
+            formula = Formula::Binary {
+                op,
+                a: Box::new(formula),
+                b: Box::new(b),
+            };
+        }
+    }
+    fn unary(&mut self) -> Result<Formula, FormulaError> {
+        if self.eat('-') {
+            Ok(Formula::Neg(Box::new(self.unary()?)))
+        } else {
+            self.power()
+        }
+    }
+    fn power(&mut self) -> Result<Formula, FormulaError> {
+        let base = self.atom()?;
+        if self.eat('^') {
+            let exponent = self.unary()?;
+            Ok(Formula::Binary {
+                op: BinaryOp::Pow,
+                a: Box::new(base),
+                b: Box::new(exponent),
+            })
+        } else {
+            Ok(base)
+        }
+    }
+    fn atom(&mut self) -> Result<Formula, FormulaError> {
+        let column = self.column();
+        match self.tokens.get(self.next).map(|(token, _)| token.clone()) {
+            Some(Token::Number(value)) => {
+                self.next += 1;
+                Ok(Formula::Number(value))
+            }
+            Some(Token::Ident(name)) => {
+                self.next += 1;
+                if !self.eat('(') {
+                    return Ok(Formula::Variable(name));
+                }
+                let mut args = Vec::new();
+                if !self.eat(')') {
+                    loop {
+                        args.push(self.sum()?);
+                        if self.eat(')') {
+                            break;
+                        }
+                        self.expect(',', "',' or ')'")?;
+                    }
+                }
+                Ok(Formula::Call {
+                    function: name,
+                    args,
+                })
+            }
+            Some(Token::Symbol('(')) => {
+                self.next += 1;
+                let formula = self.sum()?;
+                self.expect(')', "')'")?;
+                Ok(formula)
+            }
+            Some(Token::Symbol(found)) => Err(FormulaError::Unexpected { found, column }),
+            None => Err(FormulaError::Expected {
+                expected: "a value",
+                column,
+            }),
+        }
+    }
+}
+
+/// Arity and name of every function a formula may call
+const FUNCTIONS: &[(&str, usize)] = &[
+    ("abs", 1),
+    ("sin", 1),
+    ("cos", 1),
+    ("floor", 1),
+    ("fract", 1),
+    ("sqrt", 1),
+    ("length", 1),
+    ("normalize", 1),
+    ("min", 2),
+    ("max", 2),
+    ("pow", 2),
+    ("dot", 2),
+    ("cross", 2),
+    ("vec2", 2),
+    ("clamp", 3),
+    ("mix", 3),
+    ("vec3", 3),
+];
+
+impl Formula {
+    pub fn parse(text: &str) -> Result<Self, FormulaError> {
+        let mut parser = Parser {
+            tokens: tokenize(text)?,
+            next: 0,
+            end: text.chars().count() + 1,
+        };
+        let formula = parser.sum()?;
+        match parser.tokens.get(parser.next) {
+            None => Ok(formula),
+            Some((Token::Symbol(found), column)) => Err(FormulaError::Unexpected {
+                found: *found,
+                column: *column,
+            }),
+            Some((_, column)) => Err(FormulaError::Expected {
+                expected: "an operator",
+                column: *column,
+            }),
+        }
+    }
+
+    /// Free variables in order of first appearance, `pi` is a constant
+    pub fn variables(&self) -> Vec<String> {
+        fn collect(formula: &Formula, variables: &mut Vec<String>) {
+            match formula {
+                Formula::Number(_) => {}
+                Formula::Variable(name) => {
+                    if name != "pi" && !variables.contains(name) {
+                        variables.push(name.clone());
+                    }
+                }
+                Formula::Neg(a) => collect(a, variables),
+                Formula::Binary { a, b, .. } => {
+                    collect(a, variables);
+                    collect(b, variables);
+                }
+                Formula::Call { args, .. } => {
+                    for arg in args {
+                        collect(arg, variables);
+                    }
+                }
+            }
+        }
+        let mut variables = Vec::new();
+        collect(self, &mut variables);
+        variables
+    }
+
+    /// Type checks the formula and builds the expression it describes
+    pub fn to_expr(&self, variables: &HashMap<String, Expr>) -> Result<Expr, FormulaError> {
+        match self {
+            Formula::Number(value) => Ok(Expr::from(*value)),
+            Formula::Variable(name) if name == "pi" => Ok(Expr::from(std::f32::consts::PI)),
+            Formula::Variable(name) => variables
+                .get(name)
+                .cloned()
+                .ok_or_else(|| FormulaError::MissingVariable(name.clone())),
+            Formula::Neg(a) => Ok(-a.to_expr(variables)?),
+            Formula::Binary { op, a, b } => {
+                let a = a.to_expr(variables)?;
+                let b = b.to_expr(variables)?;
+                let name = match op {
+                    BinaryOp::Add => "+",
+                    BinaryOp::Sub => "-",
+                    BinaryOp::Mul => "*",
+                    BinaryOp::Div => "/",
+                    _ => "^",
+                };
+                componentwise(name, &[&a, &b])?;
+                Ok(Expr::binary(*op, a, b))
+            }
+            Formula::Call { function, args } => {
+                let expected = FUNCTIONS
+                    .iter()
+                    .find(|(name, _)| name == function)
+                    .map(|(_, arity)| *arity)
+                    .ok_or_else(|| FormulaError::UnknownFunction(function.clone()))?;
+                if args.len() != expected {
+                    return Err(FormulaError::ArgumentCount {
+                        function: function.clone(),
+                        expected,
+                        found: args.len(),
+                    });
+                }
+                let args = args
+                    .iter()
+                    .map(|arg| arg.to_expr(variables))
+                    .collect::<Result<Vec<_>, _>>()?;
+                call(function, args)
+            }
+        }
+    }
+}
+
+/// Checks operands of a component-wise operation, which may mix scalars with
+/// one vector kind but not two different vector kinds
+fn componentwise(function: &str, args: &[&Expr]) -> Result<(), FormulaError> {
+    let mut vector = None;
+    for arg in args {
+        let kind = arg.kind();
+        if kind == ValueKind::Scalar {
+            continue;
+        }
+        match vector {
+            Some(other) if other != kind => {
+                return Err(FormulaError::Type {
+                    function: function.to_string(),
+                    found: "vectors of different sizes",
+                })
+            }
+            _ => vector = Some(kind),
+        }
+    }
+    Ok(())
+}
+
+/// Builds a function call, the argument count was already checked
+fn call(function: &str, args: Vec<Expr>) -> Result<Expr, FormulaError> {
+    let type_error = |found| FormulaError::Type {
+        function: function.to_string(),
+        found,
+    };
+    let unary = |op| Ok(Expr::unary(op, args[0].clone()));
+    match (function, args.as_slice()) {
+        ("abs", _) => unary(UnaryOp::Abs),
+        ("sin", _) => unary(UnaryOp::Sin),
+        ("cos", _) => unary(UnaryOp::Cos),
+        ("floor", _) => unary(UnaryOp::Floor),
+        ("fract", _) => unary(UnaryOp::Fract),
+        ("sqrt", _) => unary(UnaryOp::Sqrt),
+        ("length", _) => unary(UnaryOp::Length),
+        ("normalize", _) => unary(UnaryOp::Normalize),
+        ("min" | "max" | "pow", [a, b]) => {
+            componentwise(function, &[a, b])?;
+            let op = match function {
+                "min" => BinaryOp::Min,
+                "max" => BinaryOp::Max,
+                _ => BinaryOp::Pow,
+            };
+            Ok(Expr::binary(op, a.clone(), b.clone()))
+        }
+        ("dot" | "cross", [a, b]) => {
+            if a.kind() != b.kind() || a.kind() == ValueKind::Scalar {
+                return Err(type_error("anything but two vectors of the same size"));
+            }
+            if function == "dot" {
+                Ok(a.clone().dot(b.clone()))
+            } else if a.kind() == ValueKind::Vec3 {
+                Ok(a.clone().cross(b.clone()))
+            } else {
+                Err(type_error("vec2"))
+            }
+        }
+        ("clamp", [value, min, max]) => {
+            componentwise(function, &[value, min, max])?;
+            Ok(Expr::clamp(value.clone(), min.clone(), max.clone()))
+        }
+        ("mix", [a, b, t]) => {
+            componentwise(function, &[a, b])?;
+            if t.kind() != ValueKind::Scalar {
+                return Err(type_error("a vector factor"));
+            }
+            Ok(Expr::mix(a.clone(), b.clone(), t.clone()))
+        }
+        ("vec2" | "vec3", components) => {
+            if components.iter().any(|c| c.kind() != ValueKind::Scalar) {
+                return Err(type_error("vectors"));
+            }
+            Ok(match components {
+                [x, y] => Expr::vec2(x.clone(), y.clone()),
+                [x, y, z] => Expr::vec3(x.clone(), y.clone(), z.clone()),
+                _ => unreachable!("argument count is checked against FUNCTIONS"),
+            })
+        }
+        _ => Err(FormulaError::UnknownFunction(function.to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Formula, FormulaError};
+    use crate::sdf::expr::{Expr, Value};
+    use std::collections::HashMap;
+    #[test]
+    fn precedence_and_variables() {
+        let formula = Formula::parse("2*r + gap^2").unwrap();
+        assert_eq!(formula.variables(), vec!["r", "gap"]);
+        let variables = HashMap::from([
+            ("r".to_string(), Expr::from(1.5)),
+            ("gap".to_string(), Expr::from(3.0)),
+        ]);
+        assert_eq!(
+            formula.to_expr(&variables),
+            Ok(Expr::Const(Value::Scalar(12.0)))
+        );
+    }
+    #[test]
+    fn runtime_inputs_are_kept() {
+        let formula = Formula::parse("sin(t) * -a").unwrap();
+        let variables = HashMap::from([
+            ("t".to_string(), Expr::Time),
+            ("a".to_string(), Expr::from(2.0)),
+        ]);
+        assert!(!formula.to_expr(&variables).unwrap().is_constant());
+    }
+    #[test]
+    fn errors() {
+        assert_eq!(
+            Formula::parse("a + * b"),
+            Err(FormulaError::Unexpected {
+                found: '*',
+                column: 5
+            })
+        );
+        assert_eq!(
+            Formula::parse("(a + b"),
+            Err(FormulaError::Expected {
+                expected: "')'",
+                column: 7
+            })
+        );
+        let cross = Formula::parse("cross(vec2(a, a), a)").unwrap();
+        let variables = HashMap::from([("a".to_string(), Expr::from(1.0))]);
+        assert!(cross.to_expr(&variables).is_err());
+    }
+}
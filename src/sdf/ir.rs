@@ -0,0 +1,911 @@
+//! Structured representation of an SDF model.
+//!
+//! The node graph evaluates to these trees instead of GLSL text so that other
+//! backends can inspect them. [`SDFBuilder`](super::SDFBuilder) lowers them to
+//! the functions defined in `sdf/strings`.
+use nalgebra::Vector3;
+
+use super::expr::Expr;
+
+/// Domain the distance functions are sampled at, starting from the scene point `p`
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "persistence", derive(serde::Serialize, serde::Deserialize))]
+pub enum SDFPosition {
+    /// The untransformed sample point
+    Origin,
+    Translate {
+        position: Box<SDFPosition>,
+        translation: Expr,
+    },
+    /// Euler rotation in degrees
+    Rotate {
+        position: Box<SDFPosition>,
+        rotation: Expr,
+    },
+    Scale {
+        position: Box<SDFPosition>,
+        scale: Expr,
+    },
+    /// Infinite repetition, axes with a spacing of zero are left alone
+    Repeat {
+        position: Box<SDFPosition>,
+        spacing: Expr,
+    },
+    /// `count` copies along each axis, centered on the origin
+    RepeatLimited {
+        position: Box<SDFPosition>,
+        spacing: Expr,
+        count: Expr,
+    },
+    /// Reflects the half space behind the plane onto the front side
+    Mirror {
+        position: Box<SDFPosition>,
+        normal: Expr,
+        offset: Expr,
+    },
+    /// `count` copies spaced evenly around `axis`
+    RepeatPolar {
+        position: Box<SDFPosition>,
+        axis: Expr,
+        count: Expr,
+    },
+    /// Rotation around the y axis growing by `rate` degrees per unit length
+    Twist {
+        position: Box<SDFPosition>,
+        rate: Expr,
+    },
+    /// Bends the y axis towards x by `rate` degrees per unit length
+    Bend {
+        position: Box<SDFPosition>,
+        rate: Expr,
+    },
+    /// Cross sections grow by `rate` per unit length up the y axis
+    Taper {
+        position: Box<SDFPosition>,
+        rate: Expr,
+    },
+    /// Stretches the shape by `size` on each side of its center
+    Elongate {
+        position: Box<SDFPosition>,
+        size: Expr,
+    },
+}
+
+impl Default for SDFPosition {
+    fn default() -> Self {
+        Self::Origin
+    }
+}
+
+impl SDFPosition {
+    /// Upper bound of how much the domain stretches distances at `time`,
+    /// i.e. the factor distances sampled through this position overestimate
+    /// the true distance by. Deformations depend on the distance from their
+    /// axis, for those the bound holds within one unit of it.
+    pub fn distortion(&self, time: f32) -> f32 {
+        match self {
+            SDFPosition::Origin => 1.0,
+            SDFPosition::Translate { position, .. }
+            | SDFPosition::Rotate { position, .. }
+            | SDFPosition::Repeat { position, .. }
+            | SDFPosition::RepeatLimited { position, .. }
+            | SDFPosition::Mirror { position, .. }
+            | SDFPosition::RepeatPolar { position, .. }
+            | SDFPosition::Elongate { position, .. } => position.distortion(time),
+            SDFPosition::Scale { position, scale } => {
+                position.distortion(time) * scale.eval(time).to_vec3().abs().max()
+            }
+            SDFPosition::Twist { position, rate } | SDFPosition::Bend { position, rate } => {
+                let rate = rate.eval(time).to_scalar();
+                position.distortion(time) * (1.0 + rate.to_radians().powi(2)).sqrt()
+            }
+            SDFPosition::Taper { position, rate } => {
+                let rate = rate.eval(time).to_scalar();
+                let narrowest = (1.0 - rate.abs()).max(0.05);
+                position.distortion(time) * (1.0 + rate * rate).sqrt() / narrowest
+            }
+        }
+    }
+
+    /// The position with `origin` in place of [`SDFPosition::Origin`] and
+    /// the [`Expr::Arg`]s replaced by `args`
+    pub fn substitute(&self, origin: &SDFPosition, args: &[Expr]) -> SDFPosition {
+        let sub = |position: &SDFPosition| Box::new(position.substitute(origin, args));
+        match self {
+            SDFPosition::Origin => origin.clone(),
+            SDFPosition::Translate {
+                position,
+                translation,
+            } => SDFPosition::Translate {
+                position: sub(position),
+                translation: translation.substitute(args),
+            },
+            SDFPosition::Rotate { position, rotation } => SDFPosition::Rotate {
+                position: sub(position),
+                rotation: rotation.substitute(args),
+            },
+            SDFPosition::Scale { position, scale } => SDFPosition::Scale {
+                position: sub(position),
+                scale: scale.substitute(args),
+            },
+            SDFPosition::Repeat { position, spacing } => SDFPosition::Repeat {
+                position: sub(position),
+                spacing: spacing.substitute(args),
+            },
+            SDFPosition::RepeatLimited {
+                position,
+                spacing,
+                count,
+            } => SDFPosition::RepeatLimited {
+                position: sub(position),
+                spacing: spacing.substitute(args),
+                count: count.substitute(args),
+            },
+            SDFPosition::Mirror {
+                position,
+                normal,
+                offset,
+            } => SDFPosition::Mirror {
+                position: sub(position),
+                normal: normal.substitute(args),
+                offset: offset.substitute(args),
+            },
+            SDFPosition::RepeatPolar {
+                position,
+                axis,
+                count,
+            } => SDFPosition::RepeatPolar {
+                position: sub(position),
+                axis: axis.substitute(args),
+                count: count.substitute(args),
+            },
+            SDFPosition::Twist { position, rate } => SDFPosition::Twist {
+                position: sub(position),
+                rate: rate.substitute(args),
+            },
+            SDFPosition::Bend { position, rate } => SDFPosition::Bend {
+                position: sub(position),
+                rate: rate.substitute(args),
+            },
+            SDFPosition::Taper { position, rate } => SDFPosition::Taper {
+                position: sub(position),
+                rate: rate.substitute(args),
+            },
+            SDFPosition::Elongate { position, size } => SDFPosition::Elongate {
+                position: sub(position),
+                size: size.substitute(args),
+            },
+        }
+    }
+}
+
+/// Signed distance field built from primitives and boolean combinators
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "persistence", derive(serde::Serialize, serde::Deserialize))]
+pub enum SDFVolume {
+    /// Nothing, infinitely far away from every point
+    Empty,
+    Sphere {
+        position: SDFPosition,
+        radius: Expr,
+    },
+    /// Box with half extents `dimensions` and edges rounded by `fillet`
+    Box {
+        position: SDFPosition,
+        dimensions: Expr,
+        fillet: Expr,
+    },
+    /// Cylinder along the y axis with edges rounded by `fillet`
+    Cylinder {
+        position: SDFPosition,
+        radius: Expr,
+        length: Expr,
+        fillet: Expr,
+    },
+    /// Torus around the y axis
+    Torus {
+        position: SDFPosition,
+        major_radius: Expr,
+        minor_radius: Expr,
+    },
+    /// Capsule along the y axis, `length` is the half length of the straight part
+    Capsule {
+        position: SDFPosition,
+        length: Expr,
+        radius: Expr,
+    },
+    /// Cone along the y axis with its base at `-height`
+    Cone {
+        position: SDFPosition,
+        height: Expr,
+        radius: Expr,
+    },
+    /// Half space below the plane with the given normal
+    Plane {
+        position: SDFPosition,
+        normal: Expr,
+        offset: Expr,
+    },
+    Ellipsoid {
+        position: SDFPosition,
+        radii: Expr,
+    },
+    /// Hexagonal prism along the z axis
+    HexPrism {
+        position: SDFPosition,
+        radius: Expr,
+        length: Expr,
+    },
+    /// Triangular prism along the z axis
+    TriPrism {
+        position: SDFPosition,
+        radius: Expr,
+        length: Expr,
+    },
+    /// Cone along the y axis with spherical caps of different radii
+    RoundedCone {
+        position: SDFPosition,
+        bottom_radius: Expr,
+        top_radius: Expr,
+        height: Expr,
+    },
+    Octahedron {
+        position: SDFPosition,
+        size: Expr,
+    },
+    /// Chain link, elongated along the y axis
+    Link {
+        position: SDFPosition,
+        length: Expr,
+        major_radius: Expr,
+        minor_radius: Expr,
+    },
+    /// Square based pyramid pointing up the y axis
+    Pyramid {
+        position: SDFPosition,
+        base: Expr,
+        height: Expr,
+    },
+    Union {
+        a: Box<SDFVolume>,
+        b: Box<SDFVolume>,
+    },
+    SmoothUnion {
+        a: Box<SDFVolume>,
+        b: Box<SDFVolume>,
+        k: Expr,
+    },
+    /// `a` with `b` removed
+    Diff {
+        a: Box<SDFVolume>,
+        b: Box<SDFVolume>,
+    },
+    SmoothDiff {
+        a: Box<SDFVolume>,
+        b: Box<SDFVolume>,
+        k: Expr,
+    },
+    Intersect {
+        a: Box<SDFVolume>,
+        b: Box<SDFVolume>,
+    },
+    SmoothIntersect {
+        a: Box<SDFVolume>,
+        b: Box<SDFVolume>,
+        k: Expr,
+    },
+    /// Grows the surface outwards by `radius`, rounding every edge
+    Round {
+        volume: Box<SDFVolume>,
+        radius: Expr,
+    },
+    /// Thin wall of `thickness` on both sides of the surface
+    Shell {
+        volume: Box<SDFVolume>,
+        thickness: Expr,
+    },
+    /// Infinitely thin surface, the interior is treated as outside
+    Hollow {
+        volume: Box<SDFVolume>,
+    },
+    /// `shape` extruded along the z axis, centered on the xy plane, with the
+    /// cap edges rounded by `rounding`
+    Extrude {
+        position: SDFPosition,
+        shape: SDF2D,
+        height: Expr,
+        rounding: Expr,
+    },
+    /// `shape` revolved around `axis`. The x axis of the profile points away
+    /// from the axis, starting `offset` away from it, y runs along the axis.
+    Revolve {
+        position: SDFPosition,
+        shape: SDF2D,
+        axis: Expr,
+        offset: Expr,
+    },
+    /// `volume` shaded with `material`, replacing any material assigned inside it
+    Material {
+        volume: Box<SDFVolume>,
+        material: Material,
+    },
+    /// Instance of a node group, lowered to a call of a GLSL function
+    /// generated once per distinct `body`
+    Call {
+        /// Name of the group, used to name the generated function
+        function: String,
+        /// Point the function is evaluated at, `p` inside `body`
+        position: SDFPosition,
+        /// Values of the [`Expr::Arg`]s in `body`
+        args: Vec<Expr>,
+        /// Volume of the group around [`SDFPosition::Origin`], see
+        /// [`SDFVolume::inlined`] for the volume at the call
+        body: Box<SDFVolume>,
+    },
+    /// Call of a distance function written in GLSL rather than built from
+    /// the volumes above
+    Glsl {
+        function: GlslFunction,
+        position: SDFPosition,
+        /// Arguments after the sample point
+        args: Vec<Expr>,
+    },
+}
+
+/// Distance function defined in GLSL, declared as
+/// `float name(vec3 p, ...)` with one parameter per argument of the call
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "persistence", derive(serde::Serialize, serde::Deserialize))]
+pub struct GlslFunction {
+    pub name: String,
+    /// Complete definition, added to the shader once however often it is called
+    pub source: String,
+}
+
+impl Default for SDFVolume {
+    fn default() -> Self {
+        Self::Empty
+    }
+}
+
+impl SDFVolume {
+    /// Largest [`SDFPosition::distortion`] of any primitive in the tree at
+    /// `time`. The ray marcher divides its steps by this so deformed shapes
+    /// are not overshot.
+    pub fn distortion(&self, time: f32) -> f32 {
+        match self {
+            SDFVolume::Empty => 1.0,
+            SDFVolume::Sphere { position, .. }
+            | SDFVolume::Box { position, .. }
+            | SDFVolume::Cylinder { position, .. }
+            | SDFVolume::Torus { position, .. }
+            | SDFVolume::Capsule { position, .. }
+            | SDFVolume::Cone { position, .. }
+            | SDFVolume::Plane { position, .. }
+            | SDFVolume::Ellipsoid { position, .. }
+            | SDFVolume::HexPrism { position, .. }
+            | SDFVolume::TriPrism { position, .. }
+            | SDFVolume::RoundedCone { position, .. }
+            | SDFVolume::Octahedron { position, .. }
+            | SDFVolume::Link { position, .. }
+            | SDFVolume::Pyramid { position, .. }
+            | SDFVolume::Glsl { position, .. } => position.distortion(time),
+            SDFVolume::Union { a, b }
+            | SDFVolume::SmoothUnion { a, b, .. }
+            | SDFVolume::Diff { a, b }
+            | SDFVolume::SmoothDiff { a, b, .. }
+            | SDFVolume::Intersect { a, b }
+            | SDFVolume::SmoothIntersect { a, b, .. } => a.distortion(time).max(b.distortion(time)),
+            SDFVolume::Round { volume, .. }
+            | SDFVolume::Shell { volume, .. }
+            | SDFVolume::Hollow { volume }
+            | SDFVolume::Material { volume, .. } => volume.distortion(time),
+            SDFVolume::Call { .. } => self.inlined().distortion(time),
+            SDFVolume::Extrude { position, .. } | SDFVolume::Revolve { position, .. } => {
+                position.distortion(time)
+            }
+        }
+    }
+}
+
+impl SDFVolume {
+    /// The volume with `origin` in place of [`SDFPosition::Origin`] and the
+    /// [`Expr::Arg`]s replaced by `args`. Bodies of nested calls have their
+    /// own origin and arguments and are left alone.
+    pub fn substitute(&self, origin: &SDFPosition, args: &[Expr]) -> SDFVolume {
+        let pos = |position: &SDFPosition| position.substitute(origin, args);
+        let sub = |volume: &SDFVolume| Box::new(volume.substitute(origin, args));
+        match self {
+            SDFVolume::Empty => SDFVolume::Empty,
+            SDFVolume::Sphere { position, radius } => SDFVolume::Sphere {
+                position: pos(position),
+                radius: radius.substitute(args),
+            },
+            SDFVolume::Box {
+                position,
+                dimensions,
+                fillet,
+            } => SDFVolume::Box {
+                position: pos(position),
+                dimensions: dimensions.substitute(args),
+                fillet: fillet.substitute(args),
+            },
+            SDFVolume::Cylinder {
+                position,
+                radius,
+                length,
+                fillet,
+            } => SDFVolume::Cylinder {
+                position: pos(position),
+                radius: radius.substitute(args),
+                length: length.substitute(args),
+                fillet: fillet.substitute(args),
+            },
+            SDFVolume::Torus {
+                position,
+                major_radius,
+                minor_radius,
+            } => SDFVolume::Torus {
+                position: pos(position),
+                major_radius: major_radius.substitute(args),
+                minor_radius: minor_radius.substitute(args),
+            },
+            SDFVolume::Capsule {
+                position,
+                length,
+                radius,
+            } => SDFVolume::Capsule {
+                position: pos(position),
+                length: length.substitute(args),
+                radius: radius.substitute(args),
+            },
+            SDFVolume::Cone {
+                position,
+                height,
+                radius,
+            } => SDFVolume::Cone {
+                position: pos(position),
+                height: height.substitute(args),
+                radius: radius.substitute(args),
+            },
+            SDFVolume::Plane {
+                position,
+                normal,
+                offset,
+            } => SDFVolume::Plane {
+                position: pos(position),
+                normal: normal.substitute(args),
+                offset: offset.substitute(args),
+            },
+            SDFVolume::Ellipsoid { position, radii } => SDFVolume::Ellipsoid {
+                position: pos(position),
+                radii: radii.substitute(args),
+            },
+            SDFVolume::HexPrism {
+                position,
+                radius,
+                length,
+            } => SDFVolume::HexPrism {
+                position: pos(position),
+                radius: radius.substitute(args),
+                length: length.substitute(args),
+            },
+            SDFVolume::TriPrism {
+                position,
+                radius,
+                length,
+            } => SDFVolume::TriPrism {
+                position: pos(position),
+                radius: radius.substitute(args),
+                length: length.substitute(args),
+            },
+            SDFVolume::RoundedCone {
+                position,
+                bottom_radius,
+                top_radius,
+                height,
+            } => SDFVolume::RoundedCone {
+                position: pos(position),
+                bottom_radius: bottom_radius.substitute(args),
+                top_radius: top_radius.substitute(args),
+                height: height.substitute(args),
+            },
+            SDFVolume::Octahedron { position, size } => SDFVolume::Octahedron {
+                position: pos(position),
+                size: size.substitute(args),
+            },
+            SDFVolume::Link {
+                position,
+                length,
+                major_radius,
+                minor_radius,
+            } => SDFVolume::Link {
+                position: pos(position),
+                length: length.substitute(args),
+                major_radius: major_radius.substitute(args),
+                minor_radius: minor_radius.substitute(args),
+            },
+            SDFVolume::Pyramid {
+                position,
+                base,
+                height,
+            } => SDFVolume::Pyramid {
+                position: pos(position),
+                base: base.substitute(args),
+                height: height.substitute(args),
+            },
+            SDFVolume::Union { a, b } => SDFVolume::Union {
+                a: sub(a),
+                b: sub(b),
+            },
+            SDFVolume::SmoothUnion { a, b, k } => SDFVolume::SmoothUnion {
+                a: sub(a),
+                b: sub(b),
+                k: k.substitute(args),
+            },
+            SDFVolume::Diff { a, b } => SDFVolume::Diff {
+                a: sub(a),
+                b: sub(b),
+            },
+            SDFVolume::SmoothDiff { a, b, k } => SDFVolume::SmoothDiff {
+                a: sub(a),
+                b: sub(b),
+                k: k.substitute(args),
+            },
+            SDFVolume::Intersect { a, b } => SDFVolume::Intersect {
+                a: sub(a),
+                b: sub(b),
+            },
+            SDFVolume::SmoothIntersect { a, b, k } => SDFVolume::SmoothIntersect {
+                a: sub(a),
+                b: sub(b),
+                k: k.substitute(args),
+            },
+            SDFVolume::Round { volume, radius } => SDFVolume::Round {
+                volume: sub(volume),
+                radius: radius.substitute(args),
+            },
+            SDFVolume::Shell { volume, thickness } => SDFVolume::Shell {
+                volume: sub(volume),
+                thickness: thickness.substitute(args),
+            },
+            SDFVolume::Hollow { volume } => SDFVolume::Hollow {
+                volume: sub(volume),
+            },
+            SDFVolume::Extrude {
+                position,
+                shape,
+                height,
+                rounding,
+            } => SDFVolume::Extrude {
+                position: pos(position),
+                shape: shape.substitute(args),
+                height: height.substitute(args),
+                rounding: rounding.substitute(args),
+            },
+            SDFVolume::Revolve {
+                position,
+                shape,
+                axis,
+                offset,
+            } => SDFVolume::Revolve {
+                position: pos(position),
+                shape: shape.substitute(args),
+                axis: axis.substitute(args),
+                offset: offset.substitute(args),
+            },
+            SDFVolume::Material { volume, material } => SDFVolume::Material {
+                volume: sub(volume),
+                material: material.substitute(args),
+            },
+            SDFVolume::Call {
+                function,
+                position,
+                args: call_args,
+                body,
+            } => SDFVolume::Call {
+                function: function.clone(),
+                position: pos(position),
+                args: call_args.iter().map(|arg| arg.substitute(args)).collect(),
+                body: body.clone(),
+            },
+            SDFVolume::Glsl {
+                function,
+                position,
+                args: call_args,
+            } => SDFVolume::Glsl {
+                function: function.clone(),
+                position: pos(position),
+                args: call_args.iter().map(|arg| arg.substitute(args)).collect(),
+            },
+        }
+    }
+
+    /// The volume with every [`SDFVolume::Call`] replaced by its body at the
+    /// position and with the arguments of the call, for backends that have
+    /// no functions
+    pub fn inlined(&self) -> SDFVolume {
+        match self {
+            SDFVolume::Call {
+                position,
+                args,
+                body,
+                ..
+            } => body.substitute(position, args).inlined(),
+            SDFVolume::Union { a, b } => SDFVolume::Union {
+                a: Box::new(a.inlined()),
+                b: Box::new(b.inlined()),
+            },
+            SDFVolume::SmoothUnion { a, b, k } => SDFVolume::SmoothUnion {
+                a: Box::new(a.inlined()),
+                b: Box::new(b.inlined()),
+                k: k.clone(),
+            },
+            SDFVolume::Diff { a, b } => SDFVolume::Diff {
+                a: Box::new(a.inlined()),
+                b: Box::new(b.inlined()),
+            },
+            SDFVolume::SmoothDiff { a, b, k } => SDFVolume::SmoothDiff {
+                a: Box::new(a.inlined()),
+                b: Box::new(b.inlined()),
+                k: k.clone(),
+            },
+            SDFVolume::Intersect { a, b } => SDFVolume::Intersect {
+                a: Box::new(a.inlined()),
+                b: Box::new(b.inlined()),
+            },
+            SDFVolume::SmoothIntersect { a, b, k } => SDFVolume::SmoothIntersect {
+                a: Box::new(a.inlined()),
+                b: Box::new(b.inlined()),
+                k: k.clone(),
+            },
+            SDFVolume::Round { volume, radius } => SDFVolume::Round {
+                volume: Box::new(volume.inlined()),
+                radius: radius.clone(),
+            },
+            SDFVolume::Shell { volume, thickness } => SDFVolume::Shell {
+                volume: Box::new(volume.inlined()),
+                thickness: thickness.clone(),
+            },
+            SDFVolume::Hollow { volume } => SDFVolume::Hollow {
+                volume: Box::new(volume.inlined()),
+            },
+            SDFVolume::Material { volume, material } => SDFVolume::Material {
+                volume: Box::new(volume.inlined()),
+                material: material.clone(),
+            },
+            _ => self.clone(),
+        }
+    }
+}
+
+impl SDFVolume {
+    /// Whether a material is assigned anywhere in the tree
+    pub fn has_material(&self) -> bool {
+        self.any_material(&|_| true)
+    }
+
+    /// Whether `f` holds for any material that shows on the surface
+    pub fn any_material(&self, f: &impl Fn(&Material) -> bool) -> bool {
+        match self {
+            SDFVolume::Material { material, .. } => f(material),
+            SDFVolume::Union { a, b }
+            | SDFVolume::SmoothUnion { a, b, .. }
+            | SDFVolume::Diff { a, b }
+            | SDFVolume::SmoothDiff { a, b, .. }
+            | SDFVolume::Intersect { a, b }
+            | SDFVolume::SmoothIntersect { a, b, .. } => a.any_material(f) || b.any_material(f),
+            SDFVolume::Round { volume, .. }
+            | SDFVolume::Shell { volume, .. }
+            | SDFVolume::Hollow { volume }
+            | SDFVolume::Call { body: volume, .. } => volume.any_material(f),
+            _ => false,
+        }
+    }
+}
+
+/// Matcap textures a material can be lit with, in the order they are bound
+/// to `u_matcaps`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "persistence", derive(serde::Serialize, serde::Deserialize))]
+pub enum Matcap {
+    #[default]
+    Jade,
+    Clay,
+    Ceramic,
+    Metal,
+    CarPaint,
+    Pearl,
+    Resin,
+    Toon,
+}
+
+impl Matcap {
+    pub const ALL: [Matcap; 8] = [
+        Matcap::Jade,
+        Matcap::Clay,
+        Matcap::Ceramic,
+        Matcap::Metal,
+        Matcap::CarPaint,
+        Matcap::Pearl,
+        Matcap::Resin,
+        Matcap::Toon,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Matcap::Jade => "Jade",
+            Matcap::Clay => "Clay",
+            Matcap::Ceramic => "Ceramic",
+            Matcap::Metal => "Metal",
+            Matcap::CarPaint => "Car paint",
+            Matcap::Pearl => "Pearl",
+            Matcap::Resin => "Resin",
+            Matcap::Toon => "Toon",
+        }
+    }
+
+    /// Slot of `u_matcaps` the texture is bound to
+    pub fn index(&self) -> usize {
+        *self as usize
+    }
+}
+
+/// Surface appearance of a volume
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "persistence", derive(serde::Serialize, serde::Deserialize))]
+pub struct Material {
+    /// Linear color the matcap is tinted with
+    pub color: Expr,
+    pub matcap: Matcap,
+    /// Blurs the matcap, from 0 for a sharp reflection to 1
+    pub roughness: Expr,
+}
+
+impl Material {
+    /// The material with the [`Expr::Arg`]s replaced by `args`
+    pub fn substitute(&self, args: &[Expr]) -> Material {
+        Material {
+            color: self.color.substitute(args),
+            matcap: self.matcap,
+            roughness: self.roughness.substitute(args),
+        }
+    }
+}
+
+impl Default for Material {
+    fn default() -> Self {
+        Self {
+            color: Vector3::new(1.0, 1.0, 1.0).into(),
+            matcap: Matcap::default(),
+            roughness: 0.0.into(),
+        }
+    }
+}
+
+/// Signed distance field of a profile in the plane, turned into a volume by
+/// [`SDFVolume::Extrude`] or [`SDFVolume::Revolve`]
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "persistence", derive(serde::Serialize, serde::Deserialize))]
+pub enum SDF2D {
+    /// Nothing, infinitely far away from every point
+    Empty,
+    Circle {
+        center: Expr,
+        radius: Expr,
+    },
+    /// Rectangle with half extents `size`
+    Rectangle {
+        center: Expr,
+        size: Expr,
+    },
+    /// Rectangle with half extents `size` and corners rounded by `radius`
+    RoundedRectangle {
+        center: Expr,
+        size: Expr,
+        radius: Expr,
+    },
+    /// Regular polygon with its corners on a circle of `radius`
+    Polygon {
+        center: Expr,
+        radius: Expr,
+        sides: Expr,
+    },
+    /// Arc of `angle` degrees opening around the y axis, `thickness` is the
+    /// distance from the center line to either side
+    Arc {
+        center: Expr,
+        radius: Expr,
+        angle: Expr,
+        thickness: Expr,
+    },
+    /// Line from `a` to `b` with rounded ends
+    Segment {
+        a: Expr,
+        b: Expr,
+        thickness: Expr,
+    },
+    Union {
+        a: Box<SDF2D>,
+        b: Box<SDF2D>,
+    },
+    /// `a` with `b` removed
+    Diff {
+        a: Box<SDF2D>,
+        b: Box<SDF2D>,
+    },
+    Intersect {
+        a: Box<SDF2D>,
+        b: Box<SDF2D>,
+    },
+}
+
+impl Default for SDF2D {
+    fn default() -> Self {
+        Self::Empty
+    }
+}
+
+impl SDF2D {
+    /// The profile with the [`Expr::Arg`]s replaced by `args`
+    pub fn substitute(&self, args: &[Expr]) -> SDF2D {
+        let sub = |shape: &SDF2D| Box::new(shape.substitute(args));
+        match self {
+            SDF2D::Empty => SDF2D::Empty,
+            SDF2D::Circle { center, radius } => SDF2D::Circle {
+                center: center.substitute(args),
+                radius: radius.substitute(args),
+            },
+            SDF2D::Rectangle { center, size } => SDF2D::Rectangle {
+                center: center.substitute(args),
+                size: size.substitute(args),
+            },
+            SDF2D::RoundedRectangle {
+                center,
+                size,
+                radius,
+            } => SDF2D::RoundedRectangle {
+                center: center.substitute(args),
+                size: size.substitute(args),
+                radius: radius.substitute(args),
+            },
+            SDF2D::Polygon {
+                center,
+                radius,
+                sides,
+            } => SDF2D::Polygon {
+                center: center.substitute(args),
+                radius: radius.substitute(args),
+                sides: sides.substitute(args),
+            },
+            SDF2D::Arc {
+                center,
+                radius,
+                angle,
+                thickness,
+            } => SDF2D::Arc {
+                center: center.substitute(args),
+                radius: radius.substitute(args),
+                angle: angle.substitute(args),
+                thickness: thickness.substitute(args),
+            },
+            SDF2D::Segment { a, b, thickness } => SDF2D::Segment {
+                a: a.substitute(args),
+                b: b.substitute(args),
+                thickness: thickness.substitute(args),
+            },
+            SDF2D::Union { a, b } => SDF2D::Union {
+                a: sub(a),
+                b: sub(b),
+            },
+            SDF2D::Diff { a, b } => SDF2D::Diff {
+                a: sub(a),
+                b: sub(b),
+            },
+            SDF2D::Intersect { a, b } => SDF2D::Intersect {
+                a: sub(a),
+                b: sub(b),
+            },
+        }
+    }
+}
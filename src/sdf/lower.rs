@@ -0,0 +1,734 @@
+//! Lowering of [`SDFVolume`] trees to GLSL expressions.
+//!
+//! Constants are either inlined as literals or assigned a slot in the
+//! `u_params` uniform array. With slots the generated source stays the same
+//! while only values change, so the compiled program can be reused and the new
+//! values uploaded as uniforms. Parameters depending on the playback time are
+//! lowered to expressions reading `u_time`.
+//!
+//! Node groups are lowered to one GLSL function per distinct body, taking the
+//! sample point and the group's arguments, with the constants of the body
+//! inlined or in slots like the rest of the scene. Functions written in GLSL
+//! by nodes are collected alongside.
+use nalgebra::{Vector2, Vector3};
+
+use super::{
+    expr::{BinaryOp, Expr, UnaryOp, Value, ValueKind},
+    ir::{GlslFunction, Material, SDFPosition, SDFVolume, SDF2D},
+};
+
+/// GLSL distance used for an empty volume
+const EMPTY_DISTANCE: &str = "1e10";
+
+/// Scene expression together with the values of the `u_params` slots it reads
+#[derive(Clone, Debug, PartialEq)]
+pub struct LoweredScene {
+    /// `float` distance returned by `scene`
+    pub scene: String,
+    /// `vec4` surface returned by `scene_surface`, see [`Lowering::surface`]
+    pub surface: String,
+    /// Definition of `scene_material`, see [`Lowering::material_function`]
+    pub materials: String,
+    /// Definitions of the group functions called by the scene, see
+    /// [`Lowering::functions`]
+    pub functions: String,
+    pub params: Vec<f32>,
+    /// Factor the ray marcher shortens its steps by, see [`SDFVolume::distortion`]
+    pub distortion: f32,
+}
+
+pub struct Lowering {
+    /// `None` when constants are inlined
+    params: Option<Vec<f32>>,
+    /// Materials met by [`Self::surface`], material ID `i + 1` is `materials[i]`
+    materials: Vec<Material>,
+    /// Group functions generated so far
+    functions: Vec<Function>,
+    /// GLSL functions called so far
+    glsl: Vec<GlslFunction>,
+}
+
+/// GLSL function generated for the body of a group
+struct Function {
+    body: SDFVolume,
+    /// Kinds of the arguments after the sample point
+    args: Vec<ValueKind>,
+    /// Returns the `vec4` surface rather than the `float` distance
+    surface: bool,
+    name: String,
+    source: String,
+}
+
+impl Lowering {
+    pub fn inline() -> Self {
+        Self {
+            params: None,
+            materials: Vec::new(),
+            functions: Vec::new(),
+            glsl: Vec::new(),
+        }
+    }
+    pub fn with_params() -> Self {
+        Self {
+            params: Some(Vec::new()),
+            materials: Vec::new(),
+            functions: Vec::new(),
+            glsl: Vec::new(),
+        }
+    }
+    pub fn into_params(self) -> Vec<f32> {
+        self.params.unwrap_or_default()
+    }
+
+    fn scalar(&mut self, value: f32) -> String {
+        match self.params {
+            None => format!("{}", value),
+            Some(ref mut params) => {
+                params.push(value);
+                format!("u_params[{}]", params.len() - 1)
+            }
+        }
+    }
+    fn vec2(&mut self, value: Vector2<f32>) -> String {
+        format!("vec2({}, {})", self.scalar(value.x), self.scalar(value.y))
+    }
+    fn vec3(&mut self, value: Vector3<f32>) -> String {
+        format!(
+            "vec3({}, {}, {})",
+            self.scalar(value.x),
+            self.scalar(value.y),
+            self.scalar(value.z)
+        )
+    }
+
+    /// GLSL expression of the same kind as `expr`
+    pub fn expr(&mut self, expr: &Expr) -> String {
+        match expr {
+            Expr::Const(Value::Scalar(value)) => self.scalar(*value),
+            Expr::Const(Value::Vec2(value)) => self.vec2(*value),
+            Expr::Const(Value::Vec3(value)) => self.vec3(*value),
+            Expr::Time => "u_time".to_string(),
+            Expr::Arg { index, .. } => format!("a{}", index),
+            Expr::Unary { op, a } => {
+                let a = self.expr(a);
+                match op {
+                    UnaryOp::Neg => format!("(-{})", a),
+                    UnaryOp::Abs => format!("abs({})", a),
+                    UnaryOp::Sin => format!("sin({})", a),
+                    UnaryOp::Cos => format!("cos({})", a),
+                    UnaryOp::Floor => format!("floor({})", a),
+                    UnaryOp::Fract => format!("fract({})", a),
+                    UnaryOp::Sqrt => format!("sqrt({})", a),
+                    UnaryOp::Length => format!("length({})", a),
+                    UnaryOp::Normalize => format!("normalize({})", a),
+                }
+            }
+            Expr::Binary { op, a: lhs, b: rhs } => {
+                let kind = expr.kind();
+                let a = self.expr(lhs);
+                let b = self.expr(rhs);
+                match op {
+                    BinaryOp::Add => format!("({} + {})", a, b),
+                    BinaryOp::Sub => format!("({} - {})", a, b),
+                    BinaryOp::Mul => format!("({} * {})", a, b),
+                    BinaryOp::Div => format!("({} / {})", a, b),
+                    BinaryOp::Min => {
+                        format!("min({}, {})", splat(a, lhs, kind), splat(b, rhs, kind))
+                    }
+                    BinaryOp::Max => {
+                        format!("max({}, {})", splat(a, lhs, kind), splat(b, rhs, kind))
+                    }
+                    BinaryOp::Pow => {
+                        format!("pow({}, {})", splat(a, lhs, kind), splat(b, rhs, kind))
+                    }
+                    BinaryOp::Dot => format!("dot({}, {})", a, b),
+                    BinaryOp::Cross => format!("cross({}, {})", a, b),
+                }
+            }
+            Expr::Clamp {
+                value: x,
+                min: lo,
+                max: hi,
+            } => {
+                let kind = expr.kind();
+                let value = self.expr(x);
+                let min = self.expr(lo);
+                let max = self.expr(hi);
+                format!(
+                    "clamp({}, {}, {})",
+                    splat(value, x, kind),
+                    splat(min, lo, kind),
+                    splat(max, hi, kind)
+                )
+            }
+            Expr::Mix { a: lhs, b: rhs, t } => {
+                let kind = expr.kind();
+                let a = self.expr(lhs);
+                let b = self.expr(rhs);
+                let t = self.expr(t);
+                format!(
+                    "mix({}, {}, {})",
+                    splat(a, lhs, kind),
+                    splat(b, rhs, kind),
+                    t
+                )
+            }
+            Expr::Vec2 { x, y } => format!("vec2({}, {})", self.expr(x), self.expr(y)),
+            Expr::Vec3 { x, y, z } => {
+                let x = self.expr(x);
+                let y = self.expr(y);
+                format!("vec3({}, {}, {})", x, y, self.expr(z))
+            }
+        }
+    }
+
+    /// GLSL `vec3` expression for a position tree
+    pub fn position(&mut self, position: &SDFPosition) -> String {
+        match position {
+            SDFPosition::Origin => "p".to_string(),
+            SDFPosition::Translate {
+                position,
+                translation,
+            } => {
+                let position = self.position(position);
+                format!("translate({}, {})", position, self.expr(translation))
+            }
+            SDFPosition::Rotate { position, rotation } => {
+                let position = self.position(position);
+                format!("rotate({}, {})", position, self.expr(rotation))
+            }
+            SDFPosition::Scale { position, scale } => {
+                let position = self.position(position);
+                format!("scale({}, {})", position, self.expr(scale))
+            }
+            SDFPosition::Repeat { position, spacing } => {
+                let position = self.position(position);
+                format!("repeat({}, {})", position, self.expr(spacing))
+            }
+            SDFPosition::RepeatLimited {
+                position,
+                spacing,
+                count,
+            } => {
+                let position = self.position(position);
+                let spacing = self.expr(spacing);
+                format!(
+                    "repeat_limited({}, {}, {})",
+                    position,
+                    spacing,
+                    self.expr(count)
+                )
+            }
+            SDFPosition::Mirror {
+                position,
+                normal,
+                offset,
+            } => {
+                let position = self.position(position);
+                let normal = self.expr(normal);
+                format!("mirror({}, {}, {})", position, normal, self.expr(offset))
+            }
+            SDFPosition::RepeatPolar {
+                position,
+                axis,
+                count,
+            } => {
+                let position = self.position(position);
+                let axis = self.expr(axis);
+                format!("repeat_polar({}, {}, {})", position, axis, self.expr(count))
+            }
+            SDFPosition::Twist { position, rate } => {
+                let position = self.position(position);
+                format!("twist({}, {})", position, self.expr(rate))
+            }
+            SDFPosition::Bend { position, rate } => {
+                let position = self.position(position);
+                format!("bend({}, {})", position, self.expr(rate))
+            }
+            SDFPosition::Taper { position, rate } => {
+                let position = self.position(position);
+                format!("taper({}, {})", position, self.expr(rate))
+            }
+            SDFPosition::Elongate { position, size } => {
+                let position = self.position(position);
+                format!("elongate({}, {})", position, self.expr(size))
+            }
+        }
+    }
+
+    /// GLSL `float` distance expression for a volume tree
+    pub fn volume(&mut self, volume: &SDFVolume) -> String {
+        match volume {
+            SDFVolume::Empty => EMPTY_DISTANCE.to_string(),
+            SDFVolume::Sphere { position, radius } => {
+                let position = self.position(position);
+                format!("sdf_sphere({}, {})", position, self.expr(radius))
+            }
+            SDFVolume::Box {
+                position,
+                dimensions,
+                fillet,
+            } => {
+                let position = self.position(position);
+                let dimensions = self.expr(dimensions);
+                let fillet = self.expr(fillet);
+                format!(
+                    "sdf_box({}, {} - {}) - {}",
+                    position, dimensions, fillet, fillet
+                )
+            }
+            SDFVolume::Cylinder {
+                position,
+                radius,
+                length,
+                fillet,
+            } => {
+                let position = self.position(position);
+                let length = self.expr(length);
+                let radius = self.expr(radius);
+                let fillet = self.expr(fillet);
+                format!(
+                    "sdf_cylinder({}, {} - {}, {} - {}) - {}",
+                    position, length, fillet, radius, fillet, fillet
+                )
+            }
+            SDFVolume::Torus {
+                position,
+                major_radius,
+                minor_radius,
+            } => {
+                let args = [self.expr(major_radius), self.expr(minor_radius)];
+                self.primitive("sdf_torus", position, &args)
+            }
+            SDFVolume::Capsule {
+                position,
+                length,
+                radius,
+            } => {
+                let args = [self.expr(length), self.expr(radius)];
+                self.primitive("sdf_capsule", position, &args)
+            }
+            SDFVolume::Cone {
+                position,
+                height,
+                radius,
+            } => {
+                let args = [self.expr(height), self.expr(radius)];
+                self.primitive("sdf_cone", position, &args)
+            }
+            SDFVolume::Plane {
+                position,
+                normal,
+                offset,
+            } => {
+                let args = [self.expr(normal), self.expr(offset)];
+                self.primitive("sdf_plane", position, &args)
+            }
+            SDFVolume::Ellipsoid { position, radii } => {
+                let args = [self.expr(radii)];
+                self.primitive("sdf_ellipsoid", position, &args)
+            }
+            SDFVolume::HexPrism {
+                position,
+                radius,
+                length,
+            } => {
+                let args = [self.expr(radius), self.expr(length)];
+                self.primitive("sdf_hex_prism", position, &args)
+            }
+            SDFVolume::TriPrism {
+                position,
+                radius,
+                length,
+            } => {
+                let args = [self.expr(radius), self.expr(length)];
+                self.primitive("sdf_tri_prism", position, &args)
+            }
+            SDFVolume::RoundedCone {
+                position,
+                bottom_radius,
+                top_radius,
+                height,
+            } => {
+                let args = [
+                    self.expr(bottom_radius),
+                    self.expr(top_radius),
+                    self.expr(height),
+                ];
+                self.primitive("sdf_rounded_cone", position, &args)
+            }
+            SDFVolume::Octahedron { position, size } => {
+                let args = [self.expr(size)];
+                self.primitive("sdf_octahedron", position, &args)
+            }
+            SDFVolume::Link {
+                position,
+                length,
+                major_radius,
+                minor_radius,
+            } => {
+                let args = [
+                    self.expr(length),
+                    self.expr(major_radius),
+                    self.expr(minor_radius),
+                ];
+                self.primitive("sdf_link", position, &args)
+            }
+            SDFVolume::Pyramid {
+                position,
+                base,
+                height,
+            } => {
+                let args = [self.expr(base), self.expr(height)];
+                self.primitive("sdf_pyramid", position, &args)
+            }
+            SDFVolume::Union { a, b } => self.op("op_union", a, b),
+            SDFVolume::SmoothUnion { a, b, k } => self.smooth_op("op_union_smooth", a, b, k),
+            SDFVolume::Diff { a, b } => self.op("op_diff", a, b),
+            SDFVolume::SmoothDiff { a, b, k } => self.smooth_op("op_diff_smooth", a, b, k),
+            SDFVolume::Intersect { a, b } => self.op("op_int", a, b),
+            SDFVolume::SmoothIntersect { a, b, k } => self.smooth_op("op_int_smooth", a, b, k),
+            SDFVolume::Round { volume, radius } => {
+                let volume = self.volume(volume);
+                format!("op_round({}, {})", volume, self.expr(radius))
+            }
+            SDFVolume::Shell { volume, thickness } => {
+                let volume = self.volume(volume);
+                format!("op_shell({}, {})", volume, self.expr(thickness))
+            }
+            SDFVolume::Hollow { volume } => format!("op_hollow({})", self.volume(volume)),
+            SDFVolume::Extrude {
+                position,
+                shape,
+                height,
+                rounding,
+            } => {
+                let position = self.position(position);
+                let shape = self.shape(shape, &format!("({}).xy", position));
+                let height = self.expr(height);
+                let rounding = self.expr(rounding);
+                format!(
+                    "op_extrude({}, ({}).z, {}, {})",
+                    shape, position, height, rounding
+                )
+            }
+            SDFVolume::Revolve {
+                position,
+                shape,
+                axis,
+                offset,
+            } => {
+                let position = self.position(position);
+                let axis = self.expr(axis);
+                let point = format!("revolve({}, {}, {})", position, axis, self.expr(offset));
+                self.shape(shape, &point)
+            }
+            SDFVolume::Material { volume, .. } => self.volume(volume),
+            SDFVolume::Call {
+                function,
+                position,
+                args,
+                body,
+            } => {
+                let name = self.function(function, body, args, false);
+                self.call(&name, position, args)
+            }
+            SDFVolume::Glsl {
+                function,
+                position,
+                args,
+            } => {
+                if !self.glsl.contains(function) {
+                    self.glsl.push(function.clone());
+                }
+                self.call(&function.name, position, args)
+            }
+        }
+    }
+
+    /// GLSL `vec4` surface expression holding the distance, the IDs of the
+    /// two materials blended between and the blend factor. Booleans keep the
+    /// material of the winning side and smooth ones blend across the fillet.
+    /// Material ID 0 is the default material.
+    pub fn surface(&mut self, volume: &SDFVolume) -> String {
+        match volume {
+            _ if !volume.has_material() => format!("op_material({}, 0.0)", self.volume(volume)),
+            SDFVolume::Material { volume, material } => {
+                let id = match self.materials.iter().position(|m| m == material) {
+                    Some(i) => i + 1,
+                    None => {
+                        self.materials.push(material.clone());
+                        self.materials.len()
+                    }
+                };
+                format!("op_material({}, {}.0)", self.volume(volume), id)
+            }
+            SDFVolume::Union { a, b } => self.surface_op("op_union", a, b, None),
+            SDFVolume::SmoothUnion { a, b, k } => self.surface_op("op_union_smooth", a, b, Some(k)),
+            SDFVolume::Diff { a, b } => self.surface_op("op_diff", a, b, None),
+            SDFVolume::SmoothDiff { a, b, k } => self.surface_op("op_diff_smooth", a, b, Some(k)),
+            SDFVolume::Intersect { a, b } => self.surface_op("op_int", a, b, None),
+            SDFVolume::SmoothIntersect { a, b, k } => {
+                self.surface_op("op_int_smooth", a, b, Some(k))
+            }
+            SDFVolume::Round { volume, radius } => {
+                let volume = self.surface(volume);
+                format!("op_round({}, {})", volume, self.expr(radius))
+            }
+            SDFVolume::Shell { volume, thickness } => {
+                let volume = self.surface(volume);
+                format!("op_shell({}, {})", volume, self.expr(thickness))
+            }
+            SDFVolume::Hollow { volume } => format!("op_hollow({})", self.surface(volume)),
+            // Materials are looked up by ID outside the function, where its
+            // arguments are out of reach
+            SDFVolume::Call {
+                position,
+                args,
+                body,
+                ..
+            } if body.any_material(&|m| m.color.uses_args() || m.roughness.uses_args()) => {
+                self.surface(&body.substitute(position, args))
+            }
+            SDFVolume::Call {
+                function,
+                position,
+                args,
+                body,
+            } => {
+                let name = self.function(function, body, args, true);
+                self.call(&name, position, args)
+            }
+            // Primitives never contain a material
+            _ => format!("op_material({}, 0.0)", self.volume(volume)),
+        }
+    }
+
+    /// Definition of `material scene_material(float id)` for the materials
+    /// met by [`Self::surface`] so far
+    pub fn material_function(&mut self) -> String {
+        let mut function =
+            "material scene_material(float id) {\n    switch (int(id)) {\n".to_string();
+        let materials = std::mem::take(&mut self.materials);
+        for (i, material) in materials.iter().enumerate() {
+            let material = self.material(material);
+            function.push_str(&format!("    case {}: return {};\n", i + 1, material));
+        }
+        // The default material never changes, so it is not worth any slots
+        let default = Lowering::inline().material(&Material::default());
+        function.push_str(&format!("    default: return {};\n    }}\n}}\n", default));
+        self.materials = materials;
+        function
+    }
+
+    /// Definitions of the GLSL functions called and the group functions
+    /// generated so far, callees first
+    pub fn functions(&self) -> String {
+        let glsl = self.glsl.iter().map(|function| function.source.as_str());
+        let groups = self.functions.iter();
+        glsl.chain(groups.map(|function| function.source.as_str()))
+            .collect()
+    }
+
+    /// Name of the function returning the distance, or the surface with
+    /// `surface`, of `body` called with `args`, generating it on first use
+    fn function(&mut self, group: &str, body: &SDFVolume, args: &[Expr], surface: bool) -> String {
+        let args: Vec<ValueKind> = args.iter().map(Expr::kind).collect();
+        let existing = self
+            .functions
+            .iter()
+            .find(|f| f.surface == surface && f.args == args && f.body == *body);
+        if let Some(function) = existing {
+            return function.name.clone();
+        }
+        let (kind, lowered) = if surface {
+            ("vec4", self.surface(body))
+        } else {
+            ("float", self.volume(body))
+        };
+        let name = format!(
+            "group_{}_{}{}",
+            glsl_identifier(group),
+            self.functions.len(),
+            if surface { "_surface" } else { "" }
+        );
+        let params: String = args
+            .iter()
+            .enumerate()
+            .map(|(index, kind)| format!(", {} a{}", kind.glsl(), index))
+            .collect();
+        let source = format!(
+            "{} {}(vec3 p{}) {{\n    return {};\n}}\n\n",
+            kind, name, params, lowered
+        );
+        self.functions.push(Function {
+            body: body.clone(),
+            args,
+            surface,
+            name: name.clone(),
+            source,
+        });
+        name
+    }
+
+    fn call(&mut self, name: &str, position: &SDFPosition, args: &[Expr]) -> String {
+        let mut call = format!("{}({}", name, self.position(position));
+        for arg in args {
+            call.push_str(", ");
+            call.push_str(&self.expr(arg));
+        }
+        call.push(')');
+        call
+    }
+
+    fn material(&mut self, material: &Material) -> String {
+        let color = self.expr(&material.color);
+        let roughness = self.expr(&material.roughness);
+        format!(
+            "material({}, {}, {})",
+            color,
+            roughness,
+            material.matcap.index()
+        )
+    }
+
+    /// GLSL `float` distance expression for a profile sampled at the `vec2`
+    /// expression `point`
+    pub fn shape(&mut self, shape: &SDF2D, point: &str) -> String {
+        match shape {
+            SDF2D::Empty => EMPTY_DISTANCE.to_string(),
+            SDF2D::Circle { center, radius } => {
+                let center = self.expr(center);
+                format!(
+                    "sdf2d_circle({} - {}, {})",
+                    point,
+                    center,
+                    self.expr(radius)
+                )
+            }
+            SDF2D::Rectangle { center, size } => {
+                let center = self.expr(center);
+                format!(
+                    "sdf2d_rectangle({} - {}, {})",
+                    point,
+                    center,
+                    self.expr(size)
+                )
+            }
+            SDF2D::RoundedRectangle {
+                center,
+                size,
+                radius,
+            } => {
+                let center = self.expr(center);
+                let size = self.expr(size);
+                format!(
+                    "sdf2d_rounded_rectangle({} - {}, {}, {})",
+                    point,
+                    center,
+                    size,
+                    self.expr(radius)
+                )
+            }
+            SDF2D::Polygon {
+                center,
+                radius,
+                sides,
+            } => {
+                let center = self.expr(center);
+                let radius = self.expr(radius);
+                format!(
+                    "sdf2d_polygon({} - {}, {}, {})",
+                    point,
+                    center,
+                    radius,
+                    self.expr(sides)
+                )
+            }
+            SDF2D::Arc {
+                center,
+                radius,
+                angle,
+                thickness,
+            } => {
+                let center = self.expr(center);
+                let radius = self.expr(radius);
+                let angle = self.expr(angle);
+                format!(
+                    "sdf2d_arc({} - {}, {}, {}, {})",
+                    point,
+                    center,
+                    radius,
+                    angle,
+                    self.expr(thickness)
+                )
+            }
+            SDF2D::Segment { a, b, thickness } => {
+                let a = self.expr(a);
+                let b = self.expr(b);
+                format!(
+                    "sdf2d_segment({}, {}, {}, {})",
+                    point,
+                    a,
+                    b,
+                    self.expr(thickness)
+                )
+            }
+            SDF2D::Union { a, b } => self.shape_op("op_union", a, b, point),
+            SDF2D::Diff { a, b } => self.shape_op("op_diff", a, b, point),
+            SDF2D::Intersect { a, b } => self.shape_op("op_int", a, b, point),
+        }
+    }
+
+    /// Call to a primitive distance function with already lowered arguments
+    fn primitive(&mut self, name: &str, position: &SDFPosition, args: &[String]) -> String {
+        let position = self.position(position);
+        format!("{}({}, {})", name, position, args.join(", "))
+    }
+    fn op(&mut self, name: &str, a: &SDFVolume, b: &SDFVolume) -> String {
+        let a = self.volume(a);
+        let b = self.volume(b);
+        format!("{}({}, {})", name, a, b)
+    }
+    fn shape_op(&mut self, name: &str, a: &SDF2D, b: &SDF2D, point: &str) -> String {
+        let a = self.shape(a, point);
+        let b = self.shape(b, point);
+        format!("{}({}, {})", name, a, b)
+    }
+    fn smooth_op(&mut self, name: &str, a: &SDFVolume, b: &SDFVolume, k: &Expr) -> String {
+        let a = self.volume(a);
+        let b = self.volume(b);
+        format!("{}({}, {}, {})", name, a, b, self.expr(k))
+    }
+    fn surface_op(&mut self, name: &str, a: &SDFVolume, b: &SDFVolume, k: Option<&Expr>) -> String {
+        let a = self.surface(a);
+        let b = self.surface(b);
+        match k {
+            Some(k) => format!("{}({}, {}, {})", name, a, b, self.expr(k)),
+            None => format!("{}({}, {})", name, a, b),
+        }
+    }
+}
+
+/// `name` with everything GLSL does not allow in identifiers replaced
+fn glsl_identifier(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_lowercase()
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+/// GLSL builtins like `min` or `pow` need both operands to be of the same
+/// type, so a scalar operand of a vector operation is converted explicitly
+fn splat(glsl: String, expr: &Expr, kind: ValueKind) -> String {
+    if expr.kind() == kind {
+        glsl
+    } else {
+        format!("{}({})", kind.glsl(), glsl)
+    }
+}
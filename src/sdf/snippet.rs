@@ -0,0 +1,336 @@
+//! Distance functions written by hand in GLSL.
+//!
+//! A snippet is the body of a `float` function of the sample point `p` and
+//! the inputs declared for it. It is checked here before it is spliced into
+//! the scene, which catches what would otherwise break the code around it:
+//! unbalanced brackets, preprocessor directives and bodies without a
+//! `return`. Anything else is left to the GLSL compiler, whose messages are
+//! mapped back to the lines of the snippet by [`Snippet::errors`].
+use std::{
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+};
+
+use thiserror::Error;
+
+use super::{expr::ValueKind, ir::GlslFunction};
+
+/// Names an input can not take, the sample point and GLSL keywords that
+/// could appear in a declaration
+const RESERVED: &[&str] = &[
+    "p", "float", "int", "bool", "vec2", "vec3", "vec4", "mat2", "mat3", "mat4", "if", "else",
+    "for", "while", "do", "return", "break", "continue", "discard", "true", "false", "in", "out",
+    "inout", "const", "uniform", "struct", "void",
+];
+
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum SnippetError {
+    #[error("input '{0}' is not a type followed by a name")]
+    Declaration(String),
+    #[error("input type '{0}' is not float, vec2 or vec3")]
+    InputType(String),
+    #[error("input name '{0}' is reserved")]
+    Reserved(String),
+    #[error("input '{0}' is declared twice")]
+    Duplicate(String),
+    #[error("the body is empty")]
+    Empty,
+    #[error("line {line}: {message}")]
+    Body { line: usize, message: String },
+    #[error("the body never returns a distance")]
+    NoReturn,
+}
+
+/// Declared input of a snippet, a parameter of its function
+#[derive(Clone, Debug, PartialEq)]
+pub struct SnippetInput {
+    pub kind: ValueKind,
+    pub name: String,
+}
+
+/// Validated GLSL body together with its inputs
+#[derive(Clone, Debug, PartialEq)]
+pub struct Snippet {
+    pub inputs: Vec<SnippetInput>,
+    pub body: String,
+}
+
+/// Error reported by the GLSL compiler on a line of a snippet, 0 being its
+/// signature
+#[derive(Clone, Debug, PartialEq)]
+pub struct SnippetLineError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl Snippet {
+    /// Checks `inputs`, comma separated declarations like `float r, vec3 c`,
+    /// and the function `body`
+    pub fn parse(inputs: &str, body: &str) -> Result<Snippet, SnippetError> {
+        let inputs = parse_inputs(inputs)?;
+        check_body(body)?;
+        Ok(Snippet {
+            inputs,
+            body: body.trim_end().to_string(),
+        })
+    }
+
+    /// GLSL function evaluating the snippet. The name is derived from the
+    /// source, so copies of a snippet share one definition.
+    pub fn function(&self) -> GlslFunction {
+        let mut parameters = vec!["vec3 p".to_string()];
+        parameters.extend(
+            self.inputs
+                .iter()
+                .map(|input| format!("{} {}", input.kind.glsl(), input.name)),
+        );
+        let mut hasher = DefaultHasher::new();
+        parameters.hash(&mut hasher);
+        self.body.hash(&mut hasher);
+        let name = format!("snippet_{:016x}", hasher.finish());
+        // The body starts on the line after the signature, which is what
+        // `errors` relies on to number its lines
+        let source = format!(
+            "float {}({}) {{\n{}\n}}\n\n",
+            name,
+            parameters.join(", "),
+            self.body
+        );
+        GlslFunction { name, source }
+    }
+
+    /// Errors of the compiler `log` for `fragment` that fall into the
+    /// function of this snippet, numbered by the lines of the body
+    pub fn errors(&self, fragment: &str, log: &str) -> Vec<SnippetLineError> {
+        let function = self.function();
+        let Some(offset) = fragment.find(&function.source) else {
+            return Vec::new();
+        };
+        // GLSL numbers lines from 1
+        let start = fragment[..offset].matches('\n').count() + 1;
+        let lines = self.body.lines().count();
+        // Errors found at the closing brace, like a missing return, are
+        // shown on the last line of the body
+        let end = start + lines + 1;
+        compiler_errors(log)
+            .into_iter()
+            .filter(|(line, _)| (start..=end).contains(line))
+            .map(|(line, message)| SnippetLineError {
+                line: (line - start).min(lines),
+                message,
+            })
+            .collect()
+    }
+}
+
+/// Parses the input declarations of a snippet on their own, see [`Snippet::parse`]
+pub fn parse_inputs(text: &str) -> Result<Vec<SnippetInput>, SnippetError> {
+    let mut inputs: Vec<SnippetInput> = Vec::new();
+    for declaration in text.split(',').map(str::trim) {
+        if declaration.is_empty() && text.trim().is_empty() {
+            break;
+        }
+        let words: Vec<&str> = declaration.split_whitespace().collect();
+        let [typ, name] = words[..] else {
+            return Err(SnippetError::Declaration(declaration.to_string()));
+        };
+        let kind = match typ {
+            "float" => ValueKind::Scalar,
+            "vec2" => ValueKind::Vec2,
+            "vec3" => ValueKind::Vec3,
+            _ => return Err(SnippetError::InputType(typ.to_string())),
+        };
+        if !is_identifier(name) {
+            return Err(SnippetError::Declaration(declaration.to_string()));
+        }
+        if RESERVED.contains(&name) || name.starts_with("gl_") || name.starts_with("u_") {
+            return Err(SnippetError::Reserved(name.to_string()));
+        }
+        if inputs.iter().any(|input| input.name == name) {
+            return Err(SnippetError::Duplicate(name.to_string()));
+        }
+        inputs.push(SnippetInput {
+            kind,
+            name: name.to_string(),
+        });
+    }
+    Ok(inputs)
+}
+
+fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Walks the body outside of comments, checking that brackets match and
+/// never close the function early
+fn check_body(body: &str) -> Result<(), SnippetError> {
+    if body.trim().is_empty() {
+        return Err(SnippetError::Empty);
+    }
+    let error = |line: usize, message: String| SnippetError::Body { line, message };
+    let mut open: Vec<(char, usize)> = Vec::new();
+    let mut code = String::new();
+    let mut in_block_comment = false;
+    for (index, text) in body.lines().enumerate() {
+        let line = index + 1;
+        let mut chars = text.chars().peekable();
+        let mut start_of_line = true;
+        while let Some(c) = chars.next() {
+            if in_block_comment {
+                if c == '*' && chars.peek() == Some(&'/') {
+                    chars.next();
+                    in_block_comment = false;
+                }
+                continue;
+            }
+            match c {
+                '/' if chars.peek() == Some(&'/') => break,
+                '/' if chars.peek() == Some(&'*') => {
+                    chars.next();
+                    in_block_comment = true;
+                }
+                '#' if start_of_line => {
+                    return Err(error(
+                        line,
+                        "preprocessor directives are not allowed".into(),
+                    ))
+                }
+                '(' | '[' | '{' => open.push((c, line)),
+                ')' | ']' | '}' => {
+                    let expected = match c {
+                        ')' => '(',
+                        ']' => '[',
+                        _ => '{',
+                    };
+                    match open.pop() {
+                        Some((bracket, _)) if bracket == expected => {}
+                        Some((bracket, opened)) => {
+                            return Err(error(
+                                line,
+                                format!("'{}' closes '{}' opened on line {}", c, bracket, opened),
+                            ))
+                        }
+                        None => return Err(error(line, format!("unmatched '{}'", c))),
+                    }
+                }
+                _ => {}
+            }
+            if !c.is_whitespace() {
+                start_of_line = false;
+            }
+            code.push(c);
+        }
+        code.push('\n');
+    }
+    if in_block_comment {
+        return Err(error(body.lines().count(), "unterminated comment".into()));
+    }
+    if let Some((bracket, line)) = open.pop() {
+        return Err(error(line, format!("'{}' is never closed", bracket)));
+    }
+    let returns = code
+        .split(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+        .any(|word| word == "return");
+    if !returns {
+        return Err(SnippetError::NoReturn);
+    }
+    Ok(())
+}
+
+/// Line and message of each error in a compiler log. Drivers format their
+/// logs differently, the common `0:12(5): error: ...` of Mesa,
+/// `ERROR: 0:12: ...` of AMD and Intel and `0(12) : error C0000: ...` of
+/// NVIDIA are understood.
+pub fn compiler_errors(log: &str) -> Vec<(usize, String)> {
+    log.lines().filter_map(compiler_error).collect()
+}
+
+fn compiler_error(text: &str) -> Option<(usize, String)> {
+    let text = text.trim();
+    let text = text.strip_prefix("ERROR:").unwrap_or(text).trim_start();
+    // Source string number, which is always 0 since the shader has one
+    let rest = text.strip_prefix('0')?;
+    let (line, rest) = if let Some(rest) = rest.strip_prefix(':') {
+        let end = rest.find(|c: char| !c.is_ascii_digit())?;
+        (&rest[..end], &rest[end..])
+    } else {
+        let rest = rest.strip_prefix('(')?;
+        let end = rest.find(')')?;
+        (&rest[..end], &rest[end + 1..])
+    };
+    let line = line.parse().ok()?;
+    // Skip the column if any, then the separator before the message
+    let rest = match rest.strip_prefix('(') {
+        Some(rest) => &rest[rest.find(')')? + 1..],
+        None => rest,
+    };
+    let message = rest.trim_start_matches([' ', ':']).trim();
+    if message.starts_with("warning") {
+        return None;
+    }
+    let message = message.strip_prefix("error:").unwrap_or(message).trim();
+    Some((line, message.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{compiler_errors, Snippet, SnippetError};
+    use crate::sdf::expr::ValueKind;
+
+    #[test]
+    fn snippet_is_validated() {
+        let snippet = Snippet::parse("float r, vec3 c", "return length(p - c) - r;").unwrap();
+        assert_eq!(snippet.inputs[1].kind, ValueKind::Vec3);
+        let function = snippet.function();
+        assert!(function.source.starts_with(&format!(
+            "float {}(vec3 p, float r, vec3 c) {{\n",
+            function.name
+        )));
+        assert_eq!(
+            Snippet::parse("float p", "return 0.0;"),
+            Err(SnippetError::Reserved("p".to_string()))
+        );
+        assert_eq!(
+            Snippet::parse("", "float d = 1.0;\n}\nreturn d;"),
+            Err(SnippetError::Body {
+                line: 2,
+                message: "unmatched '}'".to_string()
+            })
+        );
+        assert_eq!(
+            Snippet::parse("", "// return 1.0;\nlength(p);"),
+            Err(SnippetError::NoReturn)
+        );
+    }
+
+    #[test]
+    fn compiler_errors_map_to_snippet_lines() {
+        let snippet = Snippet::parse("float r", "float d = length(p) - r\nreturn d;").unwrap();
+        let fragment = format!(
+            "#version 330\n\n{}float scene(vec3 p) {{",
+            snippet.function().source
+        );
+        assert_eq!(
+            compiler_errors(
+                "ERROR: 0:7: 'x' : undeclared identifier\n0(4) : error C0000: syntax error"
+            ),
+            vec![
+                (7, "'x' : undeclared identifier".to_string()),
+                (4, "error C0000: syntax error".to_string())
+            ]
+        );
+        let errors = snippet.errors(
+            &fragment,
+            "0:5(1): error: syntax error, unexpected RETURN\n\
+             0:6(1): error: function does not return a value\n\
+             0:9(2): error: unrelated",
+        );
+        assert_eq!(errors.len(), 2);
+        assert_eq!(errors[0].line, 2);
+        assert_eq!(errors[0].message, "syntax error, unexpected RETURN");
+        // The closing brace counts as the last line of the body
+        assert_eq!(errors[1].line, 2);
+    }
+}
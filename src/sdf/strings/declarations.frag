@@ -3,6 +3,12 @@ struct marcher {
     vec3 normal;
     vec3 pos;
     float dist;
+    vec4 surface;
+};
+struct material {
+    vec3 color;
+    float roughness;
+    int matcap;
 };
 struct camera {
     vec3 pos;
@@ -18,16 +24,48 @@ float srgb_to_linear (float srgb);
 // math functions
 float vmax(vec3 v);
 float vmin(vec3 v);
+float dot2(vec2 v);
+float dot2(vec3 v);
 
 // space operations
 vec3 translate(vec3 p, vec3 translation);
 vec3 rotate(vec3 p, vec3 rotation);
 vec3 scale(vec3 p, vec3 scale);
+vec3 repeat(vec3 p, vec3 spacing);
+vec3 repeat_limited(vec3 p, vec3 spacing, vec3 count);
+vec3 mirror(vec3 p, vec3 normal, float offset);
+vec3 repeat_polar(vec3 p, vec3 axis, float count);
+vec3 twist(vec3 p, float rate);
+vec3 bend(vec3 p, float rate);
+vec3 taper(vec3 p, float rate);
+vec3 elongate(vec3 p, vec3 size);
 
 // sdf primatives
 float sdf_sphere(vec3 p, float r);
 float sdf_box (vec3 p, vec3 r);
 float sdf_cylinder(vec3 p, float height, float radius);
+float sdf_torus(vec3 p, float major_radius, float minor_radius);
+float sdf_capsule(vec3 p, float height, float radius);
+float sdf_capped_cone(vec3 p, float height, float r1, float r2);
+float sdf_cone(vec3 p, float height, float radius);
+float sdf_plane(vec3 p, vec3 normal, float offset);
+float sdf_ellipsoid(vec3 p, vec3 r);
+float sdf_hex_prism(vec3 p, float radius, float height);
+float sdf_tri_prism(vec3 p, float radius, float height);
+float sdf_rounded_cone(vec3 p, float r1, float r2, float height);
+float sdf_octahedron(vec3 p, float size);
+float sdf_link(vec3 p, float len, float major_radius, float minor_radius);
+float sdf_pyramid(vec3 p, float base, float height);
+
+// 2d sdf primatives
+float sdf2d_circle(vec2 p, float radius);
+float sdf2d_rectangle(vec2 p, vec2 size);
+float sdf2d_rounded_rectangle(vec2 p, vec2 size, float radius);
+float sdf2d_polygon(vec2 p, float radius, float sides);
+float sdf2d_arc(vec2 p, float radius, float angle, float thickness);
+float sdf2d_segment(vec2 p, vec2 a, vec2 b, float thickness);
+float op_extrude(float d, float z, float height, float rounding);
+vec2 revolve(vec3 p, vec3 axis, float offset);
 
 // sdf operations
 float op_union_smooth(float d1, float d2, float k);
@@ -36,6 +74,25 @@ float op_diff_smooth(float d1, float d2, float k);
 float op_diff(float d1, float d2);
 float op_int_smooth(float d1, float d2, float k);
 float op_int(float d1, float d2);
+float op_round(float d, float radius);
+float op_shell(float d, float thickness);
+float op_hollow(float d);
+vec4  op_union_smooth(vec4 a, vec4 b, float k);
+vec4  op_union(vec4 a, vec4 b);
+vec4  op_diff_smooth(vec4 a, vec4 b, float k);
+vec4  op_diff(vec4 a, vec4 b);
+vec4  op_int_smooth(vec4 a, vec4 b, float k);
+vec4  op_int(vec4 a, vec4 b);
+vec4  op_round(vec4 s, float radius);
+vec4  op_shell(vec4 s, float thickness);
+vec4  op_hollow(vec4 s);
+
+// materials, surfaces are vec4(distance, material a, material b, blend from a to b)
+vec4  op_material(float d, float id);
+float dominant_material(vec4 s);
+vec3  sample_matcap(int matcap, vec2 uv, float lod);
+vec3  shade_material(material m, vec2 uv);
+vec3  shade_surface(vec4 s, vec2 uv);
 
 // ray marching
 marcher ray_march(vec3 ro, vec3 rd);
@@ -49,4 +106,6 @@ camera cam_zoom(camera cam);
 float  dep(float d, float n, float f);
 
 // scene
-float scene(vec3 p);
+float    scene(vec3 p);
+vec4     scene_surface(vec3 p);
+material scene_material(float id);
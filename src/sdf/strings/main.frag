@@ -18,9 +18,10 @@ void main()
 
 
 
-    vec3 normal = ray_march(cam.pos, cam.ray).normal;
-    vec3 pos = ray_march(cam.pos, cam.ray).pos;
-    float dist = ray_march(cam.pos, cam.ray).dist;
+    marcher hit = ray_march(cam.pos, cam.ray);
+    vec3 normal = hit.normal;
+    vec3 pos = hit.pos;
+    float dist = hit.dist;
     float near = 0.01;
     float far = 100.0;
     o_color = vec4((dep(dist, near, far)).xxx, 1.0);
@@ -36,8 +37,7 @@ void main()
     float xg = dot(cross(-cam.ray, cam.right), normal) / 2.0 + 0.5;
     vec3 t_normals = vec3(xr, xg, xb);
     vec4 mat = vec4(xr, xg, xb, 1.0);
-    o_color = texture(u_matcap, vec2(t_normals.x, -t_normals.y+1.0));
+    o_color = vec4(shade_surface(hit.surface, vec2(t_normals.x, -t_normals.y+1.0)), 1.0);
     o_color = vec4(linear_to_srgb(o_color.x), linear_to_srgb(o_color.y), linear_to_srgb(o_color.z), 1.0);
-    o_color = vec4(t_normals, 1.0);
 }
 
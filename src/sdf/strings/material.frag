@@ -0,0 +1,28 @@
+vec4 op_material(float d, float id) {
+    return vec4(d, id, id, 0.0);
+}
+float dominant_material(vec4 s) {
+    return s.w < 0.5 ? s.y : s.z;
+}
+vec3 sample_matcap(int matcap, vec2 uv, float lod) {
+    // sampler arrays can only be indexed by constants
+    switch (matcap) {
+    case 1: return textureLod(u_matcaps[1], uv, lod).rgb;
+    case 2: return textureLod(u_matcaps[2], uv, lod).rgb;
+    case 3: return textureLod(u_matcaps[3], uv, lod).rgb;
+    case 4: return textureLod(u_matcaps[4], uv, lod).rgb;
+    case 5: return textureLod(u_matcaps[5], uv, lod).rgb;
+    case 6: return textureLod(u_matcaps[6], uv, lod).rgb;
+    case 7: return textureLod(u_matcaps[7], uv, lod).rgb;
+    default: return textureLod(u_matcaps[0], uv, lod).rgb;
+    }
+}
+vec3 shade_material(material m, vec2 uv) {
+    const float MAXIMUM_LOD = 6.0;
+    return m.color * sample_matcap(m.matcap, uv, clamp(m.roughness, 0.0, 1.0) * MAXIMUM_LOD);
+}
+vec3 shade_surface(vec4 s, vec2 uv) {
+    vec3 a = shade_material(scene_material(s.y), uv);
+    vec3 b = shade_material(scene_material(s.z), uv);
+    return mix(a, b, s.w);
+}
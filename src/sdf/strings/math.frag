@@ -4,3 +4,9 @@ float vmax(vec3 v) {
 float vmin(vec3 v) {
     return min(min(v.x, v.y), v.z);
 }
+float dot2(vec2 v) {
+    return dot(v, v);
+}
+float dot2(vec3 v) {
+    return dot(v, v);
+}
@@ -19,3 +19,41 @@ float op_int_smooth(float d1, float d2, float k) {
 float op_int(float d1, float d2) {
     return max(d1, d2);
 }
+float op_round(float d, float radius) {
+    return d - radius;
+}
+float op_shell(float d, float thickness) {
+    return abs(d) - thickness;
+}
+float op_hollow(float d) {
+    return abs(d);
+}
+vec4 op_union_smooth(vec4 a, vec4 b, float k) {
+    float h = clamp( 0.5 + 0.5*(b.x-a.x)/k, 0.0, 1.0 );
+    return vec4(op_union_smooth(a.x, b.x, k), dominant_material(b), dominant_material(a), h);
+}
+vec4 op_union(vec4 a, vec4 b) {
+    return a.x < b.x ? a : b;
+}
+vec4 op_diff_smooth(vec4 a, vec4 b, float k) {
+    return vec4(op_diff_smooth(a.x, b.x, k), a.yzw);
+}
+vec4 op_diff(vec4 a, vec4 b) {
+    return vec4(op_diff(a.x, b.x), a.yzw);
+}
+vec4 op_int_smooth(vec4 a, vec4 b, float k) {
+    float h = clamp( 0.5 - 0.5*(b.x-a.x)/k, 0.0, 1.0 );
+    return vec4(op_int_smooth(a.x, b.x, k), dominant_material(b), dominant_material(a), h);
+}
+vec4 op_int(vec4 a, vec4 b) {
+    return a.x > b.x ? a : b;
+}
+vec4 op_round(vec4 s, float radius) {
+    return vec4(op_round(s.x, radius), s.yzw);
+}
+vec4 op_shell(vec4 s, float thickness) {
+    return vec4(op_shell(s.x, thickness), s.yzw);
+}
+vec4 op_hollow(vec4 s) {
+    return vec4(op_hollow(s.x), s.yzw);
+}
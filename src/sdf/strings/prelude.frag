@@ -14,7 +14,8 @@ uniform vec3      u_cam_translation;
 uniform float     u_fov;                             
 
 uniform float     u_fillet;                          
-uniform sampler2D u_matcap;                          
+uniform float     u_time;                            
+uniform sampler2D u_matcaps[8];                      
 
 
 // Outputs
@@ -10,3 +10,99 @@ float sdf_cylinder(vec3 p, float height, float radius) {
   vec2 d = abs(vec2(length(p.xz),p.y)) - vec2(radius,height);
   return min(max(d.x,d.y),0.0) + length(max(d,0.0));
 }
+
+float sdf_torus(vec3 p, float major_radius, float minor_radius) {
+    vec2 q = vec2(length(p.xz) - major_radius, p.y);
+    return length(q) - minor_radius;
+}
+
+float sdf_capsule(vec3 p, float height, float radius) {
+    p.y -= clamp(p.y, -height, height);
+    return length(p) - radius;
+}
+
+float sdf_capped_cone(vec3 p, float height, float r1, float r2) {
+    vec2 q = vec2(length(p.xz), p.y);
+    vec2 k1 = vec2(r2, height);
+    vec2 k2 = vec2(r2 - r1, 2.0 * height);
+    vec2 ca = vec2(q.x - min(q.x, (q.y < 0.0) ? r1 : r2), abs(q.y) - height);
+    vec2 cb = q - k1 + k2 * clamp(dot(k1 - q, k2) / dot2(k2), 0.0, 1.0);
+    float s = (cb.x < 0.0 && ca.y < 0.0) ? -1.0 : 1.0;
+    return s * sqrt(min(dot2(ca), dot2(cb)));
+}
+
+float sdf_cone(vec3 p, float height, float radius) {
+    return sdf_capped_cone(p, height, radius, 0.0);
+}
+
+float sdf_plane(vec3 p, vec3 normal, float offset) {
+    return dot(p, normalize(normal)) - offset;
+}
+
+float sdf_ellipsoid(vec3 p, vec3 r) {
+    float k0 = length(p / r);
+    float k1 = length(p / (r * r));
+    return k0 * (k0 - 1.0) / k1;
+}
+
+float sdf_hex_prism(vec3 p, float radius, float height) {
+    const vec3 k = vec3(-0.8660254, 0.5, 0.57735);
+    p = abs(p);
+    p.xy -= 2.0 * min(dot(k.xy, p.xy), 0.0) * k.xy;
+    vec2 d = vec2(length(p.xy - vec2(clamp(p.x, -k.z * radius, k.z * radius), radius)) * sign(p.y - radius),
+                  p.z - height);
+    return min(max(d.x, d.y), 0.0) + length(max(d, 0.0));
+}
+
+float sdf_tri_prism(vec3 p, float radius, float height) {
+    vec3 q = abs(p);
+    return max(q.z - height, max(q.x * 0.866025 + p.y * 0.5, -p.y) - radius * 0.5);
+}
+
+float sdf_rounded_cone(vec3 p, float r1, float r2, float height) {
+    // a cone of no height is a sphere, keep the slope finite
+    height = max(height, 1e-4);
+    float b = (r1 - r2) / height;
+    float a = sqrt(1.0 - b * b);
+    vec2 q = vec2(length(p.xz), p.y);
+    float k = dot(q, vec2(-b, a));
+    if (k < 0.0) return length(q) - r1;
+    if (k > a * height) return length(q - vec2(0.0, height)) - r2;
+    return dot(q, vec2(a, b)) - r1;
+}
+
+float sdf_octahedron(vec3 p, float size) {
+    p = abs(p);
+    float m = p.x + p.y + p.z - size;
+    vec3 q;
+    if (3.0 * p.x < m) q = p.xyz;
+    else if (3.0 * p.y < m) q = p.yzx;
+    else if (3.0 * p.z < m) q = p.zxy;
+    else return m * 0.57735027;
+    float k = clamp(0.5 * (q.z - q.y + size), 0.0, size);
+    return length(vec3(q.x, q.y - size + k, q.z - k));
+}
+
+float sdf_link(vec3 p, float len, float major_radius, float minor_radius) {
+    vec3 q = vec3(p.x, max(abs(p.y) - len, 0.0), p.z);
+    return length(vec2(length(q.xy) - major_radius, q.z)) - minor_radius;
+}
+
+float sdf_pyramid(vec3 p, float base, float height) {
+    // unit base pyramid scaled to the requested base width, which must not
+    // be zero
+    base = max(base, 1e-4);
+    p /= base;
+    float h = height / base;
+    float m2 = h * h + 0.25;
+    p.xz = abs(p.xz);
+    p.xz = (p.z > p.x) ? p.zx : p.xz;
+    p.xz -= 0.5;
+    vec3 q = vec3(p.z, h * p.y - 0.5 * p.x, h * p.x + 0.5 * p.y);
+    float s = max(-q.x, 0.0);
+    float t = clamp((q.y - 0.5 * p.z) / (m2 + 0.25), 0.0, 1.0);
+    float a = m2 * (q.x + s) * (q.x + s) + q.y * q.y;
+    float b = m2 * (q.x + 0.5 * t) * (q.x + 0.5 * t) + (q.y - m2 * t) * (q.y - m2 * t);
+    float d2 = min(q.y, -q.x * m2 - q.y * 0.5) > 0.0 ? 0.0 : min(a, b);
+    return base * sqrt((d2 + q.z * q.z) / m2) * sign(max(q.z, -p.y));
+}
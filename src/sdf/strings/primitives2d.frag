@@ -0,0 +1,54 @@
+
+float sdf2d_circle(vec2 p, float radius) {
+    return length(p) - radius;
+}
+
+float sdf2d_rectangle(vec2 p, vec2 size) {
+    vec2 d = abs(p) - size;
+    return length(max(d, 0.0)) + min(max(d.x, d.y), 0.0);
+}
+
+float sdf2d_rounded_rectangle(vec2 p, vec2 size, float radius) {
+    return sdf2d_rectangle(p, size - radius) - radius;
+}
+
+// regular polygon with its corners on a circle of `radius`
+float sdf2d_polygon(vec2 p, float radius, float sides) {
+    float an = PI / max(round(sides), 3.0);
+    vec2 acs = vec2(cos(an), sin(an));
+    float bn = mod(atan(p.x, p.y), 2.0 * an) - an;
+    p = length(p) * vec2(cos(bn), abs(sin(bn)));
+    p -= radius * acs;
+    p.y += clamp(-p.y, 0.0, radius * acs.y);
+    return length(p) * sign(p.x);
+}
+
+// arc of `angle` degrees opening symmetrically around the +y axis
+float sdf2d_arc(vec2 p, float radius, float angle, float thickness) {
+    float a = radians(clamp(angle, 0.0, 360.0)) * 0.5;
+    vec2 sc = vec2(sin(a), cos(a));
+    p.x = abs(p.x);
+    float d = (sc.y * p.x > sc.x * p.y) ? length(p - sc * radius) : abs(length(p) - radius);
+    return d - thickness;
+}
+
+float sdf2d_segment(vec2 p, vec2 a, vec2 b, float thickness) {
+    vec2 pa = p - a;
+    vec2 ba = b - a;
+    float h = clamp(dot(pa, ba) / max(dot(ba, ba), 1e-12), 0.0, 1.0);
+    return length(pa - ba * h) - thickness;
+}
+
+// `d` is the distance to the profile in the xy plane, the extrusion is centered on z = 0
+float op_extrude(float d, float z, float height, float rounding) {
+    vec2 w = vec2(d + rounding, abs(z) - height * 0.5 + rounding);
+    return min(max(w.x, w.y), 0.0) + length(max(w, 0.0)) - rounding;
+}
+
+// profile coordinates of `p` revolved around `axis`: the distance from the
+// axis less `offset` along x and the height along the axis along y
+vec2 revolve(vec3 p, vec3 axis, float offset) {
+    vec3 a = normalize(axis);
+    float h = dot(p, a);
+    return vec2(length(p - h * a) - offset, h);
+}
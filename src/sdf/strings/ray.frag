@@ -2,10 +2,15 @@ marcher ray_march(vec3 ro, vec3 rd)
 {
     float total_distance_traveled = 0.0;
     const int NUMBER_OF_STEPS = 48;
+    const int MAXIMUM_NUMBER_OF_STEPS = 512;
     const float MINIMUM_HIT_DISTANCE = 0.01;
     const float MAXIMUM_TRACE_DISTANCE = 20.0;
+    // deformed scenes overestimate distances, so steps are shortened accordingly
+    // and as many more are taken to reach as far
+    float step_scale = u_distortion > 0.0 ? 1.0 / u_distortion : 1.0;
+    int steps = min(int(ceil(float(NUMBER_OF_STEPS) * max(u_distortion, 1.0))), MAXIMUM_NUMBER_OF_STEPS);
 
-    for (int i = 0; i < NUMBER_OF_STEPS; ++i)
+    for (int i = 0; i < steps; ++i)
     {
         vec3 current_position = ro + total_distance_traveled * rd;
 
@@ -14,7 +19,7 @@ marcher ray_march(vec3 ro, vec3 rd)
         if (distance_to_closest < MINIMUM_HIT_DISTANCE) 
         {
             vec3 normal = scene_normal(current_position);
-            return marcher(normal, current_position, total_distance_traveled);
+            return marcher(normal, current_position, total_distance_traveled, scene_surface(current_position));
             vec3 light_position = vec3(2.0, 5.0, -3.0);
             vec3 direction_to_light = normalize(current_position - light_position);
 
@@ -27,10 +32,10 @@ marcher ray_march(vec3 ro, vec3 rd)
         {
             break;
         }
-        total_distance_traveled += distance_to_closest;
+        total_distance_traveled += distance_to_closest * step_scale;
     }
     discard;
-    return marcher(vec3(0.0), vec3(0.0), 0.0);
+    return marcher(vec3(0.0), vec3(0.0), 0.0, vec4(0.0));
 }
 
 vec3 scene_normal(vec3 p)
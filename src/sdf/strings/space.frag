@@ -24,3 +24,55 @@ vec3 scale(vec3 p, vec3 scale) {
                 0.0    , 0.0    , scale.z) * p;
 
 }
+// axes with a spacing of zero are not repeated
+vec3 repeat(vec3 p, vec3 spacing) {
+    vec3 s = max(spacing, vec3(1e-6));
+    vec3 q = p - s * round(p / s);
+    return mix(p, q, step(vec3(1e-6), spacing));
+}
+// `count` copies along each axis, centered on the origin
+vec3 repeat_limited(vec3 p, vec3 spacing, vec3 count) {
+    vec3 s = max(spacing, vec3(1e-6));
+    vec3 n = max(round(count), vec3(1.0)) - 1.0;
+    vec3 q = p + s * n * 0.5;
+    q -= s * clamp(round(q / s), vec3(0.0), n);
+    return mix(p, q, step(vec3(1e-6), spacing));
+}
+// points behind the plane are reflected onto the front side
+vec3 mirror(vec3 p, vec3 normal, float offset) {
+    vec3 n = normalize(normal);
+    return p - 2.0 * min(dot(p, n) - offset, 0.0) * n;
+}
+// `count` copies spaced evenly around `axis`, the first one in the direction of the local x axis
+vec3 repeat_polar(vec3 p, vec3 axis, float count) {
+    vec3 a = normalize(axis);
+    vec3 t = normalize(cross(abs(a.y) < 0.999 ? vec3(0.0, 1.0, 0.0) : vec3(0.0, 0.0, 1.0), a));
+    vec3 b = cross(a, t);
+    vec2 q = vec2(dot(p, t), dot(p, b));
+    float sector = 2.0 * PI / max(round(count), 1.0);
+    float angle = mod(atan(q.y, q.x) + sector * 0.5, sector) - sector * 0.5;
+    q = length(q) * vec2(cos(angle), sin(angle));
+    return q.x * t + q.y * b + dot(p, a) * a;
+}
+// deformations along the y axis, `rate` is in degrees per unit length
+vec3 twist(vec3 p, float rate) {
+    float a = radians(rate) * p.y;
+    float c = cos(a);
+    float s = sin(a);
+    return vec3(c * p.x - s * p.z, p.y, s * p.x + c * p.z);
+}
+vec3 bend(vec3 p, float rate) {
+    float a = radians(rate) * p.y;
+    float c = cos(a);
+    float s = sin(a);
+    return vec3(c * p.x - s * p.y, s * p.x + c * p.y, p.z);
+}
+// cross sections grow by `rate` per unit length up the y axis
+vec3 taper(vec3 p, float rate) {
+    float s = max(1.0 + rate * p.y, 0.05);
+    return vec3(p.x / s, p.y, p.z / s);
+}
+// stretches the shape by inserting `size` worth of space at its center on each axis
+vec3 elongate(vec3 p, vec3 size) {
+    return p - clamp(p, -size, size);
+}